@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC control plane is generated from its .proto, which needs `protoc`
+    // on PATH (or in $PROTOC). The client is only used by the tests.
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(true)
+        .compile_protos(&["proto/zeromcp.proto"], &["proto"])?;
+    Ok(())
+}
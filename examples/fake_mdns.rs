@@ -0,0 +1,101 @@
+//! Runs ZeroMCP against a fake mDNS browser that "discovers" a single canned
+//! service, instead of browsing the network.
+//!
+//! Point the service at a real MCP SSE server to see it connect:
+//! `cargo run --example fake_mdns -- 127.0.0.1 8000`
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use mdns_sd::{Receiver, ServiceEvent, ServiceInfo};
+use rmcp::service::QuitReason;
+use std::{
+    env,
+    sync::{Arc, Mutex},
+};
+use tracing::info;
+use zeromcp::{
+    DiscoveredService, MdnsBrowser, ServiceEventHandler, UserInputProvider, ZeroClient, ZeroConfig,
+    ZeroHandler,
+};
+
+const CONFIG: &str = r#"
+[[service_mapping]]
+zeroconf_service = "_mcp._tcp.local."
+name = "Fake MCP"
+protocol = "sse"
+url = "http://{{service.hostname}}:{{service.port}}/sse"
+"#;
+
+/// Resolves `service` for browses of its type, and nothing else.
+struct FakeBrowser {
+    service: ServiceInfo,
+    // Keeps the channels open: a disconnected receiver is browsed again.
+    senders: Mutex<Vec<flume::Sender<ServiceEvent>>>,
+}
+
+impl MdnsBrowser for FakeBrowser {
+    fn browse(&self, service_type: &str) -> Result<Receiver<ServiceEvent>> {
+        let (tx, rx) = flume::unbounded();
+        if self.service.get_type() == service_type {
+            tx.send(ServiceEvent::ServiceResolved(self.service.clone()))?;
+        }
+        self.senders.lock().unwrap().push(tx);
+        Ok(rx)
+    }
+}
+
+struct MyApplication {
+    client: ZeroClient,
+}
+
+#[async_trait]
+impl ServiceEventHandler for MyApplication {
+    async fn on_service_started(&self, service: &DiscoveredService) {
+        match self.client.list_all_tools(&service.fullname).await {
+            Ok(tools) => info!("'{}' offers {} tools", service.fullname, tools.len()),
+            Err(e) => info!("Failed to list tools of '{}': {}", service.fullname, e),
+        }
+    }
+
+    async fn on_service_stopped(&self, service_name: &str, reason: QuitReason) {
+        info!("'{}' stopped: {:?}", service_name, reason);
+    }
+}
+
+#[async_trait]
+impl UserInputProvider for MyApplication {
+    async fn request_input(&self, service_name: &str, key: &str) -> Result<String> {
+        bail!(
+            "'{}' asked for '{}', which this example can't provide",
+            service_name,
+            key
+        )
+    }
+}
+
+impl ZeroHandler for MyApplication {}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let mut args = env::args().skip(1);
+    let host = args.next().unwrap_or_else(|| "127.0.0.1".to_string());
+    let port = args.next().map_or(Ok(8000), |p| p.parse())?;
+
+    let service = ServiceInfo::new("_mcp._tcp.local.", "fake", &host, "", port, None)?;
+    let browser = FakeBrowser {
+        service,
+        senders: Mutex::default(),
+    };
+
+    let config = ZeroConfig::from_reader(CONFIG.as_bytes())?;
+    let mcp =
+        zeromcp::start_with_mdns(config, |client| Arc::new(MyApplication { client }), browser)
+            .await?;
+
+    info!("Press Ctrl+C to exit.");
+    tokio::signal::ctrl_c().await?;
+    mcp.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use crate::{
+    SamplingHandler, ZeroHandler,
+    client::ZeroClient,
+    config::ZeroConfig,
+    manager::{ManagerHooks, ZeroMcp},
+    mdns::MdnsBrowser,
+    metrics::MetricsSink,
+    models::DiscoveredService,
+    state::ManagerState,
+};
+use anyhow::{Context, Result};
+use mdns_sd::ServiceDaemon;
+use std::{fmt, net::SocketAddr, sync::Arc};
+
+/// Creates the mDNS browser when the builder starts.
+type MakeBrowser<M> = Box<dyn FnOnce() -> Result<M> + Send>;
+
+/// A builder for configuring and starting ZeroMCP.
+///
+/// This is an alternative to [`crate::start`] for setups that need more than a
+/// config and a handler factory. `M` is the mDNS browser, a
+/// [`ServiceDaemon`] unless another is set with [`ZeroMcpBuilder::mdns`].
+pub struct ZeroMcpBuilder<M = ServiceDaemon> {
+    config: Option<ZeroConfig>,
+    handler: Option<Arc<dyn ZeroHandler>>,
+    mdns: MakeBrowser<M>,
+    status_addr: Option<SocketAddr>,
+    grpc_addr: Option<SocketAddr>,
+    grpc_token: Option<String>,
+    hooks: ManagerHooks,
+    state: Option<ManagerState>,
+}
+
+impl Default for ZeroMcpBuilder {
+    fn default() -> Self {
+        Self {
+            config: None,
+            handler: None,
+            mdns: Box::new(|| Ok(ServiceDaemon::new()?)),
+            status_addr: None,
+            grpc_addr: None,
+            grpc_token: None,
+            hooks: ManagerHooks::default(),
+            state: None,
+        }
+    }
+}
+
+impl<M> fmt::Debug for ZeroMcpBuilder<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZeroMcpBuilder")
+            .field("config", &self.config)
+            .field(
+                "handler",
+                &self.handler.as_ref().map(|_| "<dyn ZeroHandler>"),
+            )
+            .field("status_addr", &self.status_addr)
+            .field("grpc_addr", &self.grpc_addr)
+            .field(
+                "grpc_token",
+                &self.grpc_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("transform", &self.hooks.transform.as_ref().map(|_| "<fn>"))
+            .field(
+                "sampling",
+                &self
+                    .hooks
+                    .sampling
+                    .as_ref()
+                    .map(|_| "<dyn SamplingHandler>"),
+            )
+            .field(
+                "metrics",
+                &self.hooks.metrics.as_ref().map(|_| "<dyn MetricsSink>"),
+            )
+            // The state's cached inputs may hold secrets.
+            .field(
+                "state",
+                &self.state.as_ref().map(|state| state.services.len()),
+            )
+            .finish()
+    }
+}
+
+impl ZeroMcpBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<M: MdnsBrowser + 'static> ZeroMcpBuilder<M> {
+    /// Sets the configuration to run with.
+    pub fn config(mut self, config: ZeroConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Sets the handler to start with, for [`ZeroMcpBuilder::build`].
+    ///
+    /// Unlike the factory given to [`ZeroMcpBuilder::start`], the handler is
+    /// built before ZeroMCP starts, so it can be constructed however the
+    /// application likes, including asynchronously. It can reach the client
+    /// through [`ZeroMcp::client`] once started.
+    pub fn handler(mut self, handler: Arc<dyn ZeroHandler>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// Discovers services with `mdns` instead of a [`ServiceDaemon`], e.g. to
+    /// feed services from a test or another discovery mechanism.
+    pub fn mdns<N: MdnsBrowser + 'static>(self, mdns: N) -> ZeroMcpBuilder<N> {
+        ZeroMcpBuilder {
+            config: self.config,
+            handler: self.handler,
+            mdns: Box::new(move || Ok(mdns)),
+            status_addr: self.status_addr,
+            grpc_addr: self.grpc_addr,
+            grpc_token: self.grpc_token,
+            hooks: self.hooks,
+            state: self.state,
+        }
+    }
+
+    /// Reconnects to the services in `state`, as exported by
+    /// [`ZeroMcp::export_state`] before a restart, as soon as ZeroMCP starts.
+    /// See [`crate::start_from_state`] for how they're restored.
+    pub fn restore(mut self, state: ManagerState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Rewrites each discovered service before its templates are rendered.
+    ///
+    /// The callback runs synchronously on the discovery loop, right after the
+    /// service is resolved, so it should be cheap. It may freely change
+    /// `hostname`, `port`, `addresses` and `properties` (e.g. to apply a host
+    /// alias table or NAT port mapping). `fullname` identifies the service for
+    /// its whole lifecycle and is restored if the callback changes it.
+    /// Statically configured services are not transformed.
+    pub fn transform<T>(mut self, transform: T) -> Self
+    where
+        T: Fn(&mut DiscoveredService) + Send + Sync + 'static,
+    {
+        self.hooks.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Answers `sampling/createMessage` requests from services with `handler`.
+    ///
+    /// Registering a handler makes ZeroMCP advertise the sampling capability
+    /// during initialization. Without one, sampling requests are rejected.
+    pub fn sampling_handler<S>(mut self, handler: S) -> Self
+    where
+        S: SamplingHandler + 'static,
+    {
+        self.hooks.sampling = Some(Arc::new(handler));
+        self
+    }
+
+    /// Reports service discoveries, launches, failures and stops to `sink`.
+    ///
+    /// Pass an [`crate::InMemoryMetrics`] to read the counters back through
+    /// `ZeroClient::metrics`, or a custom sink to forward them elsewhere.
+    pub fn metrics_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.hooks.metrics = Some(sink);
+        self
+    }
+
+    /// Serves `/healthz`, `/services` and `/metrics` over HTTP on `addr`.
+    ///
+    /// Requires the `status-server` feature.
+    #[cfg(feature = "status-server")]
+    pub fn with_status_server(mut self, addr: SocketAddr) -> Self {
+        self.status_addr = Some(addr);
+        self
+    }
+
+    /// Serves the gRPC control plane defined in `proto/zeromcp.proto` on `addr`,
+    /// letting other processes list services and tools, call tools, stop
+    /// services and read their health.
+    ///
+    /// The server has no TLS. Without a token set by
+    /// [`ZeroMcpBuilder::with_grpc_token`] it is unauthenticated, so starting
+    /// fails unless `addr` is a loopback address.
+    ///
+    /// Requires the `grpc` feature.
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc_server(mut self, addr: SocketAddr) -> Self {
+        self.grpc_addr = Some(addr);
+        self
+    }
+
+    /// Requires gRPC requests to carry `token` as `authorization: Bearer <token>`
+    /// metadata, which lets the server bind non-loopback addresses. The token
+    /// travels in plain text, so keep the server on a trusted network.
+    ///
+    /// Requires the `grpc` feature.
+    #[cfg(feature = "grpc")]
+    pub fn with_grpc_token(mut self, token: impl Into<String>) -> Self {
+        self.grpc_token = Some(token.into());
+        self
+    }
+
+    /// Starts ZeroMCP with the handler set by [`ZeroMcpBuilder::handler`].
+    pub async fn build(mut self) -> Result<ZeroMcp> {
+        let handler = self.handler.take().context(
+            "ZeroMcpBuilder::build requires a handler; set one with `handler` or use `start`",
+        )?;
+        self.launch(move |_| handler).await
+    }
+
+    /// Starts ZeroMCP, constructing the handler from the client via `make_handler`.
+    pub async fn start<H, F>(self, make_handler: F) -> Result<ZeroMcp>
+    where
+        H: ZeroHandler + 'static,
+        F: FnOnce(ZeroClient) -> Arc<H>,
+    {
+        self.launch(move |client| make_handler(client) as Arc<dyn ZeroHandler>)
+            .await
+    }
+
+    async fn launch<F>(self, make_handler: F) -> Result<ZeroMcp>
+    where
+        F: FnOnce(ZeroClient) -> Arc<dyn ZeroHandler>,
+    {
+        let config = self.config.context("ZeroMcpBuilder requires a config")?;
+        let mdns = (self.mdns)()?;
+        let make_handler = move |client| std::future::ready(Ok(make_handler(client)));
+        #[allow(unused_mut)]
+        let mut zeromcp =
+            crate::manager::start_with_hooks(config, make_handler, mdns, self.hooks).await?;
+        if let Some(state) = self.state {
+            zeromcp.restore(state)?;
+        }
+
+        #[cfg(feature = "status-server")]
+        if let Some(addr) = self.status_addr {
+            let task = crate::status::serve(addr, zeromcp.client().clone()).await?;
+            zeromcp.background_tasks.push(task);
+        }
+        #[cfg(not(feature = "status-server"))]
+        let _ = self.status_addr;
+
+        #[cfg(feature = "grpc")]
+        if let Some(addr) = self.grpc_addr {
+            let task = crate::grpc::serve(addr, zeromcp.client().clone(), self.grpc_token).await?;
+            zeromcp.background_tasks.push(task);
+        }
+        #[cfg(not(feature = "grpc"))]
+        let _ = (self.grpc_addr, self.grpc_token);
+
+        Ok(zeromcp)
+    }
+}
@@ -1,12 +1,60 @@
-use crate::manager::ServiceMessage;
+use crate::{
+    config::{McpConfig, OverloadPolicy, ZeroConfig},
+    error::ZeroError,
+    health::{HealthStatus, HealthTracker},
+    history::TimeBucket,
+    manager::{ServiceMessage, StopResults, StreamingCall},
+    memory::{FragmentSender, MemoryStats},
+    metrics::{Metrics, MetricsSnapshot},
+    models::{Capability, DiscoveredService, StopOutcome, ToolsDiff, TrafficEvent},
+    state::ManagerState,
+    timing::{StartupHistogram, StartupTimings},
+    tool_result::{JsonDecoder, ToolResultDecoder},
+    utils::{bounded_fan_out, normalize_fullname, validate_fullname},
+};
 
 use anyhow::{Result, anyhow};
+use futures::stream::{self, BoxStream, StreamExt};
 use ractor::{ActorRef, RpcReplyPort, rpc::CallResult};
 use rmcp::{
-    model::{GetPromptRequestParam, GetPromptResult, Prompt, Resource, ResourceTemplate, Tool},
-    service::QuitReason,
+    model::{
+        CallToolRequest, CallToolRequestParam, CallToolResult, CancelledNotification,
+        CancelledNotificationMethod, CancelledNotificationParam, ClientRequest,
+        CompleteRequestParam, CompleteResult, GetPromptRequestParam, GetPromptResult, JsonObject,
+        Meta, Prompt, PromptArgument, ReadResourceResult, Resource, ResourceTemplate, Root,
+        ServerResult, Tool,
+    },
+    service::{PeerRequestOptions, QuitReason, ServiceError},
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
-use std::fmt::Debug;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, broadcast::error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// How many requests the fan-out methods keep in flight when no explicit
+/// `fan_out_concurrency` is given.
+pub const DEFAULT_FAN_OUT_CONCURRENCY: usize = 16;
+
+/// The `_meta` field that carries the key passed to
+/// [`ZeroClient::call_tool_idempotent`].
+pub const IDEMPOTENCY_KEY_META: &str = "idempotencyKey";
+
+/// Why a streaming tool call ended before its response arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Abandoned {
+    /// `tool_call_timeout_ms` passed.
+    TimedOut,
+    /// The caller dropped the stream.
+    Dropped,
+    /// The stream's unread fragments were shed to stay within the memory budget.
+    Shed,
+}
 
 /// The main client for interacting with discovered MCP services.
 ///
@@ -15,9 +63,158 @@ use std::fmt::Debug;
 #[derive(Clone, Debug)]
 pub struct ZeroClient {
     pub(crate) actor: ActorRef<ServiceMessage>,
+    pub(crate) admission: Option<Admission>,
+    pub(crate) inputs: Option<InputCache>,
+    /// Inputs supplied for prompt argument templates, kept apart from
+    /// `inputs` so they never fill in a launch template.
+    pub(crate) prompt_inputs: Option<InputCache>,
+    pub(crate) metrics: Metrics,
+}
+
+/// Bounds the requests pending on the manager, shared by every clone of a client.
+#[derive(Clone, Debug)]
+pub(crate) struct Admission {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    policy: OverloadPolicy,
+}
+
+impl Admission {
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        match self.policy {
+            OverloadPolicy::Wait => Ok(self.permits.clone().acquire_owned().await?),
+            OverloadPolicy::Error => self
+                .permits
+                .clone()
+                .try_acquire_owned()
+                .map_err(|_| ZeroError::Overloaded { limit: self.limit }.into()),
+        }
+    }
+}
+
+/// Template inputs supplied for each service, under `cache_inputs`, shared
+/// by every clone of a client.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InputCache(Arc<Mutex<HashMap<String, Map<String, Value>>>>);
+
+impl InputCache {
+    /// Returns the inputs remembered for `service_name`.
+    pub(crate) fn get(&self, service_name: &str) -> Option<Map<String, Value>> {
+        self.lock().get(service_name).cloned()
+    }
+
+    /// Adds the inputs remembered for `service_name` to a template context.
+    pub(crate) fn seed(&self, service_name: &str, ctx: &mut Value) {
+        if let Some(cached) = self.lock().get(service_name) {
+            seed_context(ctx, cached);
+        }
+    }
+
+    /// Remembers the inputs in a rendered template context.
+    pub(crate) fn store(&self, service_name: &str, ctx: &Value) {
+        self.insert(service_name, context_inputs(ctx));
+    }
+
+    /// Remembers `inputs` for `service_name`, unless there are none.
+    pub(crate) fn insert(&self, service_name: &str, inputs: Map<String, Value>) {
+        if !inputs.is_empty() {
+            self.lock().insert(service_name.to_string(), inputs);
+        }
+    }
+
+    /// Returns the inputs remembered for every service.
+    pub(crate) fn snapshot(&self) -> HashMap<String, Map<String, Value>> {
+        self.lock().clone()
+    }
+
+    /// Remembers inputs saved by an earlier manager, keeping any already
+    /// supplied to this one.
+    pub(crate) fn restore(&self, inputs: HashMap<String, Map<String, Value>>) {
+        let mut cached = self.lock();
+        for (service_name, inputs) in inputs {
+            cached.entry(service_name).or_insert(inputs);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Map<String, Value>>> {
+        // The map stays consistent even if a holder panicked.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Adds `inputs` to a template context, keeping the values it already has.
+pub(crate) fn seed_context(ctx: &mut Value, inputs: &Map<String, Value>) {
+    if let Some(ctx) = ctx.as_object_mut() {
+        for (key, value) in inputs {
+            ctx.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// Returns the inputs in a rendered template context, which is everything
+/// but `service`.
+pub(crate) fn context_inputs(ctx: &Value) -> Map<String, Value> {
+    ctx.as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| *key != "service")
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
 }
 
 impl ZeroClient {
+    pub(crate) fn new(actor: ActorRef<ServiceMessage>, config: &ZeroConfig) -> Self {
+        let admission = config.max_pending_requests.map(|limit| Admission {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            policy: config.overload_policy,
+        });
+        let inputs = config.cache_inputs.then(InputCache::default);
+        let prompt_inputs = config.cache_inputs.then(InputCache::default);
+        Self {
+            actor,
+            admission,
+            inputs,
+            prompt_inputs,
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Returns a clone for the manager's own traffic, which bypasses the
+    /// admission limit: shedding its launch bookkeeping or stops would skip
+    /// launches and leak child processes.
+    pub(crate) fn internal(&self) -> Self {
+        Self {
+            admission: None,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the lifecycle counters recorded by the configured metrics sink,
+    /// or `None` if it doesn't keep any (e.g. no sink was configured).
+    pub fn metrics(&self) -> Option<MetricsSnapshot> {
+        self.metrics.snapshot()
+    }
+
+    /// Forgets the template inputs remembered under `cache_inputs`, so the
+    /// next launch or templated prompt asks for them again, e.g. after a
+    /// token is rejected.
+    ///
+    /// # Arguments
+    /// * `service_name` - The service whose inputs to forget, or `None` for all services.
+    pub fn clear_input_cache(&self, service_name: Option<&str>) {
+        for inputs in self.inputs.iter().chain(&self.prompt_inputs) {
+            match service_name {
+                Some(name) => {
+                    inputs.lock().remove(&normalize_fullname(name));
+                }
+                None => inputs.lock().clear(),
+            }
+        }
+    }
+
     async fn call_actor<TRequest, TResponse>(
         &self,
         msg_builder: impl FnOnce(RpcReplyPort<Result<TResponse>>) -> TRequest,
@@ -27,6 +224,10 @@ impl ZeroClient {
         TResponse: Send + 'static + Debug,
         ServiceMessage: From<TRequest>,
     {
+        let _permit = match &self.admission {
+            Some(admission) => Some(admission.acquire().await?),
+            None => None,
+        };
         let rpc_result = self
             .actor
             .call(
@@ -52,12 +253,34 @@ impl ZeroClient {
 
     /// Lists all available tools for a given service.
     ///
+    /// A server that didn't advertise the tools capability isn't asked and has
+    /// no tools. The prompt and resource listings likewise come back empty for
+    /// servers without those capabilities.
+    ///
     /// # Arguments
     ///
     /// * `service_name` - The full name of the service (e.g., "MyTool._mcp._tcp.local.").
+    ///   The trailing dot is optional for this and every other method taking a service name.
     pub async fn list_all_tools(&self, service_name: impl Into<String>) -> Result<Vec<Tool>> {
         self.call_actor(|reply| ServiceMessage::ListAllTools {
-            service_name: service_name.into(),
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Lists a service's tools and reports what changed since the previous listing.
+    ///
+    /// The comparison is against the last listing fetched through this method or
+    /// [`list_all_tools`](Self::list_all_tools). If the service hasn't been
+    /// listed before, every tool is reported as added.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service.
+    pub async fn tools_diff(&self, service_name: impl Into<String>) -> Result<ToolsDiff> {
+        self.call_actor(|reply| ServiceMessage::ToolsDiff {
+            service_name: normalize_fullname(&service_name.into()),
             reply,
         })
         .await
@@ -70,7 +293,31 @@ impl ZeroClient {
     /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
     pub async fn list_all_prompts(&self, service_name: impl Into<String>) -> Result<Vec<Prompt>> {
         self.call_actor(|reply| ServiceMessage::ListAllPrompts {
-            service_name: service_name.into(),
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the arguments a prompt accepts, with their descriptions and
+    /// whether they're required, e.g. to render an input form before calling
+    /// [`get_prompt`](Self::get_prompt).
+    ///
+    /// Answered from the service's last prompt listing when it includes the
+    /// prompt; otherwise the prompts are listed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `prompt_name` - The name of the prompt.
+    pub async fn prompt_arguments(
+        &self,
+        service_name: impl Into<String>,
+        prompt_name: impl Into<String>,
+    ) -> Result<Vec<PromptArgument>> {
+        self.call_actor(|reply| ServiceMessage::PromptArguments {
+            service_name: normalize_fullname(&service_name.into()),
+            prompt_name: prompt_name.into(),
             reply,
         })
         .await
@@ -86,7 +333,65 @@ impl ZeroClient {
         service_name: impl Into<String>,
     ) -> Result<Vec<Resource>> {
         self.call_actor(|reply| ServiceMessage::ListAllResources {
-            service_name: service_name.into(),
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Reads the contents of a resource from a given service.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `uri` - The URI of the resource, as listed by `list_all_resources`.
+    pub async fn read_resource(
+        &self,
+        service_name: impl Into<String>,
+        uri: impl Into<String>,
+    ) -> Result<ReadResourceResult> {
+        self.call_actor(|reply| ServiceMessage::ReadResource {
+            service_name: normalize_fullname(&service_name.into()),
+            uri: uri.into(),
+            reply,
+        })
+        .await
+    }
+
+    /// Asks a service to send `resources/updated` notifications for a resource,
+    /// which are delivered to `on_resource_updated`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `uri` - The URI of the resource to watch.
+    pub async fn subscribe_resource(
+        &self,
+        service_name: impl Into<String>,
+        uri: impl Into<String>,
+    ) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::SubscribeResource {
+            service_name: normalize_fullname(&service_name.into()),
+            uri: uri.into(),
+            reply,
+        })
+        .await
+    }
+
+    /// Stops the notifications requested by `subscribe_resource`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `uri` - The URI of the resource to stop watching.
+    pub async fn unsubscribe_resource(
+        &self,
+        service_name: impl Into<String>,
+        uri: impl Into<String>,
+    ) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::UnsubscribeResource {
+            service_name: normalize_fullname(&service_name.into()),
+            uri: uri.into(),
             reply,
         })
         .await
@@ -102,7 +407,7 @@ impl ZeroClient {
         service_name: impl Into<String>,
     ) -> Result<Vec<ResourceTemplate>> {
         self.call_actor(|reply| ServiceMessage::ListAllResourceTemplates {
-            service_name: service_name.into(),
+            service_name: normalize_fullname(&service_name.into()),
             reply,
         })
         .await
@@ -120,13 +425,502 @@ impl ZeroClient {
         prompt_request: GetPromptRequestParam,
     ) -> Result<GetPromptResult> {
         self.call_actor(|reply| ServiceMessage::GetPrompt {
-            service_name: service_name.into(),
+            service_name: normalize_fullname(&service_name.into()),
+            prompt_request,
+            meta: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Gets a prompt, adding `meta` to the request's `_meta` over the
+    /// service's configured `request_meta`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `prompt_request` - The prompt name and its arguments.
+    /// * `meta` - Metadata for this request only, e.g. a trace ID.
+    pub async fn get_prompt_with_meta(
+        &self,
+        service_name: impl Into<String>,
+        prompt_request: GetPromptRequestParam,
+        meta: Meta,
+    ) -> Result<GetPromptResult> {
+        self.call_actor(|reply| ServiceMessage::GetPrompt {
+            service_name: normalize_fullname(&service_name.into()),
             prompt_request,
+            meta: Some(meta),
+            reply,
+        })
+        .await
+    }
+
+    /// Gets a prompt, rendering each argument as a template first, like
+    /// launch templates: against `{{service.*}}`, prompting through
+    /// `request_input_for` for other variables. Under `cache_inputs`, the
+    /// values supplied are reused by later prompts, but never by launches.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `prompt_name` - The name of the prompt.
+    /// * `arg_templates` - Argument names and the templates for their values.
+    pub async fn get_prompt_templated(
+        &self,
+        service_name: impl Into<String>,
+        prompt_name: impl Into<String>,
+        arg_templates: HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let service_name = normalize_fullname(&service_name.into());
+        let cache = self.prompt_inputs.as_ref();
+        let (arguments, inputs) = self
+            .call_actor(|reply| ServiceMessage::RenderTemplates {
+                service_name: service_name.clone(),
+                templates: arg_templates,
+                inputs: cache.and_then(|cache| cache.get(&service_name)),
+                reply,
+            })
+            .await?;
+        if let Some(cache) = cache {
+            cache.insert(&service_name, inputs);
+        }
+        let prompt_request = GetPromptRequestParam {
+            name: prompt_name.into(),
+            arguments: Some(
+                arguments
+                    .into_iter()
+                    .map(|(key, value)| (key, Value::String(value)))
+                    .collect(),
+            ),
+        };
+        self.get_prompt(service_name, prompt_request).await
+    }
+
+    /// Asks a service to suggest values for a prompt or resource template
+    /// argument, e.g. to autocomplete it in a UI.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The prompt or resource being filled in, and the argument's
+    ///   name and current value.
+    pub async fn complete(
+        &self,
+        service_name: impl Into<String>,
+        params: CompleteRequestParam,
+    ) -> Result<CompleteResult> {
+        self.call_actor(|reply| ServiceMessage::Complete {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            reply,
+        })
+        .await
+    }
+
+    /// Sends an MCP ping to a service, returning the round-trip time.
+    ///
+    /// This is a cheap way to check that a service is still responsive, e.g.
+    /// for a status indicator. The ping is bounded by the service's
+    /// `request_timeout_ms`, if configured, and doesn't wait for a
+    /// `max_concurrent_calls` slot.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    pub async fn ping(&self, service_name: impl Into<String>) -> Result<Duration> {
+        self.call_actor(|reply| ServiceMessage::Ping {
+            service_name: normalize_fullname(&service_name.into()),
+            timeout: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a tool on a given service.
+    ///
+    /// The call is bounded by the service's `tool_call_timeout_ms`, if configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    pub async fn call_tool(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+    ) -> Result<CallToolResult> {
+        self.call_actor(|reply| ServiceMessage::CallTool {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            timeout: None,
+            meta: None,
+            idempotency_key: None,
+            cancel: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a tool, adding `meta` to the request's `_meta` over the service's
+    /// configured `request_meta`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    /// * `meta` - Metadata for this call only, e.g. a trace ID or user context.
+    pub async fn call_tool_with_meta(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+        meta: Meta,
+    ) -> Result<CallToolResult> {
+        self.call_actor(|reply| ServiceMessage::CallTool {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            timeout: None,
+            meta: Some(meta),
+            idempotency_key: None,
+            cancel: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a tool on a given service with an explicit timeout.
+    ///
+    /// If the call doesn't complete within `timeout`, a cancellation is sent to the
+    /// server and the error downcasts to [`ZeroError::ToolCallTimeout`](crate::ZeroError::ToolCallTimeout).
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    /// * `timeout` - How long to wait for the tool to finish.
+    pub async fn call_tool_with_timeout(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+        timeout: Duration,
+    ) -> Result<CallToolResult> {
+        self.call_actor(|reply| ServiceMessage::CallTool {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            timeout: Some(timeout),
+            meta: None,
+            idempotency_key: None,
+            cancel: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a tool that the caller can abort by cancelling `cancel`.
+    ///
+    /// Cancelling sends the server a cancellation notification and fails the
+    /// call with [`ZeroError::ToolCallCancelled`](crate::ZeroError::ToolCallCancelled);
+    /// a call still waiting in the service's queue never reaches the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    /// * `timeout` - How long to wait for the tool to finish, overriding `tool_call_timeout_ms`.
+    /// * `cancel` - Aborts the call when cancelled.
+    pub async fn call_tool_cancellable(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+        timeout: Option<Duration>,
+        cancel: CancellationToken,
+    ) -> Result<CallToolResult> {
+        self.call_actor(|reply| ServiceMessage::CallTool {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            timeout,
+            meta: None,
+            idempotency_key: None,
+            cancel: Some(cancel),
             reply,
         })
         .await
     }
 
+    /// Calls a tool tagged with an idempotency key, so that a retried call
+    /// isn't executed twice.
+    ///
+    /// The key is sent in the request's `_meta` under [`IDEMPOTENCY_KEY_META`].
+    /// Locally, a call made while another with the same key is still in flight
+    /// on the same service joins it and gets its result instead of reaching the
+    /// server. A call reusing an in-flight key with different arguments fails
+    /// instead. Retries after the first call has completed do reach the server;
+    /// end-to-end idempotency needs the server to remember the keys it has
+    /// seen and replay their results.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    /// * `idempotency_key` - Identifies the logical call across retries.
+    /// * `timeout` - How long to wait for the tool, or `None` for the service default.
+    pub async fn call_tool_idempotent(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+        idempotency_key: impl Into<String>,
+        timeout: Option<Duration>,
+    ) -> Result<CallToolResult> {
+        self.call_actor(|reply| ServiceMessage::CallTool {
+            service_name: normalize_fullname(&service_name.into()),
+            params,
+            timeout,
+            meta: None,
+            idempotency_key: Some(idempotency_key.into()),
+            cancel: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Calls a tool and decodes its result with `decoder`.
+    ///
+    /// The outer `Result` reports failures to call the tool or to decode its
+    /// result; the inner one is the tool's own success or error, as modelled by
+    /// the decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    /// * `decoder` - Maps the raw result onto the caller's types.
+    pub async fn call_tool_decoded<D: ToolResultDecoder>(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+        decoder: &D,
+    ) -> Result<std::result::Result<D::Ok, D::Err>> {
+        let result = self.call_tool(service_name, params).await?;
+        decoder.decode(&result)
+    }
+
+    /// Calls a tool whose results are JSON text, decoding successes into `T`
+    /// and errors into `E`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `params` - The tool name and its arguments.
+    pub async fn call_tool_json<T, E>(
+        &self,
+        service_name: impl Into<String>,
+        params: CallToolRequestParam,
+    ) -> Result<std::result::Result<T, E>>
+    where
+        T: serde::de::DeserializeOwned,
+        E: serde::de::DeserializeOwned,
+    {
+        self.call_tool_decoded(service_name, params, &JsonDecoder::<T, E>::new())
+            .await
+    }
+
+    /// Calls a tool and streams the text it produces as it arrives.
+    ///
+    /// Fragments are taken from the `message` of the progress notifications the
+    /// server sends for this call. The stream ends when the tool finishes; if the
+    /// server sent no progress messages, the text content of the final result is
+    /// yielded instead. A tool result flagged as an error ends the stream with an
+    /// error. Dropping the stream cancels the call on the server.
+    ///
+    /// Like [`ZeroClient::call_tool`], the call takes a place in the service's
+    /// call queue, is refused while its circuit breaker is open, ends with
+    /// `ZeroError::ToolCallTimeout` after `tool_call_timeout_ms` and counts
+    /// towards the service's health. A call ended by dropping the stream
+    /// doesn't count.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `tool_name` - The name of the tool to call.
+    /// * `args` - The tool's arguments.
+    pub async fn call_tool_stream_text(
+        &self,
+        service_name: impl Into<String>,
+        tool_name: impl Into<String>,
+        args: Option<JsonObject>,
+    ) -> Result<BoxStream<'static, Result<String>>> {
+        let service_name = normalize_fullname(&service_name.into());
+        let StreamingCall {
+            peer,
+            progress,
+            mut meta,
+            timeout,
+            limit,
+            slot,
+            buffer,
+        } = self
+            .call_actor(|reply| ServiceMessage::StreamingPeer {
+                service_name: service_name.clone(),
+                reply,
+            })
+            .await?;
+        let actor = self.actor.clone();
+        let record = move |outcome| {
+            let _ = actor.cast(ServiceMessage::CallFinished {
+                service_name,
+                outcome,
+            });
+        };
+        let tool = tool_name.into();
+        // As for other calls, time spent waiting for a `max_concurrent_calls`
+        // slot counts towards the timeout.
+        let started = tokio::time::Instant::now();
+        let deadline = timeout.map(|timeout| started + timeout);
+        let mut expired = Box::pin(async move {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        });
+        let timed_out = {
+            let tool = tool.clone();
+            move || -> anyhow::Error {
+                ZeroError::ToolCallTimeout {
+                    tool,
+                    elapsed: started.elapsed(),
+                }
+                .into()
+            }
+        };
+
+        let permit = tokio::select! {
+            permit = limit.acquire() => permit?,
+            _ = &mut expired => {
+                let error = timed_out();
+                record(HealthTracker::failure(&error));
+                return Err(error);
+            }
+        };
+        let (token, mut fragments) = progress.subscribe();
+        meta.set_progress_token(token.clone());
+        let params = CallToolRequestParam {
+            name: tool.into(),
+            arguments: args,
+        };
+        let handle = match peer
+            .send_cancellable_request(
+                ClientRequest::CallToolRequest(CallToolRequest::new(params)),
+                PeerRequestOptions {
+                    timeout: None,
+                    meta: Some(meta),
+                },
+            )
+            .await
+        {
+            Ok(handle) => handle,
+            Err(e) => {
+                progress.unsubscribe(&token);
+                let error = e.into();
+                record(HealthTracker::failure(&error));
+                return Err(error);
+            }
+        };
+
+        // Fragments wait in a buffer the manager can shed under its memory
+        // budget; dropping the stream ends the call.
+        let tx = FragmentSender(buffer.clone());
+        let dropped = CancellationToken::new();
+        let guard = dropped.clone().drop_guard();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let _slot = slot;
+            let mut response = handle.rx;
+            let mut streamed = false;
+            let outcome = loop {
+                tokio::select! {
+                    Some(text) = fragments.recv() => {
+                        streamed = true;
+                        if !tx.send(Ok(text)) {
+                            break Err(Abandoned::Shed);
+                        }
+                    }
+                    result = &mut response => {
+                        let result = result.unwrap_or(Err(ServiceError::TransportClosed));
+                        break Ok(result);
+                    }
+                    _ = &mut expired => break Err(Abandoned::TimedOut),
+                    _ = dropped.cancelled() => break Err(Abandoned::Dropped),
+                }
+            };
+            progress.unsubscribe(&token);
+
+            let result = match outcome {
+                Ok(result) => result,
+                Err(abandoned) => {
+                    let reason = match abandoned {
+                        Abandoned::TimedOut => "timed out",
+                        Abandoned::Dropped => "stream dropped",
+                        Abandoned::Shed => "stream output shed under memory pressure",
+                    };
+                    let notification = CancelledNotification {
+                        params: CancelledNotificationParam {
+                            request_id: handle.id,
+                            reason: Some(reason.to_string()),
+                        },
+                        method: CancelledNotificationMethod,
+                        extensions: Default::default(),
+                    };
+                    let _ = handle.peer.send_notification(notification.into()).await;
+                    // Only a timeout says something about the service's health;
+                    // the stream already ended if it was dropped or shed.
+                    if abandoned == Abandoned::TimedOut {
+                        let error = timed_out();
+                        record(HealthTracker::failure(&error));
+                        tx.send(Err(error));
+                    }
+                    return;
+                }
+            };
+            // Fragments sent just before the response may still be queued.
+            while let Ok(text) = fragments.try_recv() {
+                streamed = true;
+                tx.send(Ok(text));
+            }
+            let result = match result {
+                Ok(ServerResult::CallToolResult(result)) => Ok(result),
+                Ok(_) => Err(ServiceError::UnexpectedResponse.into()),
+                Err(e) => Err(e.into()),
+            };
+            record(HealthTracker::outcome(&result));
+            match result {
+                Ok(result) => {
+                    let text: Vec<String> = result
+                        .content
+                        .iter()
+                        .filter_map(|content| content.as_text().map(|t| t.text.clone()))
+                        .collect();
+                    if result.is_error == Some(true) {
+                        tx.send(Err(anyhow!("Tool reported an error: {}", text.join("\n"))));
+                    } else if !streamed {
+                        for fragment in text {
+                            if !tx.send(Ok(fragment)) {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    tx.send(Err(e));
+                }
+            }
+        });
+        Ok(stream::poll_fn(move |cx| {
+            let _guard = &guard;
+            buffer.poll_next(cx)
+        })
+        .boxed())
+    }
+
     /// Stops and removes a managed service.
     ///
     /// # Arguments
@@ -134,9 +928,558 @@ impl ZeroClient {
     /// * `service_name` - The full name of the service to stop.
     pub async fn stop_service(&self, service_name: impl Into<String>) -> Result<QuitReason> {
         self.call_actor(|reply| ServiceMessage::CancelService {
-            name: service_name.into(),
+            name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Stops a managed service and launches it again from the same
+    /// discovered service and config, without waiting for mDNS to report it
+    /// gone and back. Returns once the new instance is active.
+    /// `on_before_service_stop` runs first, as with [`ZeroClient::stop_service`].
+    ///
+    /// If the relaunch fails, the service stays stopped and
+    /// `on_service_error` and `on_service_stopped` are called. Its next mDNS
+    /// resolution launches it again, even if nothing changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service to restart.
+    pub async fn restart_service(&self, service_name: impl Into<String>) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::RestartService {
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Stops and removes every managed service, returning each one's outcome
+    /// sorted by name.
+    ///
+    /// `on_before_service_stop` runs for each service first, as with
+    /// [`ZeroClient::stop_service`].
+    pub async fn stop_all_services(&self) -> Result<StopResults> {
+        self.call_actor(|reply| ServiceMessage::StopAllServices { reply })
+            .await
+    }
+
+    /// Stops a managed service, escalating if it doesn't shut down in time.
+    ///
+    /// Each attempt waits up to `backoff` for the service to shut down. The
+    /// first cancels the client, closing a stdio process's stdin, and later
+    /// ones send the process SIGTERM. If all attempts time out, stdio services
+    /// have their child process killed; other transports return an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service to stop.
+    /// * `attempts` - How many attempts to make before giving up.
+    /// * `backoff` - How long to wait on each attempt.
+    pub async fn stop_service_with_retry(
+        &self,
+        service_name: impl Into<String>,
+        attempts: usize,
+        backoff: Duration,
+    ) -> Result<StopOutcome> {
+        self.call_actor(|reply| ServiceMessage::CancelServiceWithRetry {
+            name: normalize_fullname(&service_name.into()),
+            attempts,
+            backoff,
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the recent history of services added and removed, bucketed by time.
+    ///
+    /// Buckets cover the most recent `window` (capped by the configured history
+    /// capacity), oldest first, including buckets with no activity.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - How far back to report.
+    pub async fn discovery_history(&self, window: Duration) -> Result<Vec<TimeBucket>> {
+        self.call_actor(|reply| ServiceMessage::DiscoveryHistory { window, reply })
+            .await
+    }
+
+    /// Returns every active service that shares the given logical identity.
+    ///
+    /// The identity is the value of the configured `replica_identity_key` TXT
+    /// property, or the instance name for services that don't advertise it.
+    ///
+    /// # Arguments
+    ///
+    /// * `identity` - The logical identity to look up.
+    pub async fn service_replicas(
+        &self,
+        identity: impl Into<String>,
+    ) -> Result<Vec<DiscoveredService>> {
+        self.call_actor(|reply| ServiceMessage::ServiceReplicas {
+            identity: identity.into(),
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the session ID negotiated by an SSE service's transport.
+    ///
+    /// This is the identifier the server assigned to the connection, useful for
+    /// correlating client and server logs. Returns `None` for transports
+    /// without sessions, or if the server didn't expose one.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service.
+    pub async fn session_id(&self, service_name: impl Into<String>) -> Result<Option<String>> {
+        self.call_actor(|reply| ServiceMessage::SessionId {
+            service_name: normalize_fullname(&service_name.into()),
             reply,
         })
         .await
     }
+
+    /// Replaces the roots reported to a service and notifies it with
+    /// `notifications/roots/list_changed`.
+    ///
+    /// Initial roots come from the service's `init_options.roots`.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    /// * `roots` - The complete new set of roots.
+    pub async fn set_roots(&self, service_name: impl Into<String>, roots: Vec<Root>) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::SetRoots {
+            service_name: normalize_fullname(&service_name.into()),
+            roots,
+            reply,
+        })
+        .await
+    }
+
+    /// Exports the identities and configurations of the active services,
+    /// along with the inputs cached for them.
+    pub(crate) async fn export_state(&self) -> Result<ManagerState> {
+        let mut state = self
+            .call_actor(|reply| ServiceMessage::ExportState { reply })
+            .await?;
+        if let Some(inputs) = &self.inputs {
+            state.inputs = inputs.snapshot();
+        }
+        Ok(state)
+    }
+
+    /// Lists the active services that advertised `capability` when they
+    /// connected, sorted by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `capability` - The capability to look for, e.g. `Capability::Prompts`.
+    pub async fn services_with_capability(&self, capability: Capability) -> Result<Vec<String>> {
+        self.call_actor(|reply| ServiceMessage::ServicesWithCapability { capability, reply })
+            .await
+    }
+
+    /// Reports how much memory cached listings use, and how many have been
+    /// evicted to stay within `memory_budget_bytes`.
+    pub async fn memory_stats(&self) -> Result<MemoryStats> {
+        self.call_actor(|reply| ServiceMessage::MemoryStats { reply })
+            .await
+    }
+
+    /// Returns how long each phase of a service's launch took, to see whether
+    /// a slow start was spent spawning, connecting or in the MCP handshake.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    pub async fn startup_timings(&self, service_name: impl Into<String>) -> Result<StartupTimings> {
+        self.call_actor(|reply| ServiceMessage::StartupTimings {
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Returns a histogram of launch phase durations across every service
+    /// started so far, including those that have since stopped.
+    pub async fn startup_histogram(&self) -> Result<StartupHistogram> {
+        self.call_actor(|reply| ServiceMessage::StartupHistogram { reply })
+            .await
+    }
+
+    /// Returns the discovery details of an active service: its hostname, port,
+    /// addresses and TXT properties, as passed to `on_service_started`.
+    ///
+    /// For SSE services, `address` is the address the connection was made on.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service (e.g., "MyService._mcp._tcp.local.").
+    pub async fn get_service_info(
+        &self,
+        service_name: impl Into<String>,
+    ) -> Result<DiscoveredService> {
+        self.call_actor(|reply| ServiceMessage::GetServiceInfo {
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the sorted names of the currently active services.
+    ///
+    /// Useful for a handler or UI attaching mid-session, which may have missed
+    /// the `on_service_started` calls for services already running.
+    pub async fn list_active_services(&self) -> Result<Vec<String>> {
+        self.call_actor(|reply| ServiceMessage::ListActiveServices { reply })
+            .await
+    }
+
+    /// Returns the health of a service, derived from the outcomes of the
+    /// requests made to it.
+    ///
+    /// See [`HealthState`](crate::HealthState) for the transition rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service.
+    pub async fn service_health(&self, service_name: impl Into<String>) -> Result<HealthStatus> {
+        self.call_actor(|reply| ServiceMessage::ServiceHealth {
+            service_name: normalize_fullname(&service_name.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Launches a specific instance with `config` instead of its type's mapping.
+    ///
+    /// The override applies the next time the instance is launched, e.g. when it
+    /// is (re)discovered; a running instance is not restarted. It persists until
+    /// cleared with [`clear_launch_override`](Self::clear_launch_override).
+    ///
+    /// # Arguments
+    ///
+    /// * `fullname` - The full name of the service instance.
+    /// * `config` - The launch config to use for that instance.
+    pub async fn override_launch(
+        &self,
+        fullname: impl Into<String>,
+        config: McpConfig,
+    ) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::SetLaunchOverride {
+            fullname: normalize_fullname(&fullname.into()),
+            config: Some(Box::new(config)),
+            reply,
+        })
+        .await
+    }
+
+    /// Removes a launch override, so the instance uses its type's mapping again.
+    ///
+    /// # Arguments
+    ///
+    /// * `fullname` - The full name of the service instance.
+    pub async fn clear_launch_override(&self, fullname: impl Into<String>) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::SetLaunchOverride {
+            fullname: normalize_fullname(&fullname.into()),
+            config: None,
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the launch override set for an instance, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `fullname` - The full name of the service instance.
+    pub async fn launch_override(&self, fullname: impl Into<String>) -> Result<Option<McpConfig>> {
+        self.call_actor(|reply| ServiceMessage::LaunchOverride {
+            fullname: normalize_fullname(&fullname.into()),
+            reply,
+        })
+        .await
+    }
+
+    /// Calls the same tool on every active service.
+    ///
+    /// At most `fan_out_concurrency` calls are in flight at once (defaulting to
+    /// [`DEFAULT_FAN_OUT_CONCURRENCY`]); every service still gets called. A
+    /// failure on one service doesn't abort the others, so each result is
+    /// reported per service, keyed by fullname.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - The tool name and its arguments.
+    /// * `fan_out_concurrency` - The maximum number of concurrent calls.
+    pub async fn call_tool_all(
+        &self,
+        params: CallToolRequestParam,
+        fan_out_concurrency: Option<usize>,
+    ) -> Result<BTreeMap<String, Result<CallToolResult>>> {
+        let names = self.active_service_names().await?;
+        let concurrency = fan_out_concurrency.unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY);
+        let results = bounded_fan_out(names, concurrency, |name| {
+            let params = params.clone();
+            async move {
+                let result = self.call_tool(name.as_str(), params).await;
+                (name, result)
+            }
+        })
+        .await;
+        Ok(results.into_iter().collect())
+    }
+
+    /// Lists the tools of every active service.
+    ///
+    /// At most `fan_out_concurrency` requests are in flight at once (defaulting
+    /// to [`DEFAULT_FAN_OUT_CONCURRENCY`]). Results are reported per service,
+    /// keyed by fullname.
+    ///
+    /// # Arguments
+    ///
+    /// * `fan_out_concurrency` - The maximum number of concurrent requests.
+    pub async fn list_all_tools_everywhere(
+        &self,
+        fan_out_concurrency: Option<usize>,
+    ) -> Result<BTreeMap<String, Result<Vec<Tool>>>> {
+        let names = self.active_service_names().await?;
+        let concurrency = fan_out_concurrency.unwrap_or(DEFAULT_FAN_OUT_CONCURRENCY);
+        let results = bounded_fan_out(names, concurrency, |name| async move {
+            let result = self.list_all_tools(name.as_str()).await;
+            (name, result)
+        })
+        .await;
+        Ok(results.into_iter().collect())
+    }
+
+    async fn active_service_names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .active_service_infos()
+            .await?
+            .into_iter()
+            .map(|info| info.fullname)
+            .collect())
+    }
+
+    /// Actively re-queries the network for a service type, prompting servers to
+    /// re-announce, instead of waiting for their next advertisement.
+    ///
+    /// The previous query is stopped and its cached records dropped, so only
+    /// services that answer are reported again. Those that haven't changed
+    /// under the configured `change_policy` are left running.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_type` - The configured Zeroconf service type to refresh, or
+    ///   `None` for every configured type.
+    pub async fn refresh_discovery(&self, service_type: Option<String>) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::RefreshDiscovery {
+            service_type,
+            reply,
+        })
+        .await
+    }
+
+    /// Returns the health of every active service.
+    pub(crate) async fn health_summary(&self) -> Result<BTreeMap<String, HealthStatus>> {
+        self.call_actor(|reply| ServiceMessage::HealthSummary { reply })
+            .await
+    }
+
+    /// Launches a service that wasn't discovered through mDNS, passing it through
+    /// the same pipeline as a discovered one: templates are rendered against
+    /// `service`, and the service is tracked under its fullname and reported
+    /// through `on_service_started`.
+    ///
+    /// Useful for reproducing issues with a particular advertisement, or for
+    /// bridging environments without mDNS. Returns once the launch is queued;
+    /// a launch override set for the fullname still takes precedence over `config`.
+    /// Nothing is launched if a service with the same fullname is already
+    /// active or being launched.
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - The service as it would have been discovered.
+    /// * `config` - How to launch it.
+    pub async fn launch_synthetic(
+        &self,
+        mut service: DiscoveredService,
+        config: McpConfig,
+    ) -> Result<()> {
+        service.fullname = validate_fullname(&service.fullname)?;
+        self.call_actor(|reply| ServiceMessage::LaunchSynthetic {
+            service: Box::new(service),
+            config: Box::new(config),
+            reply,
+        })
+        .await
+    }
+
+    /// Streams every JSON-RPC message exchanged with a service from now on, in
+    /// both directions, for protocol debugging.
+    ///
+    /// The stream ends when the service stops. A consumer that falls too far
+    /// behind skips the oldest events it missed.
+    ///
+    /// # Arguments
+    ///
+    /// * `service_name` - The full name of the service to observe.
+    pub async fn tap_traffic(
+        &self,
+        service_name: impl Into<String>,
+    ) -> Result<BoxStream<'static, TrafficEvent>> {
+        let service_name = normalize_fullname(&service_name.into());
+        let receiver = self
+            .call_actor(|reply| ServiceMessage::TapTraffic {
+                service_name: service_name.clone(),
+                reply,
+            })
+            .await?;
+        Ok(stream::unfold(receiver, move |mut receiver| {
+            let service_name = service_name.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => return Some((event, receiver)),
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!(service.name = %service_name, missed, "Traffic tap fell behind");
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+        .boxed())
+    }
+
+    /// Returns when a service was last stopped, while its relaunch cooldown lasts.
+    pub(crate) async fn stopped_at(&self, service_name: &str) -> Result<Option<Instant>> {
+        self.call_actor(|reply| ServiceMessage::StoppedAt {
+            service_name: service_name.to_string(),
+            reply,
+        })
+        .await
+    }
+
+    /// Stops a service taking new tool calls, which then fail with
+    /// `ZeroError::Draining`, and waits until its outstanding calls and
+    /// requests have finished. Returns `false` if it wasn't active.
+    pub(crate) async fn drain_calls(&self, service_name: &str) -> Result<bool> {
+        self.call_actor(|reply| ServiceMessage::DrainCalls {
+            service_name: service_name.to_string(),
+            reply,
+        })
+        .await
+    }
+
+    /// Lets a service drained by [`ZeroClient::drain_calls`] take calls again.
+    pub(crate) async fn resume_calls(&self, service_name: &str) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::ResumeCalls {
+            service_name: service_name.to_string(),
+            reply,
+        })
+        .await
+    }
+
+    /// Pings a service, returning the round-trip time, or failing after `timeout`.
+    pub(crate) async fn ping_within(
+        &self,
+        service_name: &str,
+        timeout: Duration,
+    ) -> Result<Duration> {
+        self.call_actor(|reply| ServiceMessage::Ping {
+            service_name: normalize_fullname(service_name),
+            timeout: Some(timeout),
+            reply,
+        })
+        .await
+    }
+
+    /// Removes a service whose process exited without being stopped, if it is
+    /// still tracked as the instance with `pid`. Returns whether it was.
+    pub(crate) async fn remove_exited(&self, service_name: &str, pid: Option<u32>) -> Result<bool> {
+        self.call_actor(|reply| ServiceMessage::RemoveExited {
+            service_name: service_name.to_string(),
+            pid,
+            reply,
+        })
+        .await
+    }
+
+    /// Marks a launch of the service as in progress, returning `None` if it
+    /// is already active or being launched. The returned claim ends the
+    /// launch when dropped.
+    pub(crate) async fn claim_launch(&self, service_name: &str) -> Result<Option<LaunchClaim>> {
+        let claimed = self
+            .call_actor(|reply| ServiceMessage::ClaimLaunch {
+                service_name: service_name.to_string(),
+                reply,
+            })
+            .await?;
+        Ok(claimed.map(|withdrawn| LaunchClaim {
+            actor: self.actor.clone(),
+            service_name: service_name.to_string(),
+            withdrawn,
+        }))
+    }
+
+    /// Records a changed resolution to relaunch once the service's launch in
+    /// flight ends, returning `false` if none is.
+    pub(crate) async fn supersede_launch(
+        &self,
+        service: &DiscoveredService,
+        config: &McpConfig,
+    ) -> Result<bool> {
+        self.call_actor(|reply| ServiceMessage::SupersedeLaunch {
+            service: Box::new(service.clone()),
+            config: Box::new(config.clone()),
+            reply,
+        })
+        .await
+    }
+
+    /// Stops the manager from tracking services again, ahead of stopping them all.
+    pub(crate) async fn begin_shutdown(&self) -> Result<()> {
+        self.call_actor(|reply| ServiceMessage::BeginShutdown { reply })
+            .await
+    }
+
+    /// Returns the discovery details of every active service.
+    pub(crate) async fn active_service_infos(&self) -> Result<Vec<DiscoveredService>> {
+        self.call_actor(|reply| ServiceMessage::ActiveServiceInfos { reply })
+            .await
+    }
+
+    /// Returns the services due a health check, marking them as checked.
+    pub(crate) async fn due_health_checks(&self) -> Result<Vec<String>> {
+        self.call_actor(|reply| ServiceMessage::DueHealthChecks { reply })
+            .await
+    }
+
+    /// Returns the services that have been idle for their `idle_timeout_ms`.
+    pub(crate) async fn idle_services(&self) -> Result<Vec<String>> {
+        self.call_actor(|reply| ServiceMessage::IdleServices { reply })
+            .await
+    }
+}
+
+/// A launch in progress, claimed with [`ZeroClient::claim_launch`]. Dropping
+/// it lets the service be launched again, however the launch ended.
+#[derive(Debug)]
+pub(crate) struct LaunchClaim {
+    actor: ActorRef<ServiceMessage>,
+    service_name: String,
+    /// Cancelled if the service is stopped before the launch ends.
+    pub(crate) withdrawn: CancellationToken,
+}
+
+impl Drop for LaunchClaim {
+    fn drop(&mut self) {
+        let _ = self.actor.cast(ServiceMessage::ReleaseLaunch {
+            service_name: std::mem::take(&mut self.service_name),
+        });
+    }
 }
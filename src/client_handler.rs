@@ -0,0 +1,248 @@
+use crate::{SamplingHandler, ZeroHandler, config::InitOptions, utils::catch_panic};
+use rmcp::{
+    ClientHandler,
+    model::{
+        ClientInfo, CreateMessageRequestMethod, CreateMessageRequestParam, CreateMessageResult,
+        Implementation, ListRootsResult, LoggingMessageNotificationParam, NumberOrString,
+        ProgressNotificationParam, ProgressToken, ResourceUpdatedNotificationParam, Root,
+        RootsCapabilities,
+    },
+    service::{NotificationContext, RequestContext, RoleClient},
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
+
+/// Routes progress notification messages to the streams waiting on them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProgressRouter {
+    next_id: Arc<AtomicU32>,
+    subscribers: Arc<Mutex<HashMap<ProgressToken, mpsc::UnboundedSender<String>>>>,
+}
+
+impl ProgressRouter {
+    /// Allocates a progress token and returns the receiver for its messages.
+    pub(crate) fn subscribe(&self) -> (ProgressToken, mpsc::UnboundedReceiver<String>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = ProgressToken(NumberOrString::String(
+            format!("zeromcp-stream-{}", id).into(),
+        ));
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.lock().insert(token.clone(), tx);
+        (token, rx)
+    }
+
+    pub(crate) fn unsubscribe(&self, token: &ProgressToken) {
+        self.lock().remove(token);
+    }
+
+    fn route(&self, params: ProgressNotificationParam) {
+        let Some(message) = params.message else {
+            return;
+        };
+        if let Some(tx) = self.lock().get(&params.progress_token) {
+            let _ = tx.send(message);
+        }
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<ProgressToken, mpsc::UnboundedSender<String>>> {
+        // The map stays consistent even if a holder panicked.
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The MCP client-side handler attached to every running service.
+///
+/// It supplies the `initialize` payload and answers server-initiated requests.
+#[derive(Clone)]
+pub(crate) struct ZeroClientHandler {
+    service_name: String,
+    info: ClientInfo,
+    roots: Arc<RwLock<Vec<Root>>>,
+    sampling: Option<Arc<dyn SamplingHandler>>,
+    progress: ProgressRouter,
+    app_handler: Arc<dyn ZeroHandler>,
+}
+
+impl fmt::Debug for ZeroClientHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZeroClientHandler")
+            .field("service_name", &self.service_name)
+            .field("info", &self.info)
+            .field("roots", &self.roots)
+            .field(
+                "sampling",
+                &self.sampling.as_ref().map(|_| "<dyn SamplingHandler>"),
+            )
+            .finish()
+    }
+}
+
+impl ZeroClientHandler {
+    pub(crate) fn new(
+        service_name: &str,
+        options: Option<InitOptions>,
+        sampling: Option<Arc<dyn SamplingHandler>>,
+        app_handler: Arc<dyn ZeroHandler>,
+    ) -> Self {
+        let mut info = ClientInfo::default();
+        // Roots are always advertised so they can be set at runtime.
+        info.capabilities.roots = Some(RootsCapabilities {
+            list_changed: Some(true),
+        });
+        if sampling.is_some() {
+            info.capabilities.sampling = Some(Default::default());
+        }
+        let mut handler = Self {
+            service_name: service_name.to_string(),
+            info,
+            roots: Arc::default(),
+            sampling,
+            progress: ProgressRouter::default(),
+            app_handler,
+        };
+        let Some(options) = options else {
+            return handler;
+        };
+
+        let defaults = Implementation::from_build_env();
+        handler.info.client_info = Implementation {
+            name: options.client_name.unwrap_or(defaults.name),
+            version: options.client_version.unwrap_or(defaults.version),
+        };
+        handler.info.capabilities.experimental = options.experimental;
+
+        let roots = options
+            .roots
+            .into_iter()
+            .map(|root| Root {
+                uri: root.uri,
+                name: root.name,
+            })
+            .collect();
+        handler.roots = Arc::new(RwLock::new(roots));
+
+        handler
+    }
+
+    /// Returns the roots this handler reports, shared so they can be replaced
+    /// while the service runs.
+    pub(crate) fn roots(&self) -> Arc<RwLock<Vec<Root>>> {
+        self.roots.clone()
+    }
+
+    /// Returns the router fed by this handler's progress notifications.
+    pub(crate) fn progress(&self) -> ProgressRouter {
+        self.progress.clone()
+    }
+}
+
+impl ClientHandler for ZeroClientHandler {
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParam,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateMessageResult, rmcp::Error> {
+        let Some(sampling) = &self.sampling else {
+            debug!(service.name = %self.service_name, "Rejecting sampling request: no handler");
+            return Err(rmcp::Error::method_not_found::<CreateMessageRequestMethod>());
+        };
+        sampling
+            .on_sampling_request(&self.service_name, params)
+            .await
+            .map_err(|e| {
+                warn!(service.name = %self.service_name, error = %e, "Sampling request failed");
+                rmcp::Error::internal_error(e.to_string(), None)
+            })
+    }
+
+    async fn on_progress(
+        &self,
+        params: ProgressNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.progress.route(params);
+    }
+
+    async fn on_logging_message(
+        &self,
+        params: LoggingMessageNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        let logged =
+            self.app_handler
+                .on_log_message(&self.service_name, params.level, &params.data);
+        if let Err(panic) = catch_panic(logged).await {
+            error!(panic = %panic, "on_log_message panicked for '{}'", self.service_name);
+        }
+    }
+
+    async fn on_resource_updated(
+        &self,
+        params: ResourceUpdatedNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        let updated = self
+            .app_handler
+            .on_resource_updated(&self.service_name, &params.uri);
+        if let Err(panic) = catch_panic(updated).await {
+            error!(panic = %panic, "on_resource_updated panicked for '{}'", self.service_name);
+        }
+    }
+
+    async fn list_roots(
+        &self,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<ListRootsResult, rmcp::Error> {
+        let roots = self
+            .roots
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        Ok(ListRootsResult { roots })
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        self.info.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_router_delivers_messages_by_token() {
+        let router = ProgressRouter::default();
+        let (token, mut rx) = router.subscribe();
+        let (other, _other_rx) = router.subscribe();
+        assert_ne!(token, other);
+
+        let progress = |token: &ProgressToken, message: Option<&str>| ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress: 1,
+            total: None,
+            message: message.map(str::to_string),
+        };
+        router.route(progress(&token, Some("hello")));
+        router.route(progress(&token, None));
+        router.route(progress(&other, Some("elsewhere")));
+        assert_eq!(rx.try_recv().unwrap(), "hello");
+        assert!(rx.try_recv().is_err());
+
+        router.unsubscribe(&token);
+        router.route(progress(&token, Some("late")));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,55 +1,1223 @@
+use crate::{
+    error::ZeroError,
+    history::DiscoveryHistoryConfig,
+    models::{DiscoveredService, REDACTED, is_sensitive, redact_url_password},
+};
 use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::{collections::HashMap, io::Read, path::Path};
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned};
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::Entry},
+    fs::File,
+    hash::{BuildHasher, Hasher, RandomState},
+    io::Read,
+    path::Path,
+    time::Duration,
+};
+use tracing::warn;
+
+/// The largest configuration, in bytes, that `load` and `from_reader` accept.
+pub const DEFAULT_MAX_CONFIG_SIZE: u64 = 1024 * 1024;
+
+/// How long, in milliseconds, a service may take to connect when it doesn't
+/// set `launch_timeout_ms`.
+pub const DEFAULT_LAUNCH_TIMEOUT_MS: u64 = 30_000;
 
 /// Represents the top-level configuration loaded from a TOML file.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ZeroConfig {
-    #[serde(rename = "service_mapping")]
+    #[serde(rename = "service_mapping", default)]
     pub service_mappings: Vec<ServiceMcpMapping>,
+    /// Services connected at startup regardless of mDNS discovery.
+    #[serde(rename = "static_service", default)]
+    pub static_services: Vec<StaticService>,
+    /// Tool calls made periodically, with results passed to `on_scheduled_result`.
+    #[serde(rename = "schedule", default)]
+    pub schedules: Vec<Schedule>,
+    /// Options applied to every service that doesn't set them itself.
+    #[serde(default)]
+    pub service_defaults: ServiceOptions,
+    /// Size and resolution of the queryable discovery history.
+    #[serde(default)]
+    pub discovery_history: DiscoveryHistoryConfig,
+    /// TXT property used to recognise the same logical service advertised from
+    /// several hosts. Falls back to the instance name when unset or absent.
+    #[serde(default)]
+    pub replica_identity_key: Option<String>,
+    /// TXT property holding a numeric replica priority, higher preferred.
+    /// When set, only the best replica of each identity runs; the others are
+    /// kept as standbys to migrate to.
+    #[serde(default)]
+    pub replica_priority_key: Option<String>,
+    /// What to do with a service when a handler callback for it panics.
+    #[serde(default)]
+    pub handler_panic_policy: HandlerPanicPolicy,
+    /// Proxy used by SSE services that don't configure their own.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Which differences make a re-resolved service count as changed, and so
+    /// get relaunched. Unchanged re-resolutions are ignored.
+    #[serde(default)]
+    pub change_policy: ChangePolicy,
+    /// Which IP version's addresses come first in each discovered service's
+    /// `addresses`, and so make its `primary_address`.
+    #[serde(default)]
+    pub address_family: AddressFamily,
+    /// If non-empty, only services on these hostnames are connected to,
+    /// whatever their type. Matching ignores case and the trailing dot.
+    #[serde(default)]
+    pub host_allowlist: Vec<String>,
+    /// Whether SSE services that don't set `compression` themselves accept
+    /// gzip- and deflate-compressed responses.
+    #[serde(default)]
+    pub compression: bool,
+    /// Approximate cap, in bytes, on the listings cached across all services
+    /// and the stream fragments waiting to be read. Past it, the least
+    /// recently used listings are dropped and the longest-waiting fragments
+    /// shed. Unlimited when unset.
+    #[serde(default)]
+    pub memory_budget_bytes: Option<usize>,
+    /// Env vars and headers whose names contain any of these, ignoring case,
+    /// have their values redacted before being passed to `on_launch`.
+    #[serde(default = "default_redact_keys")]
+    pub redact_keys: Vec<String>,
+    /// Cap on `ZeroClient` requests waiting on the manager at once, across all
+    /// clones of the client. Unlimited when unset.
+    #[serde(default)]
+    pub max_pending_requests: Option<usize>,
+    /// What a request does when `max_pending_requests` are already pending.
+    #[serde(default)]
+    pub overload_policy: OverloadPolicy,
+    /// Fraction of the interval by which periodic per-service timers, such as
+    /// health checks and keepalives, are randomly shifted on each tick, so
+    /// services aren't all checked at once. Clamped to `0.0..=1.0`.
+    #[serde(default = "default_timer_jitter")]
+    pub timer_jitter: f64,
+    /// Ping every active service this often, in milliseconds, and remove those
+    /// that don't answer, rather than waiting for mDNS to report them gone.
+    /// Disabled when unset. A service's own `health_check_interval_ms`, or
+    /// the one in `service_defaults`, takes precedence.
+    #[serde(default)]
+    pub health_check_interval_ms: Option<u64>,
+    /// How long a health-check ping may take before the service counts as dead.
+    #[serde(default = "default_health_check_timeout_ms")]
+    pub health_check_timeout_ms: u64,
+    /// Remember the values supplied through `request_input` for each service
+    /// and reuse them when it is relaunched, instead of prompting again.
+    #[serde(default)]
+    pub cache_inputs: bool,
+    /// How long, in milliseconds, a service reported removed by mDNS is kept
+    /// running in case it resolves again, so brief flapping on a congested
+    /// network doesn't stop and relaunch it. `0` stops services right away.
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_health_check_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_debounce_ms() -> u64 {
+    500
+}
+
+fn default_timer_jitter() -> f64 {
+    0.1
+}
+
+/// How a `ZeroClient` request behaves once `max_pending_requests` is reached.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverloadPolicy {
+    /// Wait for an earlier request to finish.
+    #[default]
+    Wait,
+    /// Fail immediately with `ZeroError::Overloaded`.
+    Error,
+}
+
+fn default_redact_keys() -> Vec<String> {
+    ["authorization", "token", "secret", "password", "key"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// The fields compared when deciding whether a re-resolved service changed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangePolicy {
+    /// Only the set of advertised addresses.
+    Addresses,
+    /// The addresses and the port.
+    AddressesAndPort,
+    /// The addresses, the port and the TXT properties.
+    #[default]
+    All,
+}
+
+/// Which IP version's addresses a service lists first.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    #[default]
+    Ipv4,
+    Ipv6,
+}
+
+/// An HTTP(S) or SOCKS5 proxy for outbound SSE connections.
+///
+/// `url`, `username` and `password` are rendered as Handlebars templates.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProxyConfig {
+    /// The proxy URL, e.g. `http://proxy:3128` or `socks5://proxy:1080`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Comma-separated hosts, domains and CIDR ranges to connect to directly,
+    /// in the `NO_PROXY` format (e.g. `"192.168.0.0/16,.local"`). Defaults to
+    /// the `NO_PROXY` environment variable.
+    pub no_proxy: Option<String>,
+}
+
+/// Determines how the manager reacts to a panic inside a handler callback.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HandlerPanicPolicy {
+    /// Log the panic and keep the service running.
+    #[default]
+    Keep,
+    /// Log the panic and stop the service the callback was handling.
+    Stop,
 }
 
 /// Defines a mapping between a Zeroconf service and its MCP configuration.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ServiceMcpMapping {
-    pub zeroconf_service: String,
+    /// The service types browsed for and launched with this mapping, written
+    /// as `zeroconf_service`, either one type or a list.
+    #[serde(
+        rename = "zeroconf_service",
+        deserialize_with = "one_or_many",
+        serialize_with = "serialize_one_or_many"
+    )]
+    pub zeroconf_services: Vec<String>,
+    /// Only discover services advertising this DNS-SD subtype, given as a
+    /// single label such as `"_ai"`. Each `zeroconf_service` is then browsed
+    /// as `<subtype>._sub.<zeroconf_service>`, e.g. `_ai._sub._mcp._tcp.local.`.
+    #[serde(default)]
+    pub subtype: Option<String>,
+    /// Capability tags a service must advertise to be connected to. Services
+    /// missing any of them are skipped.
+    #[serde(default)]
+    pub required_capabilities: Vec<String>,
+    /// TXT property holding the service's comma-separated capability tags.
+    #[serde(default = "default_capabilities_key")]
+    pub capabilities_key: String,
+    /// TXT properties a service must advertise with exactly these values to
+    /// be connected to, e.g. `{ env = "prod" }`.
+    #[serde(default)]
+    pub filter: HashMap<String, String>,
+    #[serde(flatten)]
+    pub mcp: McpConfig,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+fn serialize_one_or_many<S: Serializer>(
+    values: &[String],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match values {
+        [one] => serializer.serialize_str(one),
+        many => many.serialize(serializer),
+    }
+}
+
+fn default_capabilities_key() -> String {
+    "caps".to_string()
+}
+
+impl ServiceMcpMapping {
+    /// Returns the queries browsed for this mapping: each of its
+    /// `zeroconf_services`, narrowed to `subtype` if one is set.
+    pub fn browse_types(&self) -> impl Iterator<Item = String> + '_ {
+        self.zeroconf_services
+            .iter()
+            .map(|service_type| match &self.subtype {
+                Some(subtype) => format!("{subtype}._sub.{service_type}"),
+                None => service_type.clone(),
+            })
+    }
+
+    /// Returns whether `service` is an instance of one of `zeroconf_services`,
+    /// judging by its fullname.
+    pub fn covers(&self, service: &DiscoveredService) -> bool {
+        self.zeroconf_services.iter().any(|service_type| {
+            service
+                .fullname
+                .strip_suffix(service_type.as_str())
+                .is_some_and(|instance| instance.ends_with('.'))
+        })
+    }
+
+    /// Returns the first required capability `service` doesn't advertise, if any.
+    pub fn missing_capability(&self, service: &DiscoveredService) -> Option<&str> {
+        let advertised = service.capabilities(&self.capabilities_key);
+        self.required_capabilities
+            .iter()
+            .map(String::as_str)
+            .find(|required| !advertised.contains(required.trim()))
+    }
+
+    /// Returns the first `filter` key whose value `service` doesn't
+    /// advertise, if any.
+    pub fn unmatched_filter(&self, service: &DiscoveredService) -> Option<&str> {
+        self.filter
+            .iter()
+            .find(|(key, value)| service.properties.get(*key) != Some(*value))
+            .map(|(key, _)| key.as_str())
+    }
+}
+
+/// A tool called on a service at a fixed interval.
+///
+/// Ticks on which the service isn't active are skipped.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Schedule {
+    /// Identifies the schedule in `on_scheduled_result`.
+    pub id: String,
+    /// The full name of the service to call.
+    pub service: String,
+    pub tool: String,
+    #[serde(default)]
+    pub args: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Time between calls, in milliseconds. The first call is attempted at startup.
+    pub interval_ms: u64,
+}
+
+/// A service that is always connected, independent of discovery.
+///
+/// Its templates are rendered against a synthetic service whose `fullname` is
+/// `service_name` and whose hostname, port and addresses are empty.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StaticService {
+    /// The name the service is tracked under, used with `ZeroClient` methods.
+    pub service_name: String,
     #[serde(flatten)]
     pub mcp: McpConfig,
 }
 
 /// Contains the template for launching an MCP server process.
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "protocol", rename_all = "lowercase")]
 pub enum McpConfig {
     Stdio {
         name: String,
         command: String,
         args: Vec<String>,
+        /// Environment variables, rendered in declaration order.
+        #[serde(default)]
+        envs: IndexMap<String, String>,
+        /// Working directory for the process, rendered like `args`. Defaults
+        /// to the current directory.
+        #[serde(default)]
+        cwd: Option<String>,
+        /// Start the process with only `envs`, rather than adding them to
+        /// this process's environment.
         #[serde(default)]
-        envs: HashMap<String, String>,
+        clear_env: bool,
+        #[serde(flatten)]
+        options: ServiceOptions,
     },
     Sse {
         name: String,
         url: String,
-        headers: Option<HashMap<String, String>>,
+        /// HTTP headers, rendered in declaration order.
+        headers: Option<IndexMap<String, String>>,
+        /// Proxy for this service, overriding the top-level `proxy`.
+        #[serde(default)]
+        proxy: Option<ProxyConfig>,
+        /// Accept gzip- and deflate-compressed responses, overriding the
+        /// top-level `compression`.
+        #[serde(default)]
+        compression: Option<bool>,
+        #[serde(flatten)]
+        options: ServiceOptions,
     },
+    /// The streamable HTTP transport, which the MCP spec favours over SSE.
+    #[serde(rename = "streamable-http")]
+    StreamableHttp {
+        name: String,
+        url: String,
+        /// HTTP headers, rendered in declaration order.
+        headers: Option<IndexMap<String, String>>,
+        /// Proxy for this service, overriding the top-level `proxy`.
+        #[serde(default)]
+        proxy: Option<ProxyConfig>,
+        /// Accept gzip- and deflate-compressed responses, overriding the
+        /// top-level `compression`.
+        #[serde(default)]
+        compression: Option<bool>,
+        #[serde(flatten)]
+        options: ServiceOptions,
+    },
+}
+
+impl McpConfig {
+    /// Returns the options shared by every transport.
+    pub fn options(&self) -> &ServiceOptions {
+        match self {
+            McpConfig::Stdio { options, .. }
+            | McpConfig::Sse { options, .. }
+            | McpConfig::StreamableHttp { options, .. } => options,
+        }
+    }
+
+    fn options_mut(&mut self) -> &mut ServiceOptions {
+        match self {
+            McpConfig::Stdio { options, .. }
+            | McpConfig::Sse { options, .. }
+            | McpConfig::StreamableHttp { options, .. } => options,
+        }
+    }
+
+    /// Layers this service's settings over the defaults in `config`: the
+    /// top-level `proxy` and `compression`, and `service_defaults`.
+    pub(crate) fn with_defaults(mut self, config: &ZeroConfig) -> Self {
+        let mut options =
+            std::mem::take(self.options_mut()).with_defaults(&config.service_defaults);
+        if options.health_check_interval_ms.is_none() {
+            options.health_check_interval_ms = config.health_check_interval_ms;
+        }
+        *self.options_mut() = options;
+        self.with_default_proxy(config.proxy.as_ref())
+            .with_default_compression(config.compression)
+    }
+
+    /// Fills in `default` as the compression setting of an HTTP-based service
+    /// that doesn't set its own.
+    pub(crate) fn with_default_compression(mut self, default: bool) -> Self {
+        if let McpConfig::Sse { compression, .. } | McpConfig::StreamableHttp { compression, .. } =
+            &mut self
+        {
+            compression.get_or_insert(default);
+        }
+        self
+    }
+
+    /// Returns the proxy of an HTTP-based service, if it sets one.
+    pub(crate) fn proxy(&self) -> Option<&ProxyConfig> {
+        match self {
+            McpConfig::Sse { proxy, .. } | McpConfig::StreamableHttp { proxy, .. } => {
+                proxy.as_ref()
+            }
+            McpConfig::Stdio { .. } => None,
+        }
+    }
+
+    /// Fills in `default` as the proxy of an HTTP-based service that doesn't
+    /// set its own.
+    pub(crate) fn with_default_proxy(mut self, default: Option<&ProxyConfig>) -> Self {
+        if let McpConfig::Sse { proxy, .. } | McpConfig::StreamableHttp { proxy, .. } = &mut self
+            && proxy.is_none()
+        {
+            *proxy = default.cloned();
+        }
+        self
+    }
+}
+
+/// Per-service options that apply regardless of transport.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ServiceOptions {
+    /// Client-side fields for the MCP `initialize` request. Every string value
+    /// is rendered as a Handlebars template before being parsed as [`InitOptions`].
+    #[serde(default)]
+    pub init_options: Option<serde_json::Value>,
+    /// Default timeout for tool calls to this service, in milliseconds. Tool calls
+    /// are otherwise unbounded, since they may legitimately run far longer than
+    /// metadata requests.
+    #[serde(default)]
+    pub tool_call_timeout_ms: Option<u64>,
+    /// Timeout for listing tools, prompts and resources, reading resources and
+    /// getting prompts from this service, in milliseconds. Unbounded when unset.
+    #[serde(default)]
+    pub request_timeout_ms: Option<u64>,
+    /// Only keep the connection if the server offers a tool matching this name.
+    /// `*` matches any run of characters, e.g. `"search_*"`.
+    #[serde(default)]
+    pub require_tool: Option<String>,
+    /// How long, in milliseconds, the service must stay connected after the
+    /// handshake before it counts as started. A service that drops within this
+    /// window is treated as a failed start and `on_service_started` isn't called.
+    #[serde(default)]
+    pub min_stable_uptime_ms: Option<u64>,
+    /// Minimum time, in milliseconds, between a service being stopped and it
+    /// being launched again, so the OS can reap the old process and free its
+    /// ports first.
+    #[serde(default)]
+    pub relaunch_cooldown_ms: Option<u64>,
+    /// Fields merged into the `_meta` of every tool, prompt and resource request
+    /// sent to this service, e.g. a tenant or trace ID. Metadata passed with an
+    /// individual call takes precedence.
+    #[serde(default)]
+    pub request_meta: Option<serde_json::Map<String, serde_json::Value>>,
+    /// What to do when the server connects but offers no tools, prompts or
+    /// resources. Defaults to [`EmptyServerPolicy::Keep`].
+    #[serde(default)]
+    pub empty_server_policy: Option<EmptyServerPolicy>,
+    /// How many tool calls may be outstanding on this service, waiting for a
+    /// `max_concurrent_calls` slot or running, before further calls fail with
+    /// `ZeroError::QueueFull`. Unlimited when unset.
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+    /// How many requests (tool calls, prompt and resource requests and
+    /// listings) may be in flight to this service at once; further ones wait
+    /// for a slot. Unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_calls: Option<usize>,
+    /// Which definition to keep when the server lists several tools with the
+    /// same name. Defaults to [`DuplicateToolPolicy::First`].
+    #[serde(default)]
+    pub duplicate_tools: Option<DuplicateToolPolicy>,
+    /// Relaunch a stdio service whose process exits on its own. Without it,
+    /// such a service just stops responding.
+    #[serde(default)]
+    pub restart: Option<RestartPolicy>,
+    /// Reconnect to an SSE or streamable HTTP service whose connection drops,
+    /// with the url and headers it was launched with. Without it, such a
+    /// service just stops responding.
+    #[serde(default)]
+    pub reconnect: Option<RestartPolicy>,
+    /// How long, in milliseconds, connecting and the MCP handshake may take
+    /// before the launch fails. Time spent waiting for user input doesn't
+    /// count. Defaults to [`DEFAULT_LAUNCH_TIMEOUT_MS`].
+    #[serde(default)]
+    pub launch_timeout_ms: Option<u64>,
+    /// Ping this service this often, in milliseconds, and remove it if it
+    /// doesn't answer. Falls back to the top-level `health_check_interval_ms`.
+    #[serde(default)]
+    pub health_check_interval_ms: Option<u64>,
+    /// Stop the service once it has gone this long, in milliseconds, without
+    /// a tool call, prompt or resource request. Never when unset.
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+    /// Fail tool calls fast with `ZeroError::CircuitOpen` while the service
+    /// keeps failing. Disabled when unset.
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreaker>,
+}
+
+/// When tool calls to a failing service are rejected without being sent.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    /// Consecutive failures (transport errors and timeouts) that open the circuit.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long, in milliseconds, the circuit stays open after the last
+    /// failure. Calls are then let through again, and the next failure
+    /// reopens it.
+    #[serde(default = "default_reset_timeout_ms")]
+    pub reset_timeout_ms: u64,
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_reset_timeout_ms() -> u64 {
+    30_000
+}
+
+impl CircuitBreaker {
+    /// Returns how long the circuit stays open after a failure.
+    pub fn reset_timeout(&self) -> Duration {
+        Duration::from_millis(self.reset_timeout_ms)
+    }
+}
+
+/// How a service that goes away unexpectedly is brought back: a stdio
+/// service's process is relaunched, an SSE or streamable HTTP service is
+/// reconnected to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Relaunch or reconnect attempts before giving up and calling
+    /// `on_service_stopped`.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first attempt, in milliseconds, doubling for each
+    /// attempt after it.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// How long, in milliseconds, a service must stay up after coming back
+    /// before its retries are counted afresh. Until then, each time it goes
+    /// away again uses up more of the same `max_retries`.
+    #[serde(default = "default_reset_after_ms")]
+    pub reset_after_ms: u64,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+fn default_reset_after_ms() -> u64 {
+    60_000
+}
+
+impl RestartPolicy {
+    /// Returns how long a service must stay up for its retries to reset.
+    pub fn reset_after(&self) -> Duration {
+        Duration::from_millis(self.reset_after_ms)
+    }
+
+    /// Returns the delay before relaunch attempt `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+impl ServiceOptions {
+    /// Fills every option this service leaves unset from `defaults`.
+    fn with_defaults(mut self, defaults: &ServiceOptions) -> Self {
+        fn fill<T: Clone>(value: &mut Option<T>, default: &Option<T>) {
+            if value.is_none() {
+                *value = default.clone();
+            }
+        }
+        // Destructured so a new option can't be added without deciding how it layers.
+        let ServiceOptions {
+            init_options,
+            tool_call_timeout_ms,
+            request_timeout_ms,
+            require_tool,
+            min_stable_uptime_ms,
+            relaunch_cooldown_ms,
+            request_meta,
+            max_queue_depth,
+            max_concurrent_calls,
+            empty_server_policy,
+            duplicate_tools,
+            restart,
+            reconnect,
+            launch_timeout_ms,
+            health_check_interval_ms,
+            idle_timeout_ms,
+            circuit_breaker,
+        } = defaults;
+        fill(&mut self.init_options, init_options);
+        fill(&mut self.tool_call_timeout_ms, tool_call_timeout_ms);
+        fill(&mut self.request_timeout_ms, request_timeout_ms);
+        fill(&mut self.require_tool, require_tool);
+        fill(&mut self.min_stable_uptime_ms, min_stable_uptime_ms);
+        fill(&mut self.relaunch_cooldown_ms, relaunch_cooldown_ms);
+        fill(&mut self.request_meta, request_meta);
+        fill(&mut self.max_queue_depth, max_queue_depth);
+        fill(&mut self.max_concurrent_calls, max_concurrent_calls);
+        fill(&mut self.empty_server_policy, empty_server_policy);
+        fill(&mut self.duplicate_tools, duplicate_tools);
+        fill(&mut self.restart, restart);
+        fill(&mut self.reconnect, reconnect);
+        fill(&mut self.launch_timeout_ms, launch_timeout_ms);
+        fill(&mut self.health_check_interval_ms, health_check_interval_ms);
+        fill(&mut self.idle_timeout_ms, idle_timeout_ms);
+        fill(&mut self.circuit_breaker, circuit_breaker);
+        self
+    }
+
+    /// Returns how long connecting and the handshake may take.
+    pub fn launch_timeout(&self) -> Duration {
+        Duration::from_millis(self.launch_timeout_ms.unwrap_or(DEFAULT_LAUNCH_TIMEOUT_MS))
+    }
+}
+
+/// How a tool listing with repeated names is made unambiguous.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateToolPolicy {
+    /// Keep the first definition of each name.
+    #[default]
+    First,
+    /// Keep the last definition of each name, in the first one's position.
+    Last,
+    /// Fail the listing.
+    Error,
+}
+
+/// How the manager treats a server that offers no tools, prompts or resources.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyServerPolicy {
+    /// Keep the service without checking.
+    #[default]
+    Keep,
+    /// Keep the service, logging a warning.
+    Warn,
+    /// Disconnect the service and call `on_service_empty`.
+    Disconnect,
+}
+
+/// The supported subset of the MCP `initialize` request that can be set from config.
+///
+/// | Config field      | MCP field                   |
+/// |-------------------|-----------------------------|
+/// | `client_name`     | `clientInfo.name`           |
+/// | `client_version`  | `clientInfo.version`        |
+/// | `roots`           | initial `roots/list` responses; see `ZeroClient::set_roots` |
+/// | `experimental`    | `capabilities.experimental` |
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct InitOptions {
+    pub client_name: Option<String>,
+    pub client_version: Option<String>,
+    #[serde(default)]
+    pub roots: Vec<RootConfig>,
+    pub experimental: Option<BTreeMap<String, serde_json::Map<String, serde_json::Value>>>,
+}
+
+/// A filesystem root advertised to the server.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RootConfig {
+    pub uri: String,
+    pub name: Option<String>,
 }
 
 impl ZeroConfig {
-    /// Loads configuration from a TOML file.
+    /// Loads configuration from a file of at most [`DEFAULT_MAX_CONFIG_SIZE`]
+    /// bytes, read as JSON if it ends in `.json`, YAML if it ends in `.yaml`
+    /// or `.yml` (with the `yaml` feature), and TOML otherwise.
+    ///
+    /// `${VAR}` in any string value is replaced with the environment
+    /// variable's value, failing if it's unset; write `$$` for a literal `$`.
+    /// This happens once, at load time, unlike `{{...}}` templates. A JSON
+    /// config that uses them doesn't keep the declaration order of `envs`
+    /// and `headers`, since `serde_json` objects are sorted by key.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = std::fs::read_to_string(&path)
-            .with_context(|| format!("read config from {:?}", path.as_ref()))?;
-        toml::from_str(&content).context("parse zeroMCP config")
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            #[cfg(not(feature = "yaml"))]
+            Some("yaml" | "yml") => {
+                anyhow::bail!("Loading {:?} requires the `yaml` feature", path)
+            }
+            _ => ConfigFormat::Toml,
+        };
+        Self::load_as(path, format)
+    }
+
+    /// Loads configuration from a JSON file, like [`Self::load`].
+    pub fn load_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::load_as(path.as_ref(), ConfigFormat::Json)
+    }
+
+    /// Loads configuration from a YAML file, like [`Self::load`].
+    #[cfg(feature = "yaml")]
+    pub fn load_yaml<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::load_as(path.as_ref(), ConfigFormat::Yaml)
+    }
+
+    fn load_as(path: &Path, format: ConfigFormat) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("read config from {:?}", path))?;
+        let content = read_capped(file, DEFAULT_MAX_CONFIG_SIZE)
+            .with_context(|| format!("read config from {:?}", path))?;
+        Self::parse(&content, format, "parse zeroMCP config")
+    }
+
+    /// Parses and validates a config, expanding `${VAR}` references in string values.
+    fn parse(content: &str, format: ConfigFormat, context: &'static str) -> Result<Self> {
+        // Deserializing straight from text keeps line numbers in errors, so
+        // only go through the format's value type when a string value
+        // actually holds something to expand, not just a comment.
+        let expanded = if content.contains("${") || content.contains("$$") {
+            format.parse_expanded(content).context(context)?
+        } else {
+            None
+        };
+        let config: Self = match expanded {
+            Some(config) => config,
+            None => format.parse(content).context(context)?,
+        };
+        config.validate()?;
+        Ok(config)
     }
 
+    /// Checks for mistakes that parse fine but leave the manager with nothing
+    /// sensible to do, failing with [`ZeroError::InvalidConfig`] naming the
+    /// first offending field.
+    ///
+    /// `load` and `from_reader` call this; call it yourself on configs built
+    /// in code.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |field: String, reason: String| ZeroError::InvalidConfig { field, reason };
+        // Services can still arrive through `launch_synthetic` or
+        // `start_from_state`, so an empty config is only worth a warning.
+        if self.service_mappings.is_empty() && self.static_services.is_empty() {
+            warn!("No services configured; add a [[service_mapping]] or [[static_service]]");
+        }
+
+        let mut seen = HashMap::new();
+        for (i, mapping) in self.service_mappings.iter().enumerate() {
+            if mapping.zeroconf_services.is_empty() {
+                return Err(invalid(
+                    format!("service_mapping[{i}].zeroconf_service"),
+                    "no service types listed".to_string(),
+                )
+                .into());
+            }
+            if let Some(subtype) = &mapping.subtype
+                && (subtype.is_empty() || subtype.contains('.'))
+            {
+                return Err(invalid(
+                    format!("service_mapping[{i}].subtype"),
+                    format!("'{subtype}' is not a single label like \"_ai\""),
+                )
+                .into());
+            }
+            for service_type in mapping.browse_types() {
+                match seen.entry(service_type) {
+                    Entry::Occupied(first) => {
+                        return Err(invalid(
+                            format!("service_mapping[{i}].zeroconf_service"),
+                            format!(
+                                "'{}' is already mapped by service_mapping[{}]",
+                                first.key(),
+                                first.get()
+                            ),
+                        )
+                        .into());
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(i);
+                    }
+                }
+            }
+        }
+
+        let mcp_configs = self
+            .service_mappings
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (format!("service_mapping[{i}]"), &m.mcp))
+            .chain(
+                self.static_services
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (format!("static_service[{i}]"), &s.mcp)),
+            );
+        let option_paths =
+            std::iter::once(("service_defaults".to_string(), &self.service_defaults))
+                .chain(mcp_configs.clone().map(|(path, mcp)| (path, mcp.options())));
+        for (path, options) in option_paths {
+            if options.max_concurrent_calls == Some(0) {
+                return Err(invalid(
+                    format!("{path}.max_concurrent_calls"),
+                    "must be at least 1".to_string(),
+                )
+                .into());
+            }
+            if options.max_queue_depth == Some(0) {
+                return Err(invalid(
+                    format!("{path}.max_queue_depth"),
+                    "must be at least 1".to_string(),
+                )
+                .into());
+            }
+            if options
+                .circuit_breaker
+                .is_some_and(|breaker| breaker.failure_threshold == 0)
+            {
+                return Err(invalid(
+                    format!("{path}.circuit_breaker.failure_threshold"),
+                    "must be at least 1".to_string(),
+                )
+                .into());
+            }
+        }
+        for (path, mcp) in mcp_configs {
+            if let McpConfig::Sse { url, .. } | McpConfig::StreamableHttp { url, .. } = mcp {
+                check_url_template(url).map_err(|reason| invalid(format!("{path}.url"), reason))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the configuration the manager acts on as TOML, for debugging.
+    ///
+    /// Top-level defaults such as `proxy`, `compression` and
+    /// `service_defaults` are filled into the services they apply to.
+    /// Values named by `redact_keys` anywhere in `envs`, `headers`,
+    /// `request_meta`, `init_options` and schedule `args`, stdio arguments
+    /// such as `--api-key=…` or `--token …`, URL query parameters named by
+    /// them, proxy credentials and passwords in URLs are replaced with
+    /// [`REDACTED`]. Templates are shown unrendered.
+    pub fn effective(&self) -> Result<String> {
+        let mut effective = self.clone();
+        let mcp_configs = effective
+            .service_mappings
+            .iter_mut()
+            .map(|m| &mut m.mcp)
+            .chain(effective.static_services.iter_mut().map(|s| &mut s.mcp));
+        for mcp in mcp_configs {
+            *mcp = mcp.clone().with_defaults(self);
+        }
+        let mut value = toml::Value::try_from(&effective).context("serialize zeroMCP config")?;
+        redact_toml(&mut value, false, &self.redact_keys);
+        toml::to_string(&value).context("serialize zeroMCP config")
+    }
+
+    /// Returns `interval` shifted by a random amount of up to `timer_jitter`
+    /// of it in either direction, for the next tick of a periodic timer.
+    pub fn jittered(&self, interval: Duration) -> Duration {
+        let fraction = self.timer_jitter.clamp(0.0, 1.0);
+        // A fresh `RandomState` is randomly keyed, which is all the
+        // randomness this needs.
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        interval.mul_f64(1.0 + fraction * (2.0 * random - 1.0))
+    }
+
+    /// Returns whether services on `hostname` may be connected to.
+    pub fn host_allowed(&self, hostname: &str) -> bool {
+        let normalize = |host: &str| host.trim().trim_end_matches('.').to_ascii_lowercase();
+        let hostname = normalize(hostname);
+        self.host_allowlist.is_empty()
+            || self
+                .host_allowlist
+                .iter()
+                .any(|allowed| normalize(allowed) == hostname)
+    }
+
+    /// Parses TOML configuration from any reader, reading at most
+    /// [`DEFAULT_MAX_CONFIG_SIZE`] bytes. Expands `${VAR}` like [`Self::load`].
     pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
-        // Parse TOML from any reader:
-        let mut buf = String::new();
-        let mut rdr = reader;
-        rdr.read_to_string(&mut buf)?;
-        toml::from_str(&buf).context("parse zeroMCP config from reader")
+        Self::from_reader_with_limit(reader, DEFAULT_MAX_CONFIG_SIZE)
     }
+
+    /// Parses TOML configuration from a reader, failing with
+    /// [`ZeroError::ConfigTooLarge`] once more than `max_bytes` have been read,
+    /// rather than buffering an endless stream.
+    pub fn from_reader_with_limit<R: Read>(reader: R, max_bytes: u64) -> Result<Self> {
+        let buf = read_capped(reader, max_bytes)?;
+        Self::parse(&buf, ConfigFormat::Toml, "parse zeroMCP config from reader")
+    }
+}
+
+/// A file format `ZeroConfig` can be loaded from.
+#[derive(Debug, Clone, Copy)]
+enum ConfigFormat {
+    Toml,
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ConfigFormat {
+    fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(content)?,
+            ConfigFormat::Json => serde_json::from_str(content)?,
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+        })
+    }
+
+    /// Parses `content` into the format's own value type and expands `${VAR}`
+    /// references in its string values, returning `None` if none held any.
+    fn parse_expanded(self, content: &str) -> Result<Option<ZeroConfig>> {
+        let mut path = String::new();
+        Ok(match self {
+            ConfigFormat::Toml => {
+                let mut value = toml::Value::Table(toml::from_str(content)?);
+                expand_toml(&mut value, &mut path)?
+                    .then(|| value.try_into())
+                    .transpose()?
+            }
+            ConfigFormat::Json => {
+                let mut value: serde_json::Value = serde_json::from_str(content)?;
+                expand_json(&mut value, &mut path)?
+                    .then(|| serde_json::from_value(value))
+                    .transpose()?
+            }
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => {
+                let mut value: serde_yaml::Value = serde_yaml::from_str(content)?;
+                expand_yaml(&mut value, &mut path)?
+                    .then(|| serde_yaml::from_value(value))
+                    .transpose()?
+            }
+        })
+    }
+}
+
+/// Replaces `${VAR}` in every string in `value` with the environment
+/// variable's value, failing if it's unset. `$$` stands for a literal `$`.
+/// `path` is the TOML path of `value`, for errors. Returns whether any string
+/// held a reference or escape.
+fn expand_toml(value: &mut toml::Value, path: &mut String) -> Result<bool> {
+    match value {
+        toml::Value::String(s) => expand_string(s, path),
+        toml::Value::Table(table) => expand_entries(table.iter_mut(), path, expand_toml),
+        toml::Value::Array(values) => expand_items(values.iter_mut(), path, expand_toml),
+        _ => Ok(false),
+    }
+}
+
+/// Expands `${VAR}` in a JSON config, like [`expand_toml`].
+fn expand_json(value: &mut serde_json::Value, path: &mut String) -> Result<bool> {
+    match value {
+        serde_json::Value::String(s) => expand_string(s, path),
+        serde_json::Value::Object(table) => expand_entries(table.iter_mut(), path, expand_json),
+        serde_json::Value::Array(values) => expand_items(values.iter_mut(), path, expand_json),
+        _ => Ok(false),
+    }
+}
+
+/// Expands `${VAR}` in a YAML config, like [`expand_toml`].
+#[cfg(feature = "yaml")]
+fn expand_yaml(value: &mut serde_yaml::Value, path: &mut String) -> Result<bool> {
+    match value {
+        serde_yaml::Value::String(s) => expand_string(s, path),
+        serde_yaml::Value::Mapping(table) => expand_entries(
+            table
+                .iter_mut()
+                .map(|(key, value)| (key.as_str().unwrap_or_default(), value)),
+            path,
+            expand_yaml,
+        ),
+        serde_yaml::Value::Sequence(values) => expand_items(values.iter_mut(), path, expand_yaml),
+        serde_yaml::Value::Tagged(tagged) => expand_yaml(&mut tagged.value, path),
+        _ => Ok(false),
+    }
+}
+
+fn expand_string(s: &mut String, path: &str) -> Result<bool> {
+    if !s.contains("${") && !s.contains("$$") {
+        return Ok(false);
+    }
+    *s = expand_env_string(s).map_err(|reason| ZeroError::InvalidConfig {
+        field: path.to_string(),
+        reason,
+    })?;
+    Ok(true)
+}
+
+fn expand_entries<'a, K: AsRef<str>, V: 'a>(
+    entries: impl Iterator<Item = (K, &'a mut V)>,
+    path: &mut String,
+    expand: fn(&mut V, &mut String) -> Result<bool>,
+) -> Result<bool> {
+    let mut expanded = false;
+    for (key, value) in entries {
+        let len = path.len();
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key.as_ref());
+        expanded |= expand(value, path)?;
+        path.truncate(len);
+    }
+    Ok(expanded)
+}
+
+fn expand_items<'a, V: 'a>(
+    items: impl Iterator<Item = &'a mut V>,
+    path: &mut String,
+    expand: fn(&mut V, &mut String) -> Result<bool>,
+) -> Result<bool> {
+    let mut expanded = false;
+    for (i, value) in items.enumerate() {
+        let len = path.len();
+        path.push_str(&format!("[{i}]"));
+        expanded |= expand(value, path)?;
+        path.truncate(len);
+    }
+    Ok(expanded)
+}
+
+fn expand_env_string(s: &str) -> std::result::Result<String, String> {
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(dollar) = rest.find('$') {
+        expanded.push_str(&rest[..dollar]);
+        rest = &rest[dollar..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| "unclosed '${' in string".to_string())?;
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("environment variable '{name}' is not set"))?;
+            expanded.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Checks that `url` is an HTTP(S) URL once rendered, standing in `0` for
+/// each template expression. URLs that start with an expression can't be
+/// checked until they're rendered.
+fn check_url_template(url: &str) -> std::result::Result<(), String> {
+    if url.trim_start().starts_with("{{") {
+        return Ok(());
+    }
+    let mut sample = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(start) = rest.find("{{") {
+        sample.push_str(&rest[..start]);
+        sample.push('0');
+        rest = match rest[start..].find("}}") {
+            Some(end) => rest[start + end..].trim_start_matches('}'),
+            None => return Err("unclosed template expression".to_string()),
+        };
+    }
+    sample.push_str(rest);
+    let parsed = reqwest::Url::parse(&sample).map_err(|e| format!("'{url}' is not a URL: {e}"))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(format!(
+            "'{url}' has scheme '{scheme}'; expected http or https"
+        )),
+    }
+}
+
+/// Config keys holding free-form data, in which any key named by
+/// `redact_keys`, however deeply nested, is a secret.
+const FREE_FORM_KEYS: &[&str] = &["envs", "headers", "request_meta", "init_options", "args"];
+
+/// Redacts secrets in a serialized config. `free_form` is set inside one of
+/// [`FREE_FORM_KEYS`].
+fn redact_toml(value: &mut toml::Value, free_form: bool, redact_keys: &[String]) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                if matches!(key.as_str(), "password" | "username") && value.is_str()
+                    || free_form && is_sensitive(key, redact_keys)
+                {
+                    *value = toml::Value::String(REDACTED.to_string());
+                } else if let ("url", toml::Value::String(url)) = (key.as_str(), &mut *value) {
+                    redact_url_value(url, redact_keys);
+                } else if let ("args", toml::Value::Array(args)) = (key.as_str(), &mut *value) {
+                    redact_args(args, redact_keys);
+                } else {
+                    let free_form = free_form || FREE_FORM_KEYS.contains(&key.as_str());
+                    redact_toml(value, free_form, redact_keys);
+                }
+            }
+        }
+        toml::Value::Array(values) => {
+            for value in values {
+                redact_toml(value, free_form, redact_keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Redacts the values of command-line flags named by `redact_keys`, whether
+/// given as `--flag=value` or as `--flag value`.
+fn redact_args(args: &mut [toml::Value], redact_keys: &[String]) {
+    let mut value_is_secret = false;
+    for arg in args {
+        let toml::Value::String(arg) = arg else {
+            value_is_secret = false;
+            continue;
+        };
+        if std::mem::take(&mut value_is_secret) && !arg.starts_with('-') {
+            *arg = REDACTED.to_string();
+            continue;
+        }
+        let Some(flag) = arg
+            .strip_prefix('-')
+            .map(|flag| flag.trim_start_matches('-'))
+        else {
+            continue;
+        };
+        match flag.split_once('=') {
+            Some((name, _)) if is_sensitive(name, redact_keys) => {
+                let value_start = arg.len() - flag.len() + name.len() + 1;
+                arg.replace_range(value_start.., REDACTED);
+            }
+            Some(_) => {}
+            None => value_is_secret = is_sensitive(flag, redact_keys),
+        }
+    }
+}
+
+/// Redacts the password in a `url` config value, and its query parameters
+/// named by `redact_keys`. A templated URL isn't a valid URL until rendered,
+/// and parsing it would percent-encode its `{{…}}` markers, so its userinfo
+/// is redacted as text instead.
+fn redact_url_value(url: &mut String, redact_keys: &[String]) {
+    redact_url_query(url, redact_keys);
+    if !url.contains("{{") {
+        redact_url_password(url);
+        return;
+    }
+    let Some(start) = url.find("://").map(|i| i + 3) else {
+        return;
+    };
+    let end = url[start..].find('/').map_or(url.len(), |i| start + i);
+    let Some(at) = url[start..end].rfind('@').map(|i| start + i) else {
+        return;
+    };
+    if let Some(colon) = url[start..at].find(':').map(|i| start + i) {
+        url.replace_range(colon + 1..at, REDACTED);
+    }
+}
+
+/// Redacts the values of the query parameters in `url` named by
+/// `redact_keys`, editing it as text so templates stay intact.
+fn redact_url_query(url: &mut String, redact_keys: &[String]) {
+    let Some(start) = url.find('?').map(|i| i + 1) else {
+        return;
+    };
+    let end = url[start..].find('#').map_or(url.len(), |i| start + i);
+    let query: Vec<String> = url[start..end]
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name, redact_keys) => format!("{name}={REDACTED}"),
+            _ => pair.to_string(),
+        })
+        .collect();
+    url.replace_range(start..end, &query.join("&"));
+}
+
+/// Reads `reader` to the end, failing once it yields more than `max_bytes`.
+fn read_capped<R: Read>(reader: R, max_bytes: u64) -> Result<String> {
+    let mut buf = String::new();
+    reader
+        .take(max_bytes.saturating_add(1))
+        .read_to_string(&mut buf)?;
+    if buf.len() as u64 > max_bytes {
+        return Err(ZeroError::ConfigTooLarge { limit: max_bytes }.into());
+    }
+    Ok(buf)
 }
 
 #[cfg(test)]
@@ -78,8 +1246,8 @@ mod tests {
 
         let stdio_mapping = &config.service_mappings[0];
         assert_eq!(
-            stdio_mapping.zeroconf_service,
-            "_my-service._mcp._tcp.local."
+            stdio_mapping.zeroconf_services,
+            ["_my-service._mcp._tcp.local."]
         );
         if let McpConfig::Stdio { command, .. } = &stdio_mapping.mcp {
             assert_eq!(command, "/usr/bin/my_tool");
@@ -89,8 +1257,8 @@ mod tests {
 
         let sse_mapping = &config.service_mappings[1];
         assert_eq!(
-            sse_mapping.zeroconf_service,
-            "_sse-service._mcp._tcp.local."
+            sse_mapping.zeroconf_services,
+            ["_sse-service._mcp._tcp.local."]
         );
         if let McpConfig::Sse { url, .. } = &sse_mapping.mcp {
             assert_eq!(url, "http://localhost:8080/sse");
@@ -99,6 +1267,381 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_config_with_streamable_http() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_http-service._mcp._tcp.local."
+            protocol = "streamable-http"
+            name = "My HTTP Tool"
+            url = "http://{{service.hostname}}:{{service.port}}/mcp"
+            headers = { Authorization = "Bearer {{TOKEN}}" }
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+
+        if let McpConfig::StreamableHttp { url, headers, .. } = &config.service_mappings[0].mcp {
+            assert_eq!(url, "http://{{service.hostname}}:{{service.port}}/mcp");
+            assert_eq!(
+                headers.as_ref().unwrap()["Authorization"],
+                "Bearer {{TOKEN}}"
+            );
+        } else {
+            panic!("Expected StreamableHttp config");
+        }
+    }
+
+    #[test]
+    fn test_default_proxy_applies_to_sse_without_own_proxy() {
+        let toml_content = r#"
+            [proxy]
+            url = "http://corp-proxy:3128"
+            no_proxy = "192.168.0.0/16,.local"
+
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "sse"
+            name = "A"
+            url = "http://{{service.hostname}}/sse"
+
+            [[service_mapping]]
+            zeroconf_service = "_b._mcp._tcp.local."
+            protocol = "sse"
+            name = "B"
+            url = "http://{{service.hostname}}/sse"
+            proxy = { url = "socks5://other:1080" }
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let proxy_url =
+            |mcp: &McpConfig| match mcp.clone().with_default_proxy(config.proxy.as_ref()) {
+                McpConfig::Sse { proxy, .. } | McpConfig::StreamableHttp { proxy, .. } => {
+                    proxy.map(|p| p.url)
+                }
+                McpConfig::Stdio { .. } => None,
+            };
+
+        assert_eq!(
+            proxy_url(&config.service_mappings[0].mcp).as_deref(),
+            Some("http://corp-proxy:3128")
+        );
+        assert_eq!(
+            proxy_url(&config.service_mappings[1].mcp).as_deref(),
+            Some("socks5://other:1080")
+        );
+    }
+
+    #[test]
+    fn test_effective_config_is_redacted_and_reloadable() {
+        let toml_content = r#"
+            [proxy]
+            url = "http://corp-proxy:3128"
+            password = "hunter2"
+
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "sse"
+            name = "A"
+            url = "http://user:pass@{{service.hostname}}/sse"
+            headers = { Authorization = "Bearer abc", Accept = "text/event-stream" }
+
+            [[service_mapping]]
+            zeroconf_service = "_b._mcp._tcp.local."
+            protocol = "stdio"
+            name = "B"
+            command = "tool"
+            args = []
+            envs = { API_TOKEN = "abc", MODE = "fast" }
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let effective = config.effective().unwrap();
+
+        for secret in ["hunter2", "pass@", "Bearer abc", "\"abc\""] {
+            assert!(!effective.contains(secret), "{secret} leaked:\n{effective}");
+        }
+        assert!(effective.contains("text/event-stream"));
+        assert!(effective.contains("fast"));
+        assert!(effective.contains("http://user:[REDACTED]@{{service.hostname}}/sse"));
+
+        let reloaded = ZeroConfig::from_reader(effective.as_bytes()).unwrap();
+        match &reloaded.service_mappings[0].mcp {
+            McpConfig::Sse {
+                proxy, compression, ..
+            } => {
+                assert_eq!(proxy.as_ref().unwrap().url, "http://corp-proxy:3128");
+                assert_eq!(*compression, Some(false));
+            }
+            _ => panic!("Expected Sse config"),
+        }
+    }
+
+    #[test]
+    fn test_effective_config_redacts_args_queries_and_nested_data() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "stdio"
+            name = "A"
+            command = "tool"
+            args = ["--api-key=k1", "--token", "t1", "--verbose", "-x"]
+            request_meta = { tenant = "acme", auth = { client_secret = "s1" } }
+            init_options = { nested = { access_token = "t2" } }
+
+            [[service_mapping]]
+            zeroconf_service = "_b._mcp._tcp.local."
+            protocol = "sse"
+            name = "B"
+            url = "https://{{service.hostname}}/sse?region=eu&token=t3"
+            proxy = { url = "http://proxy:3128", username = "me", password = "p1" }
+
+            [[schedule]]
+            id = "sync"
+            service = "svc"
+            tool = "sync"
+            interval_ms = 1000
+            args = { api_key = "k2", limit = 10 }
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let effective = config.effective().unwrap();
+
+        for secret in ["k1", "\"t1\"", "s1", "t2", "t3", "\"me\"", "p1", "k2"] {
+            assert!(!effective.contains(secret), "{secret} leaked:\n{effective}");
+        }
+        for kept in ["--verbose", "\"-x\"", "acme", "region=eu", "limit = 10"] {
+            assert!(effective.contains(kept), "{kept} missing:\n{effective}");
+        }
+        assert!(effective.contains("--api-key=[REDACTED]"));
+        assert!(effective.contains("https://{{service.hostname}}/sse?region=eu&token=[REDACTED]"));
+        ZeroConfig::from_reader(effective.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_service_defaults_fill_unset_options() {
+        let toml_content = r#"
+            health_check_interval_ms = 60000
+
+            [service_defaults]
+            tool_call_timeout_ms = 5000
+            relaunch_cooldown_ms = 200
+            empty_server_policy = "disconnect"
+            duplicate_tools = "last"
+            idle_timeout_ms = 300000
+            circuit_breaker = { failure_threshold = 2 }
+
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "stdio"
+            name = "A"
+            command = "tool"
+            args = []
+            tool_call_timeout_ms = 1000
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let mcp = config.service_mappings[0]
+            .mcp
+            .clone()
+            .with_defaults(&config);
+        assert_eq!(mcp.options().tool_call_timeout_ms, Some(1000));
+        assert_eq!(mcp.options().relaunch_cooldown_ms, Some(200));
+        assert_eq!(mcp.options().max_queue_depth, None);
+        assert_eq!(
+            mcp.options().empty_server_policy,
+            Some(EmptyServerPolicy::Disconnect)
+        );
+        assert_eq!(
+            mcp.options().duplicate_tools,
+            Some(DuplicateToolPolicy::Last)
+        );
+        assert_eq!(mcp.options().health_check_interval_ms, Some(60_000));
+        assert_eq!(mcp.options().idle_timeout_ms, Some(300_000));
+        assert_eq!(
+            mcp.options().circuit_breaker,
+            Some(CircuitBreaker {
+                failure_threshold: 2,
+                reset_timeout_ms: 30_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_restart_backoff_doubles() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "stdio"
+            name = "A"
+            command = "tool"
+            args = []
+
+            [service_mapping.restart]
+            backoff_ms = 100
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let policy = config.service_mappings[0].mcp.options().restart.unwrap();
+        assert_eq!(policy.max_retries, 3);
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_host_allowlist() {
+        let config: ZeroConfig = toml::from_str("").unwrap();
+        assert!(config.host_allowed("anything.local."));
+
+        let config: ZeroConfig =
+            toml::from_str(r#"host_allowlist = ["nas.local", "Pi.local."]"#).unwrap();
+        assert!(config.host_allowed("nas.local."));
+        assert!(config.host_allowed("pi.local"));
+        assert!(!config.host_allowed("laptop.local."));
+    }
+
+    #[test]
+    fn test_required_capabilities() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            required_capabilities = ["gpu", "fs"]
+            protocol = "sse"
+            name = "A"
+            url = "http://{{service.hostname}}/sse"
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let mapping = &config.service_mappings[0];
+        let mut service = DiscoveredService::new_static("svc");
+
+        assert_eq!(mapping.missing_capability(&service), Some("gpu"));
+        service
+            .properties
+            .insert("caps".to_string(), "fs, gpu,,net".to_string());
+        assert_eq!(mapping.missing_capability(&service), None);
+    }
+
+    #[test]
+    fn test_mapping_covers_its_instances_only() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "sse"
+            name = "A"
+            url = "http://{{service.hostname}}/sse"
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let mapping = &config.service_mappings[0];
+        let covers = |name| mapping.covers(&DiscoveredService::new_static(name));
+
+        assert!(covers("box._a._mcp._tcp.local."));
+        assert!(!covers("box._b._mcp._tcp.local."));
+        assert!(!covers("box_a._mcp._tcp.local."));
+    }
+
+    #[test]
+    fn test_txt_filter() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            filter = { env = "prod" }
+            protocol = "stdio"
+            name = "A"
+            command = "tool"
+            args = []
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let mapping = &config.service_mappings[0];
+        let mut service = DiscoveredService::new_static("a._a._mcp._tcp.local.");
+        assert_eq!(mapping.unmatched_filter(&service), Some("env"));
+        service
+            .properties
+            .insert("env".to_string(), "staging".to_string());
+        assert_eq!(mapping.unmatched_filter(&service), Some("env"));
+        service
+            .properties
+            .insert("env".to_string(), "prod".to_string());
+        assert_eq!(mapping.unmatched_filter(&service), None);
+    }
+
+    #[test]
+    fn test_jittered_stays_within_fraction() {
+        let config: ZeroConfig = toml::from_str("timer_jitter = 0.25").unwrap();
+        let interval = Duration::from_secs(10);
+        for _ in 0..100 {
+            let next = config.jittered(interval);
+            assert!(next >= Duration::from_millis(7500) && next <= Duration::from_millis(12500));
+        }
+
+        let config: ZeroConfig = toml::from_str("timer_jitter = 0.0").unwrap();
+        assert_eq!(config.jittered(interval), interval);
+    }
+
+    #[test]
+    fn test_envs_keep_declaration_order() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_my-service._mcp._tcp.local."
+            protocol = "stdio"
+            name = "My Stdio Tool"
+            command = "/usr/bin/my_tool"
+            args = []
+
+            [service_mapping.envs]
+            ZULU = "{{Z}}"
+            ALPHA = "{{A}}"
+            MIKE = "{{M}}"
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+
+        let McpConfig::Stdio { envs, .. } = &config.service_mappings[0].mcp else {
+            panic!("Expected Stdio config");
+        };
+        let keys: Vec<&str> = envs.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["ZULU", "ALPHA", "MIKE"]);
+    }
+
+    #[test]
+    fn test_load_config_with_init_options() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_fs._mcp._tcp.local."
+            protocol = "stdio"
+            name = "File Server"
+            command = "/usr/bin/fs_server"
+            args = []
+
+            [service_mapping.init_options]
+            client_name = "my-editor"
+            roots = [{ uri = "file:///{{WORKSPACE}}", name = "workspace" }]
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let init = config.service_mappings[0]
+            .mcp
+            .options()
+            .init_options
+            .clone()
+            .expect("init_options should be parsed");
+
+        let opts: InitOptions = serde_json::from_value(init).unwrap();
+        assert_eq!(opts.client_name.as_deref(), Some("my-editor"));
+        assert_eq!(opts.roots.len(), 1);
+        assert_eq!(opts.roots[0].uri, "file:///{{WORKSPACE}}");
+    }
+
+    #[test]
+    fn test_load_config_with_static_service() {
+        let toml_content = r#"
+            [[static_service]]
+            service_name = "remote-search"
+            protocol = "sse"
+            name = "Remote Search"
+            url = "https://mcp.example.com/sse"
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+
+        assert!(config.service_mappings.is_empty());
+        assert_eq!(config.static_services.len(), 1);
+        assert_eq!(config.static_services[0].service_name, "remote-search");
+        assert!(matches!(
+            config.static_services[0].mcp,
+            McpConfig::Sse { .. }
+        ));
+    }
+
     #[test]
     fn test_load_config_from_reader_invalid_toml() {
         let toml_content = "this is not toml";
@@ -119,4 +1662,203 @@ mod tests {
         let result = ZeroConfig::from_reader(toml_content.as_bytes());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_mapping_with_subtype() {
+        let mapping = |subtype: &str| {
+            format!(
+                r#"
+                [[service_mapping]]
+                zeroconf_service = "_mcp._tcp.local."
+                subtype = "{subtype}"
+                protocol = "sse"
+                name = "AI"
+                url = "http://{{{{service.hostname}}}}/sse"
+
+                [[service_mapping]]
+                zeroconf_service = "_mcp._tcp.local."
+                protocol = "sse"
+                name = "Any"
+                url = "http://{{{{service.hostname}}}}/sse"
+            "#
+            )
+        };
+        let config = ZeroConfig::from_reader(mapping("_ai").as_bytes()).unwrap();
+        assert_eq!(config.service_mappings[0].subtype.as_deref(), Some("_ai"));
+        assert_eq!(
+            config.service_mappings[0]
+                .browse_types()
+                .collect::<Vec<_>>(),
+            ["_ai._sub._mcp._tcp.local."]
+        );
+        assert_eq!(
+            config.service_mappings[1]
+                .browse_types()
+                .collect::<Vec<_>>(),
+            ["_mcp._tcp.local."]
+        );
+
+        for invalid in ["", "_ai._sub"] {
+            let err = ZeroConfig::from_reader(mapping(invalid).as_bytes()).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(ZeroError::InvalidConfig { field, .. }) if field == "service_mapping[0].subtype"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_mapping_with_several_service_types() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = ["_mcp._tcp.local.", "_ai-mcp._tcp.local."]
+            protocol = "sse"
+            name = "A"
+            url = "http://{{service.hostname}}/sse"
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        assert_eq!(
+            config.service_mappings[0].zeroconf_services,
+            ["_mcp._tcp.local.", "_ai-mcp._tcp.local."]
+        );
+        let reloaded = ZeroConfig::from_reader(config.effective().unwrap().as_bytes()).unwrap();
+        assert_eq!(
+            reloaded.service_mappings[0].zeroconf_services,
+            config.service_mappings[0].zeroconf_services
+        );
+
+        let overlapping = format!(
+            "{toml_content}{}",
+            toml_content.replace(
+                r#"["_mcp._tcp.local.", "_ai-mcp._tcp.local."]"#,
+                r#""_ai-mcp._tcp.local.""#
+            )
+        );
+        let err = ZeroConfig::from_reader(overlapping.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ZeroError::InvalidConfig { field, .. }) if field == "service_mapping[1].zeroconf_service"
+        ));
+    }
+
+    #[test]
+    fn test_validate_names_offending_field() {
+        let field = |toml_content: &str| match ZeroConfig::from_reader(toml_content.as_bytes())
+            .unwrap_err()
+            .downcast::<ZeroError>()
+        {
+            Ok(ZeroError::InvalidConfig { field, .. }) => field,
+            other => panic!("Expected InvalidConfig, got {other:?}"),
+        };
+        let mapping = |service: &str, url: &str| {
+            format!(
+                "[[service_mapping]]\nzeroconf_service = \"{service}\"\nprotocol = \"sse\"\nname = \"A\"\nurl = \"{url}\"\n"
+            )
+        };
+
+        assert!(ZeroConfig::from_reader("".as_bytes()).is_ok());
+        let duplicated =
+            mapping("_a._tcp.local.", "http://a/sse") + &mapping("_a._tcp.local.", "http://b/sse");
+        assert_eq!(field(&duplicated), "service_mapping[1].zeroconf_service");
+        assert_eq!(
+            field(&mapping("_a._tcp.local.", "localhost:8080/sse")),
+            "service_mapping[0].url"
+        );
+        assert_eq!(
+            field(&mapping("_a._tcp.local.", "http://{{service.hostname}/sse")),
+            "service_mapping[0].url"
+        );
+        assert_eq!(
+            field(&(mapping("_a._tcp.local.", "http://a/sse") + "max_concurrent_calls = 0\n")),
+            "service_mapping[0].max_concurrent_calls"
+        );
+
+        for url in [
+            "http://{{service.hostname}}:{{service.port}}/sse",
+            "{{service.properties.url}}",
+        ] {
+            assert!(ZeroConfig::from_reader(mapping("_a._tcp.local.", url).as_bytes()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_expands_env_vars_in_strings() {
+        let path = std::env::var("PATH").unwrap();
+        let toml_content = r#"
+            [[static_service]]
+            service_name = "a"
+            protocol = "sse"
+            name = "A"
+            url = "http://a/sse?cost=$$5"
+            headers = { X-Path = "${PATH}:extra" }
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let McpConfig::Sse { url, headers, .. } = &config.static_services[0].mcp else {
+            panic!("Expected Sse config");
+        };
+        assert_eq!(url, "http://a/sse?cost=$5");
+        assert_eq!(headers.as_ref().unwrap()["X-Path"], format!("{path}:extra"));
+
+        let unset = toml_content.replace("${PATH}", "${ZEROMCP_TEST_UNSET_VAR}");
+        let err = ZeroConfig::from_reader(unset.as_bytes()).unwrap_err();
+        let Some(ZeroError::InvalidConfig { field, reason }) = err.downcast_ref() else {
+            panic!("Expected InvalidConfig, got {err:?}");
+        };
+        assert_eq!(field, "static_service[0].headers.X-Path");
+        assert!(reason.contains("ZEROMCP_TEST_UNSET_VAR"));
+
+        // References outside string values are left alone, and errors keep
+        // their line numbers.
+        let commented = "# uses ${ZEROMCP_TEST_UNSET_VAR}\ndebounce_ms = \"soon\"\n";
+        let err = ZeroConfig::from_reader(commented.as_bytes()).unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+    }
+
+    #[test]
+    fn test_load_round_trips_json_and_yaml() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "stdio"
+            name = "A"
+            command = "tool"
+            args = ["--stdio"]
+            envs = { Z = "1", A = "$$2" }
+            tool_call_timeout_ms = 500
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let McpConfig::Stdio { envs, .. } = &config.service_mappings[0].mcp else {
+            panic!("Expected Stdio config");
+        };
+        assert_eq!(envs.keys().collect::<Vec<_>>(), ["Z", "A"]);
+
+        let expected = serde_json::to_value(&config).unwrap();
+        let encoded = [
+            ("json", serde_json::to_string(&config).unwrap()),
+            #[cfg(feature = "yaml")]
+            ("yaml", serde_yaml::to_string(&config).unwrap()),
+        ];
+        for (extension, content) in encoded {
+            let path = std::env::temp_dir()
+                .join(format!("zeromcp-config-{}.{extension}", std::process::id()));
+            std::fs::write(&path, content).unwrap();
+            let loaded = ZeroConfig::load(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(serde_json::to_value(loaded.unwrap()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_from_reader_rejects_oversized_input() {
+        let endless = std::io::repeat(b'#');
+        let err = ZeroConfig::from_reader_with_limit(endless, 1024).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(ZeroError::ConfigTooLarge { limit: 1024 })
+        ));
+
+        let config = r#"static_service = [{ service_name = "a", protocol = "sse", name = "A", url = "http://a/sse" }]"#;
+        let limit = config.len() as u64;
+        assert!(ZeroConfig::from_reader_with_limit(config.as_bytes(), limit).is_ok());
+    }
 }
@@ -0,0 +1,198 @@
+use std::{fmt, io, sync::Arc, time::Duration};
+use thiserror::Error;
+
+/// Typed errors surfaced by ZeroMCP.
+///
+/// Methods return `anyhow::Result`; use `error.downcast_ref::<ZeroError>()` to
+/// distinguish these cases from other failures.
+#[derive(Debug, Error)]
+pub enum ZeroError {
+    /// A tool call did not complete within its timeout. A cancellation
+    /// notification has been sent to the server.
+    #[error("Tool call '{tool}' timed out after {elapsed:?}")]
+    ToolCallTimeout { tool: String, elapsed: Duration },
+
+    /// A tool call was aborted through its cancellation token. If it had
+    /// reached the server, a cancellation notification has been sent.
+    #[error("Tool call '{tool}' was cancelled")]
+    ToolCallCancelled { tool: String },
+
+    /// A listing, resource read or prompt request did not complete within the
+    /// service's `request_timeout_ms`. A cancellation notification has been
+    /// sent to the server.
+    #[error("Request timed out after {timeout:?}")]
+    RequestTimeout { timeout: Duration },
+
+    /// The OS could not allocate the pipes or process needed to launch a stdio
+    /// service, typically because the open file descriptor limit was hit.
+    #[error(
+        "Out of OS resources launching '{command}': {source}. \
+         Raise the open file limit (e.g. `ulimit -n`) or launch fewer services at once"
+    )]
+    ResourceExhausted {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A service didn't finish connecting and completing the MCP handshake
+    /// within its `launch_timeout_ms`.
+    #[error("Service did not finish connecting within {timeout:?}")]
+    LaunchTimeout { timeout: Duration },
+
+    /// A configuration source held more than the allowed number of bytes.
+    #[error("Configuration exceeds the {limit}-byte size limit")]
+    ConfigTooLarge { limit: u64 },
+
+    /// The configuration parsed but can't work as written. `field` is its
+    /// path in the TOML, e.g. `service_mapping[1].url`.
+    #[error("Invalid config at `{field}`: {reason}")]
+    InvalidConfig { field: String, reason: String },
+
+    /// `max_pending_requests` requests were already waiting on the manager,
+    /// under `overload_policy = "error"`. Back off and retry.
+    #[error("Manager overloaded: {limit} requests already pending")]
+    Overloaded { limit: usize },
+
+    /// The service already had `max_queue_depth` tool calls waiting.
+    #[error("Call queue for '{service}' is full ({depth} calls)")]
+    QueueFull { service: String, depth: usize },
+
+    /// The service's `circuit_breaker` is open after repeated failures, so
+    /// the tool call wasn't sent. Retry once its `reset_timeout_ms` has passed.
+    #[error("Circuit open for '{service}' after {failures} consecutive failures")]
+    CircuitOpen { service: String, failures: u32 },
+
+    /// The service is being drained for a migration to a preferred replica,
+    /// so it takes no new tool calls. Retry on the replica replacing it.
+    #[error("Service '{service}' is draining and takes no new calls")]
+    Draining { service: String },
+
+    /// No active service goes by the name a request was addressed to.
+    /// `action` says what was being attempted, e.g. `to list tools`.
+    #[error("Service '{service}' not found {action}.")]
+    ServiceNotFound { service: String, action: String },
+
+    /// A streaming tool call's unread fragments were dropped to stay within
+    /// `memory_budget_bytes`, so its output is incomplete. The stream ends
+    /// with this error and the call is cancelled.
+    #[error("Dropped {bytes} bytes of unread stream output to stay within the memory budget")]
+    FragmentsShed { bytes: usize },
+}
+
+impl ZeroError {
+    /// Copies the error for each caller sharing one failure. `io::Error`
+    /// isn't `Clone`, so a `ResourceExhausted` source keeps only its OS code,
+    /// or its kind and message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Self::ToolCallTimeout { tool, elapsed } => Self::ToolCallTimeout {
+                tool: tool.clone(),
+                elapsed: *elapsed,
+            },
+            Self::ToolCallCancelled { tool } => Self::ToolCallCancelled { tool: tool.clone() },
+            Self::RequestTimeout { timeout } => Self::RequestTimeout { timeout: *timeout },
+            Self::ResourceExhausted { command, source } => Self::ResourceExhausted {
+                command: command.clone(),
+                source: match source.raw_os_error() {
+                    Some(code) => io::Error::from_raw_os_error(code),
+                    None => io::Error::new(source.kind(), source.to_string()),
+                },
+            },
+            Self::LaunchTimeout { timeout } => Self::LaunchTimeout { timeout: *timeout },
+            Self::ConfigTooLarge { limit } => Self::ConfigTooLarge { limit: *limit },
+            Self::InvalidConfig { field, reason } => Self::InvalidConfig {
+                field: field.clone(),
+                reason: reason.clone(),
+            },
+            Self::Overloaded { limit } => Self::Overloaded { limit: *limit },
+            Self::QueueFull { service, depth } => Self::QueueFull {
+                service: service.clone(),
+                depth: *depth,
+            },
+            Self::CircuitOpen { service, failures } => Self::CircuitOpen {
+                service: service.clone(),
+                failures: *failures,
+            },
+            Self::Draining { service } => Self::Draining {
+                service: service.clone(),
+            },
+            Self::ServiceNotFound { service, action } => Self::ServiceNotFound {
+                service: service.clone(),
+                action: action.clone(),
+            },
+            Self::FragmentsShed { bytes } => Self::FragmentsShed { bytes: *bytes },
+        }
+    }
+
+    /// Reports that no active service is named `service`, `action` being
+    /// what the request was for.
+    pub(crate) fn service_not_found(service: &str, action: impl Into<String>) -> anyhow::Error {
+        Self::ServiceNotFound {
+            service: service.to_string(),
+            action: action.into(),
+        }
+        .into()
+    }
+
+    /// Returns whether `error` means the process or file descriptor table is full.
+    pub(crate) fn is_resource_exhaustion(error: &io::Error) -> bool {
+        // ERROR_TOO_MANY_OPEN_FILES / ERROR_NO_SYSTEM_RESOURCES on Windows.
+        #[cfg(unix)]
+        const CODES: &[i32] = &[libc::EMFILE, libc::ENFILE];
+        #[cfg(windows)]
+        const CODES: &[i32] = &[4, 1450];
+        #[cfg(not(any(unix, windows)))]
+        const CODES: &[i32] = &[];
+
+        // `fork` reports hitting the process limit as EAGAIN.
+        (cfg!(unix) && error.kind() == io::ErrorKind::WouldBlock)
+            || error
+                .raw_os_error()
+                .is_some_and(|code| CODES.contains(&code))
+    }
+}
+
+/// An error shared by several callers, such as those who joined one
+/// idempotent tool call. It displays and chains like the original.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedError(pub(crate) Arc<anyhow::Error>);
+
+impl SharedError {
+    /// Returns a copy of `error` for one of its callers. A `ZeroError` is
+    /// duplicated, so callers can still `downcast_ref` it.
+    pub(crate) fn copy(error: &Arc<anyhow::Error>) -> anyhow::Error {
+        match error.downcast_ref::<ZeroError>() {
+            Some(e) => e.duplicate().into(),
+            None => Self(error.clone()).into(),
+        }
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_resource_exhaustion() {
+        assert!(ZeroError::is_resource_exhaustion(
+            &io::Error::from_raw_os_error(libc::EMFILE)
+        ));
+        assert!(!ZeroError::is_resource_exhaustion(&io::Error::from(
+            io::ErrorKind::NotFound
+        )));
+    }
+}
@@ -1,18 +1,112 @@
-use crate::models::DiscoveredService;
-use anyhow::Result;
+use crate::{
+    config::McpConfig,
+    models::{DiscoveredService, RenderedLaunch},
+    utils::normalize_fullname,
+};
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use rmcp::service::QuitReason;
+use rmcp::{
+    model::{CallToolResult, CreateMessageRequestParam, CreateMessageResult, LoggingLevel},
+    service::QuitReason,
+};
+use serde_json::Value;
+use std::collections::HashMap;
 
 /// A trait for handling service lifecycle events.
 ///
 /// Implement this trait to react to services appearing and disappearing on the network.
 #[async_trait]
 pub trait ServiceEventHandler: Send + Sync {
+    /// Called when a service is about to be launched, with the configuration
+    /// it will be launched from (defaults applied, templates unrendered).
+    ///
+    /// `on_service_started` follows if the launch succeeds.
+    async fn on_service_discovered(&self, _service: &DiscoveredService, _config: &McpConfig) {}
+
     /// Called when a new service has been discovered, configured, and is now running.
     async fn on_service_started(&self, service: &DiscoveredService);
 
+    /// Called when a service connected successfully but was disconnected because it
+    /// didn't meet the configured requirements (e.g. `require_tool`).
+    async fn on_service_rejected(&self, _service: &DiscoveredService, _reason: &str) {}
+
+    /// Called when a service connected successfully but was disconnected because it
+    /// offered no tools, prompts or resources, under `empty_server_policy = "disconnect"`.
+    async fn on_service_empty(&self, _service: &DiscoveredService) {}
+
+    /// Called before a running service is stopped, to do cleanup tied to it.
+    /// The service has already been taken out of rotation, so it's called once
+    /// however many stops race. The stop waits for this to return, and is
+    /// followed by `on_service_stopped`.
+    async fn on_before_service_stop(&self, _service_name: &str) {}
+
+    /// Called with the rendered launch parameters right before a process is
+    /// spawned or a transport connects, with secrets redacted.
+    ///
+    /// Returning an error vetoes the launch, which then fails with that error.
+    async fn on_launch(&self, _service_name: &str, _launch: RenderedLaunch) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a higher-priority replica of `identity` starts launching
+    /// while the replicas in `from` are active. They keep serving until it is up.
+    async fn on_migration_started(
+        &self,
+        _identity: &str,
+        _from: &[String],
+        _to: &DiscoveredService,
+    ) {
+    }
+
+    /// Called once the replicas in `from` have been drained and stopped in
+    /// favour of `to`.
+    async fn on_migration_completed(
+        &self,
+        _identity: &str,
+        _from: &[String],
+        _to: &DiscoveredService,
+    ) {
+    }
+
+    /// Called when a service fails to launch, its process exits unexpectedly,
+    /// restarting it fails for good, or it fails a health check.
+    ///
+    /// `on_service_stopped` follows for services that had been running.
+    async fn on_service_error(&self, _service_name: &str, _error: &anyhow::Error) {}
+
+    /// Called with each line a stdio service writes to stderr, without the
+    /// line ending. Lines are delivered in order; a slow handler holds up the
+    /// process once the pipe fills.
+    async fn on_service_log(&self, _service_name: &str, _line: &str) {}
+
+    /// Called with each `notifications/message` logging event a service sends,
+    /// with its severity so consumers can filter. `data` is whatever JSON the
+    /// server attached, often just a string.
+    async fn on_log_message(&self, _service_name: &str, _level: LoggingLevel, _data: &Value) {}
+
+    /// Called when a service reports that a resource subscribed to with
+    /// `ZeroClient::subscribe_resource` has changed. Read it again with
+    /// `ZeroClient::read_resource` to get the new contents.
+    async fn on_resource_updated(&self, _service_name: &str, _uri: &str) {}
+
     /// Called when a running service has been stopped.
     async fn on_service_stopped(&self, service_name: &str, reason: QuitReason);
+
+    /// Called with the full, sorted list of active service names whenever a
+    /// service is added to or removed from the active set.
+    ///
+    /// Rapid changes may be coalesced, so only the latest set is guaranteed to
+    /// be delivered. This makes it a convenient signal to re-render from.
+    async fn on_active_set_changed(&self, _active: Vec<String>) {}
+
+    /// Called with the outcome of each call made by a `[[schedule]]` entry.
+    async fn on_scheduled_result(&self, _schedule_id: &str, _result: Result<CallToolResult>) {}
+
+    /// Called when another callback on this handler panicked while handling a service.
+    ///
+    /// The panic has already been caught and logged; what happens to the service
+    /// is governed by the configured `handler_panic_policy`.
+    async fn on_handler_panic(&self, _service_name: &str, _message: &str) {}
 }
 
 /// A trait for providing user input when required by the library.
@@ -30,6 +124,99 @@ pub trait UserInputProvider: Send + Sync {
     /// # Returns
     /// A `Result` containing the string value provided by the user.
     async fn request_input(&self, service_name: &str, key: &str) -> Result<String>;
+
+    /// Like `request_input`, but with the full service, for prompts that show
+    /// its hostname, port or TXT properties. This is what ZeroMCP calls; the
+    /// default delegates to `request_input` with the service's full name.
+    ///
+    /// # Arguments
+    /// * `service` - The service requiring input.
+    /// * `key` - The name of the variable that needs a value.
+    async fn request_input_for(&self, service: &DiscoveredService, key: &str) -> Result<String> {
+        self.request_input(&service.fullname, key).await
+    }
+}
+
+/// A non-interactive `UserInputProvider` that answers from fixed values, for
+/// CI and daemons where nobody can be prompted.
+///
+/// Values set for a specific service take precedence over shared ones.
+/// Requests for keys with no value fail, which fails the launch.
+///
+/// Delegate to it from your handler:
+///
+/// ```ignore
+/// #[async_trait]
+/// impl UserInputProvider for MyApp {
+///     async fn request_input(&self, service_name: &str, key: &str) -> Result<String> {
+///         self.secrets.request_input(service_name, key).await
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticInputProvider {
+    values: HashMap<String, String>,
+    service_values: HashMap<(String, String), String>,
+}
+
+impl StaticInputProvider {
+    /// Creates a provider answering every service from `values`, keyed by variable name.
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Self {
+            values,
+            service_values: HashMap::new(),
+        }
+    }
+
+    /// Sets the value of `key` for one service only.
+    ///
+    /// # Arguments
+    /// * `service_name` - The full name of the service.
+    /// * `key` - The name of the variable.
+    /// * `value` - The value to answer with.
+    pub fn with_service_value(
+        mut self,
+        service_name: &str,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.service_values
+            .insert((normalize_fullname(service_name), key.into()), value.into());
+        self
+    }
+}
+
+#[async_trait]
+impl UserInputProvider for StaticInputProvider {
+    async fn request_input(&self, service_name: &str, key: &str) -> Result<String> {
+        self.service_values
+            .get(&(normalize_fullname(service_name), key.to_string()))
+            .or_else(|| self.values.get(key))
+            .cloned()
+            .ok_or_else(|| anyhow!("No value for '{}' needed by '{}'", key, service_name))
+    }
+}
+
+/// A trait for answering `sampling/createMessage` requests sent by servers.
+///
+/// Servers use sampling to ask the client to run an LLM completion on their behalf.
+/// Sampling is only advertised to servers when a handler is registered via
+/// `ZeroMcpBuilder::sampling_handler`; without one, such requests are rejected.
+#[async_trait]
+pub trait SamplingHandler: Send + Sync {
+    /// Called when a service requests an LLM completion.
+    ///
+    /// # Arguments
+    /// * `service_name` - The name of the service making the request.
+    /// * `params` - The messages and model preferences sent by the server.
+    ///
+    /// # Returns
+    /// The completion to send back, or an error to report to the server.
+    async fn on_sampling_request(
+        &self,
+        service_name: &str,
+        params: CreateMessageRequestParam,
+    ) -> Result<CreateMessageResult>;
 }
 
 /// A convenient super-trait that combines `ServiceEventHandler` and `UserInputProvider`.
@@ -38,3 +225,25 @@ pub trait UserInputProvider: Send + Sync {
 /// It simplifies the setup process by requiring only one logical "handler" object.
 #[async_trait]
 pub trait ZeroHandler: ServiceEventHandler + UserInputProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_input_provider_prefers_service_values() {
+        let provider =
+            StaticInputProvider::new(HashMap::from([("token".to_string(), "shared".to_string())]))
+                .with_service_value("a._mcp._tcp.local", "token", "a-only");
+
+        let token = |service: &'static str| provider.request_input(service, "token");
+        assert_eq!(token("a._mcp._tcp.local.").await.unwrap(), "a-only");
+        assert_eq!(token("b._mcp._tcp.local.").await.unwrap(), "shared");
+        assert!(
+            provider
+                .request_input("a._mcp._tcp.local.", "missing")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! A gRPC control plane exposing `ZeroClient` operations, generated from
+//! `proto/zeromcp.proto`.
+
+// tonic handlers return `Status` by value, whatever its size.
+#![allow(clippy::result_large_err)]
+
+use crate::{client::ZeroClient, error::ZeroError};
+use anyhow::{Result, anyhow, bail};
+use rmcp::model::{CallToolRequestParam, JsonObject};
+use std::{net::SocketAddr, time::Duration};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tonic::{
+    Request, Response, Status,
+    transport::{Server, server::TcpIncoming},
+};
+use tracing::{info, warn};
+
+// Only the tests use the generated client.
+#[allow(clippy::all)]
+#[cfg_attr(not(test), allow(dead_code))]
+mod proto {
+    tonic::include_proto!("zeromcp.v1");
+}
+
+use proto::{
+    CallToolRequest, CallToolResponse, GetStatusRequest, GetStatusResponse, ListServicesRequest,
+    ListServicesResponse, ListToolsRequest, ListToolsResponse, ServiceHealth, StopServiceRequest,
+    StopServiceResponse,
+    zero_mcp_server::{ZeroMcp, ZeroMcpServer},
+};
+
+/// Binds `addr` and serves the control plane in the background until the
+/// returned task is aborted.
+///
+/// With a `token`, requests must carry it as a bearer token. Without one the
+/// server is unauthenticated, so `addr` must be a loopback address.
+pub(crate) async fn serve(
+    addr: SocketAddr,
+    client: ZeroClient,
+    token: Option<String>,
+) -> Result<JoinHandle<()>> {
+    if token.is_none() && !addr.ip().is_loopback() {
+        bail!(
+            "Refusing to serve gRPC on {} without authentication; bind a loopback address or set a token with `with_grpc_token`",
+            addr
+        );
+    }
+    let listener = TcpListener::bind(addr).await?;
+    let local = listener.local_addr()?;
+    match &token {
+        Some(_) if !local.ip().is_loopback() => {
+            warn!(addr = %local, "gRPC server listening without TLS; its bearer token is sent in plain text")
+        }
+        Some(_) => info!(addr = %local, "gRPC server listening"),
+        None => info!(addr = %local, "gRPC server listening on loopback without authentication"),
+    }
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!(e))?;
+    let expected = token.map(|token| format!("Bearer {}", token));
+    let service = ZeroMcpServer::with_interceptor(ControlPlane { client }, move |request| {
+        authorize(request, expected.as_deref())
+    });
+
+    Ok(tokio::spawn(async move {
+        let result = Server::builder()
+            .add_service(service)
+            .serve_with_incoming(incoming)
+            .await;
+        if let Err(e) = result {
+            warn!(error = %e, "gRPC server stopped");
+        }
+    }))
+}
+
+struct ControlPlane {
+    client: ZeroClient,
+}
+
+/// Lets a request through if it carries the `expected` authorization, or if
+/// none is expected.
+fn authorize(request: Request<()>, expected: Option<&str>) -> Result<Request<()>, Status> {
+    let Some(expected) = expected else {
+        return Ok(request);
+    };
+    let given = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // Compares every byte, so the time taken doesn't reveal a matching prefix.
+    let matches = given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if matches {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("missing or invalid bearer token"))
+    }
+}
+
+/// Maps a client error onto the closest gRPC status.
+fn to_status(error: anyhow::Error) -> Status {
+    let message = format!("{:#}", error);
+    match error.downcast_ref::<ZeroError>() {
+        Some(ZeroError::ServiceNotFound { .. }) => Status::not_found(message),
+        Some(ZeroError::Overloaded { .. } | ZeroError::QueueFull { .. }) => {
+            Status::resource_exhausted(message)
+        }
+        Some(ZeroError::ToolCallTimeout { .. }) => Status::deadline_exceeded(message),
+        Some(ZeroError::Draining { .. }) => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Status> {
+    serde_json::to_string(value).map_err(|e| Status::internal(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl ZeroMcp for ControlPlane {
+    async fn list_services(
+        &self,
+        _request: Request<ListServicesRequest>,
+    ) -> Result<Response<ListServicesResponse>, Status> {
+        let services = self
+            .client
+            .active_service_infos()
+            .await
+            .map_err(to_status)?
+            .into_iter()
+            .map(|s| proto::Service {
+                fullname: s.fullname,
+                hostname: s.hostname,
+                port: s.port.into(),
+                addresses: s.addresses,
+            })
+            .collect();
+        Ok(Response::new(ListServicesResponse { services }))
+    }
+
+    async fn list_tools(
+        &self,
+        request: Request<ListToolsRequest>,
+    ) -> Result<Response<ListToolsResponse>, Status> {
+        let tools = self
+            .client
+            .list_all_tools(request.into_inner().service_name)
+            .await
+            .map_err(to_status)?
+            .into_iter()
+            .map(|tool| {
+                Ok(proto::Tool {
+                    name: tool.name.into_owned(),
+                    description: tool.description.map(|d| d.into_owned()),
+                    input_schema_json: to_json(&tool.input_schema)?,
+                })
+            })
+            .collect::<Result<_, Status>>()?;
+        Ok(Response::new(ListToolsResponse { tools }))
+    }
+
+    async fn call_tool(
+        &self,
+        request: Request<CallToolRequest>,
+    ) -> Result<Response<CallToolResponse>, Status> {
+        let request = request.into_inner();
+        let arguments = if request.arguments_json.trim().is_empty() {
+            None
+        } else {
+            let arguments: JsonObject = serde_json::from_str(&request.arguments_json)
+                .map_err(|e| Status::invalid_argument(format!("arguments_json: {}", e)))?;
+            Some(arguments)
+        };
+        let params = CallToolRequestParam {
+            name: request.tool_name.into(),
+            arguments,
+        };
+        let result = match request.timeout_ms {
+            Some(ms) => {
+                self.client
+                    .call_tool_with_timeout(request.service_name, params, Duration::from_millis(ms))
+                    .await
+            }
+            None => self.client.call_tool(request.service_name, params).await,
+        }
+        .map_err(to_status)?;
+        Ok(Response::new(CallToolResponse {
+            result_json: to_json(&result)?,
+            is_error: result.is_error.unwrap_or(false),
+        }))
+    }
+
+    async fn stop_service(
+        &self,
+        request: Request<StopServiceRequest>,
+    ) -> Result<Response<StopServiceResponse>, Status> {
+        let reason = self
+            .client
+            .stop_service(request.into_inner().service_name)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(StopServiceResponse {
+            reason: format!("{:?}", reason),
+        }))
+    }
+
+    async fn get_status(
+        &self,
+        _request: Request<GetStatusRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        let services = self
+            .client
+            .health_summary()
+            .await
+            .map_err(to_status)?
+            .into_iter()
+            .map(|(name, status)| {
+                let health = ServiceHealth {
+                    state: serde_json::to_value(status.state)
+                        .ok()
+                        .and_then(|v| v.as_str().map(str::to_string))
+                        .unwrap_or_default(),
+                    consecutive_failures: status.consecutive_failures,
+                    last_error: status.last_error,
+                };
+                (name, health)
+            })
+            .collect();
+        Ok(Response::new(GetStatusResponse { services }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ZeroConfig,
+        manager::{ServiceActor, ServiceActorArgs},
+    };
+    use proto::zero_mcp_client::ZeroMcpClient;
+    use ractor::Actor;
+    use tonic::Code;
+
+    async fn idle_client() -> ZeroClient {
+        let config: ZeroConfig = toml::from_str("").unwrap();
+        let (active_set, _) = tokio::sync::watch::channel(Vec::new());
+        let (discovery, _) = tokio::sync::mpsc::unbounded_channel();
+        let args = ServiceActorArgs {
+            config: config.clone(),
+            active_set,
+            discovery,
+            handler: Default::default(),
+            metrics: Default::default(),
+            liveness: Default::default(),
+        };
+        let (actor, _handle) = Actor::spawn(None, ServiceActor, args).await.unwrap();
+        ZeroClient::new(actor, &config)
+    }
+
+    async fn start_server(token: Option<&str>) -> (SocketAddr, JoinHandle<()>) {
+        let client = idle_client().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let task = serve(addr, client, token.map(str::to_string))
+            .await
+            .unwrap();
+        (addr, task)
+    }
+
+    #[tokio::test]
+    async fn test_control_plane_round_trip() {
+        let (addr, task) = start_server(None).await;
+        let mut client = ZeroMcpClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+
+        let services = client
+            .list_services(ListServicesRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .services;
+        assert!(services.is_empty());
+        let missing = client
+            .stop_service(StopServiceRequest {
+                service_name: "missing".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_control_plane_requires_its_token() {
+        let (addr, task) = start_server(Some("secret")).await;
+        let mut client = ZeroMcpClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+
+        let denied = client.get_status(GetStatusRequest {}).await.unwrap_err();
+        assert_eq!(denied.code(), Code::Unauthenticated);
+        let mut request = Request::new(GetStatusRequest {});
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(client.get_status(request).await.is_ok());
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_server_only_binds_loopback() {
+        let client = idle_client().await;
+        let error = serve("0.0.0.0:0".parse().unwrap(), client, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("without authentication"));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::config::CircuitBreaker;
+use anyhow::Result;
+use rmcp::service::ServiceError;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+/// Consecutive failures after which a service is reported as `Unhealthy`.
+pub const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+
+/// The coarse health of a service.
+///
+/// Transitions are driven by consecutive failures:
+/// - any success resets the service to `Healthy`;
+/// - one or two consecutive failures make it `Degraded`;
+/// - [`UNHEALTHY_AFTER_FAILURES`] or more make it `Unhealthy`.
+///
+/// Only failures that suggest the service itself is in trouble count
+/// (transport errors, timeouts). Error responses the server sends back, such as
+/// an unknown tool or invalid arguments, show it is alive and count as successes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+/// A snapshot of a service's health, as returned by `ZeroClient::service_health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthStatus {
+    pub state: HealthState,
+    /// When the service's health was last observed, if ever.
+    pub last_check: Option<SystemTime>,
+    /// The most recent failure, kept after the service recovers.
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+}
+
+/// How long each probe of `ZeroMcp::self_test` may take.
+pub const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of mDNS discovery, as seen by `ZeroMcp::self_test`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryStatus {
+    /// Whether the mDNS daemon answered a status request, saying it's running.
+    pub daemon_running: bool,
+    /// Service types whose browse is running.
+    pub browsing: Vec<String>,
+    /// Service types whose browse failed and is waiting to be retried.
+    pub retrying: Vec<String>,
+}
+
+/// The result of `ZeroMcp::self_test`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    /// The discovery loop's status, or `None` if it has stopped or didn't answer.
+    pub discovery: Option<DiscoveryStatus>,
+    /// How long the service actor took to answer.
+    pub actor_round_trip: Duration,
+    pub active_services: usize,
+    /// Active services that are `Degraded` or `Unhealthy`.
+    pub impaired_services: BTreeMap<String, HealthStatus>,
+}
+
+impl SelfTestReport {
+    /// Returns whether the mDNS daemon is running, discovery is running for
+    /// every service type and no service is `Unhealthy`. Degraded services
+    /// don't fail the check.
+    pub fn is_ok(&self) -> bool {
+        self.discovery
+            .as_ref()
+            .is_some_and(|d| d.daemon_running && d.retrying.is_empty())
+            && self
+                .impaired_services
+                .values()
+                .all(|status| status.state != HealthState::Unhealthy)
+    }
+}
+
+/// Tracks the health of a single service from the outcomes of its requests.
+#[derive(Debug, Default)]
+pub(crate) struct HealthTracker {
+    consecutive_failures: u32,
+    last_check: Option<SystemTime>,
+    last_error: Option<String>,
+}
+
+impl HealthTracker {
+    pub(crate) fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.last_check = Some(SystemTime::now());
+    }
+
+    pub(crate) fn record_failure(&mut self, error: String) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.last_check = Some(SystemTime::now());
+        self.last_error = Some(error);
+    }
+
+    /// Records the outcome of a request made to the service.
+    pub(crate) fn observe<T>(&mut self, result: &Result<T>) {
+        self.record(Self::outcome(result));
+    }
+
+    /// Records an outcome computed by [`HealthTracker::outcome`], for requests
+    /// that finished away from the tracker.
+    pub(crate) fn record(&mut self, outcome: std::result::Result<(), String>) {
+        match outcome {
+            Ok(()) => self.record_success(),
+            Err(e) => self.record_failure(e),
+        }
+    }
+
+    /// Reduces a request's result to what matters for health. Errors reported
+    /// by the server itself show it is responsive, so they count as successes.
+    pub(crate) fn outcome<T>(result: &Result<T>) -> std::result::Result<(), String> {
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => Self::failure(e),
+        }
+    }
+
+    /// Reduces a request's error to what matters for health, as `outcome` does.
+    pub(crate) fn failure(error: &anyhow::Error) -> std::result::Result<(), String> {
+        if matches!(error.downcast_ref(), Some(ServiceError::McpError(_))) {
+            Ok(())
+        } else {
+            Err(format!("{:#}", error))
+        }
+    }
+
+    /// Returns whether `breaker` should reject calls: its failure threshold
+    /// has been reached and the last failure is within its reset timeout.
+    pub(crate) fn circuit_open(&self, breaker: &CircuitBreaker) -> bool {
+        self.consecutive_failures >= breaker.failure_threshold
+            && self
+                .last_check
+                .and_then(|at| at.elapsed().ok())
+                .is_some_and(|elapsed| elapsed < breaker.reset_timeout())
+    }
+
+    pub(crate) fn status(&self) -> HealthStatus {
+        let state = match self.consecutive_failures {
+            0 => HealthState::Healthy,
+            n if n < UNHEALTHY_AFTER_FAILURES => HealthState::Degraded,
+            _ => HealthState::Unhealthy,
+        };
+        HealthStatus {
+            state,
+            last_check: self.last_check,
+            last_error: self.last_error.clone(),
+            consecutive_failures: self.consecutive_failures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_health_transitions() {
+        let mut tracker = HealthTracker::default();
+        assert_eq!(tracker.status().state, HealthState::Healthy);
+        assert!(tracker.status().last_check.is_none());
+
+        let failure: Result<()> = Err(anyhow!("connection reset"));
+        tracker.observe(&failure);
+        assert_eq!(tracker.status().state, HealthState::Degraded);
+        tracker.observe(&failure);
+        assert_eq!(tracker.status().state, HealthState::Degraded);
+        tracker.observe(&failure);
+        assert_eq!(tracker.status().state, HealthState::Unhealthy);
+
+        let server_error: Result<()> =
+            Err(ServiceError::McpError(rmcp::Error::invalid_params("bad arguments", None)).into());
+        tracker.observe(&server_error);
+        let status = tracker.status();
+        assert_eq!(status.state, HealthState::Healthy);
+        assert_eq!(status.last_error.as_deref(), Some("connection reset"));
+    }
+
+    #[test]
+    fn test_circuit_opens_after_threshold_until_reset() {
+        let breaker = |reset_timeout_ms| CircuitBreaker {
+            failure_threshold: 2,
+            reset_timeout_ms,
+        };
+        let mut tracker = HealthTracker::default();
+        tracker.record_failure("timeout".to_string());
+        assert!(!tracker.circuit_open(&breaker(60_000)));
+        tracker.record_failure("timeout".to_string());
+        assert!(tracker.circuit_open(&breaker(60_000)));
+        assert!(!tracker.circuit_open(&breaker(0)));
+        tracker.record_success();
+        assert!(!tracker.circuit_open(&breaker(60_000)));
+    }
+
+    #[test]
+    fn test_self_test_needs_a_running_daemon() {
+        let report = |daemon_running| SelfTestReport {
+            discovery: Some(DiscoveryStatus {
+                daemon_running,
+                browsing: vec!["_mcp._tcp.local.".to_string()],
+                retrying: Vec::new(),
+            }),
+            actor_round_trip: Duration::ZERO,
+            active_services: 0,
+            impaired_services: BTreeMap::new(),
+        };
+        assert!(report(true).is_ok());
+        assert!(!report(false).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Controls the size and resolution of the in-memory discovery history.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DiscoveryHistoryConfig {
+    /// Width of each bucket, in seconds.
+    #[serde(default = "default_resolution_secs")]
+    pub resolution_secs: u64,
+    /// Maximum number of buckets retained.
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+}
+
+fn default_resolution_secs() -> u64 {
+    60
+}
+
+fn default_capacity() -> usize {
+    60
+}
+
+impl Default for DiscoveryHistoryConfig {
+    fn default() -> Self {
+        Self {
+            resolution_secs: default_resolution_secs(),
+            capacity: default_capacity(),
+        }
+    }
+}
+
+/// Lifecycle event counts for a single time bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimeBucket {
+    /// Start of the bucket.
+    pub start: SystemTime,
+    /// Number of services added during the bucket.
+    pub added: u64,
+    /// Number of services removed during the bucket.
+    pub removed: u64,
+}
+
+/// The kind of lifecycle event recorded in the history.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum LifecycleEvent {
+    Added,
+    Removed,
+}
+
+/// A ring buffer of lifecycle event counts bucketed by wall-clock time.
+#[derive(Debug)]
+pub(crate) struct DiscoveryHistory {
+    resolution: u64,
+    capacity: usize,
+    // (bucket index since the epoch, added, removed), oldest first.
+    buckets: VecDeque<(u64, u64, u64)>,
+}
+
+impl DiscoveryHistory {
+    pub(crate) fn new(config: &DiscoveryHistoryConfig) -> Self {
+        Self {
+            resolution: config.resolution_secs.max(1),
+            capacity: config.capacity.max(1),
+            buckets: VecDeque::new(),
+        }
+    }
+
+    fn bucket_index(&self, at: SystemTime) -> u64 {
+        at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / self.resolution
+    }
+
+    fn evict(&mut self, current: u64) {
+        let oldest = current.saturating_sub(self.capacity as u64 - 1);
+        while matches!(self.buckets.front(), Some((idx, ..)) if *idx < oldest) {
+            self.buckets.pop_front();
+        }
+    }
+
+    pub(crate) fn record(&mut self, event: LifecycleEvent) {
+        self.record_at(event, SystemTime::now());
+    }
+
+    fn record_at(&mut self, event: LifecycleEvent, at: SystemTime) {
+        let idx = self.bucket_index(at);
+        self.evict(idx);
+        if !matches!(self.buckets.back(), Some((last, ..)) if *last == idx) {
+            self.buckets.push_back((idx, 0, 0));
+        }
+        if let Some((_, added, removed)) = self.buckets.back_mut() {
+            match event {
+                LifecycleEvent::Added => *added += 1,
+                LifecycleEvent::Removed => *removed += 1,
+            }
+        }
+    }
+
+    /// Returns a contiguous series of buckets covering `window`, oldest first.
+    /// Buckets with no activity are included with zero counts.
+    pub(crate) fn query(&mut self, window: Duration) -> Vec<TimeBucket> {
+        self.query_at(window, SystemTime::now())
+    }
+
+    fn query_at(&mut self, window: Duration, now: SystemTime) -> Vec<TimeBucket> {
+        let current = self.bucket_index(now);
+        self.evict(current);
+
+        let count = window
+            .as_secs()
+            .div_ceil(self.resolution)
+            .clamp(1, self.capacity as u64);
+        let first = current.saturating_sub(count - 1);
+
+        (first..=current)
+            .map(|idx| {
+                let (added, removed) = self
+                    .buckets
+                    .iter()
+                    .find(|(i, ..)| *i == idx)
+                    .map(|(_, a, r)| (*a, *r))
+                    .unwrap_or((0, 0));
+                TimeBucket {
+                    start: UNIX_EPOCH + Duration::from_secs(idx * self.resolution),
+                    added,
+                    removed,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_history_buckets_and_fills_gaps() {
+        let mut history = DiscoveryHistory::new(&DiscoveryHistoryConfig {
+            resolution_secs: 60,
+            capacity: 10,
+        });
+        history.record_at(LifecycleEvent::Added, at(600));
+        history.record_at(LifecycleEvent::Added, at(630));
+        history.record_at(LifecycleEvent::Removed, at(725));
+
+        let buckets = history.query_at(Duration::from_secs(180), at(730));
+        assert_eq!(buckets.len(), 3);
+        assert_eq!((buckets[0].added, buckets[0].removed), (2, 0));
+        assert_eq!((buckets[1].added, buckets[1].removed), (0, 0));
+        assert_eq!((buckets[2].added, buckets[2].removed), (0, 1));
+        assert_eq!(buckets[2].start, at(720));
+    }
+
+    #[test]
+    fn test_history_evicts_beyond_capacity() {
+        let mut history = DiscoveryHistory::new(&DiscoveryHistoryConfig {
+            resolution_secs: 1,
+            capacity: 3,
+        });
+        history.record_at(LifecycleEvent::Added, at(0));
+        history.record_at(LifecycleEvent::Added, at(5));
+
+        let buckets = history.query_at(Duration::from_secs(100), at(5));
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.iter().map(|b| b.added).sum::<u64>(), 1);
+    }
+}
@@ -89,17 +89,51 @@
 //! }
 //! ```
 
+pub mod builder;
 pub mod client;
+mod client_handler;
 pub mod config;
+pub mod error;
 pub mod events;
+#[cfg(feature = "grpc")]
+mod grpc;
+pub mod health;
+pub mod history;
+mod liveness;
 pub mod manager;
 pub mod mdns;
+pub mod memory;
+pub mod metrics;
 pub mod models;
+mod schedule;
+pub mod state;
+#[cfg(feature = "status-server")]
+mod status;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod timing;
+pub mod tool_result;
+mod transport;
 mod utils;
 
 // Re-export public-facing components.
+pub use builder::ZeroMcpBuilder;
 pub use client::ZeroClient;
 pub use config::ZeroConfig;
-pub use events::{ServiceEventHandler, UserInputProvider, ZeroHandler};
-pub use manager::start;
-pub use models::DiscoveredService;
+pub use error::ZeroError;
+pub use events::{
+    SamplingHandler, ServiceEventHandler, StaticInputProvider, UserInputProvider, ZeroHandler,
+};
+pub use health::{DiscoveryStatus, HealthState, HealthStatus, SelfTestReport};
+pub use history::TimeBucket;
+pub use manager::{StopResults, start, start_from_state, start_with, start_with_mdns};
+pub use mdns::MdnsBrowser;
+pub use memory::MemoryStats;
+pub use metrics::{InMemoryMetrics, MetricsSink, MetricsSnapshot, NoopMetrics};
+pub use models::{
+    Capability, DiscoveredService, RenderedLaunch, ToolsDiff, TrafficDirection, TrafficEvent,
+};
+pub use state::{ManagerState, SavedService};
+pub use timing::{StartupHistogram, StartupTimings};
+pub use tokio_util::sync::CancellationToken;
+pub use tool_result::{JsonDecoder, ToolResultDecoder};
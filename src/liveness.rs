@@ -0,0 +1,118 @@
+//! Periodic pings that catch dead services before mDNS reports them gone,
+//! and stops of services left idle past their `idle_timeout_ms`.
+
+use crate::{
+    ZeroHandler,
+    client::ZeroClient,
+    config::{HandlerPanicPolicy, ServiceOptions, ZeroConfig},
+    manager::{handle_handler_panic, report_service_error},
+    utils::{bounded_fan_out, catch_panic},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{debug, error, info, warn};
+
+/// How many services are pinged at once on each round.
+const HEALTH_CHECK_CONCURRENCY: usize = 16;
+
+/// The longest wait between two looks at which services are due a health
+/// check or idle. Shorter configured intervals shorten it.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spawns the loop that pings each service every `health_check_interval_ms`
+/// and stops those idle for their `idle_timeout_ms`.
+///
+/// When the config sets neither, the loop stays parked until `wake` is
+/// notified, which the actor does once it tracks a service that brings its
+/// own settings, e.g. a synthetic or restored launch.
+pub(crate) fn spawn(
+    config: &ZeroConfig,
+    client: &ZeroClient,
+    handler: &Arc<dyn ZeroHandler>,
+    wake: Arc<Notify>,
+) -> JoinHandle<()> {
+    let interval = shortest_interval(config);
+    let sweep = interval.map_or(SWEEP_INTERVAL, |ms| {
+        SWEEP_INTERVAL.min(Duration::from_millis(ms.max(1)))
+    });
+    let config = config.clone();
+    let client = client.clone();
+    let handler = handler.clone();
+    tokio::spawn(async move {
+        if interval.is_none() {
+            wake.notified().await;
+            debug!("A service enabled health checks or idle stops; starting the loop");
+        }
+        run(sweep, config, client, handler).await
+    })
+}
+
+/// Returns the shortest health-check interval or idle timeout in `config`.
+fn shortest_interval(config: &ZeroConfig) -> Option<u64> {
+    let options = std::iter::once(&config.service_defaults)
+        .chain(config.service_mappings.iter().map(|m| m.mcp.options()))
+        .chain(config.static_services.iter().map(|s| s.mcp.options()));
+    options
+        .flat_map(|o: &ServiceOptions| [o.health_check_interval_ms, o.idle_timeout_ms])
+        .chain([config.health_check_interval_ms])
+        .flatten()
+        .min()
+}
+
+async fn run(
+    sweep: Duration,
+    config: ZeroConfig,
+    client: ZeroClient,
+    handler: Arc<dyn ZeroHandler>,
+) {
+    let timeout = Duration::from_millis(config.health_check_timeout_ms);
+    loop {
+        tokio::time::sleep(config.jittered(sweep)).await;
+        match client.idle_services().await {
+            Ok(idle) => {
+                for name in idle {
+                    remove(&client, &handler, &name, "was idle").await;
+                }
+            }
+            Err(e) => error!(error = %e, "Failed to list idle services"),
+        }
+        let services = match client.due_health_checks().await {
+            Ok(services) => services,
+            Err(e) => {
+                error!(error = %e, "Failed to list services for health checks");
+                continue;
+            }
+        };
+        bounded_fan_out(services, HEALTH_CHECK_CONCURRENCY, |name| {
+            let client = client.clone();
+            let handler = handler.clone();
+            async move {
+                match client.ping_within(&name, timeout).await {
+                    Ok(rtt) => debug!(service = %name, rtt = ?rtt, "Health check passed"),
+                    Err(e) => {
+                        warn!(service = %name, error = %e, "Health check failed; removing service");
+                        let e = e.context("health check failed");
+                        report_service_error(&client, &handler, &name, &e).await;
+                        remove(&client, &handler, &name, "failed its health check").await;
+                    }
+                }
+            }
+        })
+        .await;
+    }
+}
+
+async fn remove(client: &ZeroClient, handler: &Arc<dyn ZeroHandler>, name: &str, why: &str) {
+    match client.stop_service(name).await {
+        Ok(reason) => {
+            info!(service = %name, reason = ?reason, "Removed service that {}", why);
+            if let Err(panic) = catch_panic(handler.on_service_stopped(name, reason)).await {
+                // The service is already gone, so only the report applies.
+                handle_handler_panic(client, handler, HandlerPanicPolicy::Keep, name, &panic).await;
+            }
+        }
+        Err(e) => {
+            debug!(service = %name, error = %e, "Service already gone after it {}", why)
+        }
+    }
+}
@@ -1,38 +1,101 @@
 use crate::{
-    ZeroHandler,
-    client::ZeroClient,
-    config::{McpConfig, ZeroConfig},
+    SamplingHandler, ZeroHandler,
+    client::{IDEMPOTENCY_KEY_META, InputCache, ZeroClient, context_inputs, seed_context},
+    client_handler::{ProgressRouter, ZeroClientHandler},
+    config::{
+        EmptyServerPolicy, HandlerPanicPolicy, InitOptions, McpConfig, ProxyConfig, RestartPolicy,
+        ServiceMcpMapping, ZeroConfig,
+    },
+    error::{SharedError, ZeroError},
+    health::{
+        DiscoveryStatus, HealthState, HealthStatus, HealthTracker, SELF_TEST_TIMEOUT,
+        SelfTestReport,
+    },
+    history::{DiscoveryHistory, LifecycleEvent, TimeBucket},
     mdns::MdnsBrowser,
-    models::DiscoveredService,
-    utils::hashmap_to_header_map,
+    memory::{Cached, FragmentBudget, FragmentBuffer, MemoryStats, lru_evictions},
+    metrics::{Metrics, MetricsSink},
+    models::{
+        Capability, DiscoveredService, RenderedLaunch, StopOutcome, ToolFingerprints, ToolsDiff,
+        TrafficEvent,
+    },
+    state::{ManagerState, SavedService},
+    timing::{StartupHistogram, StartupTimings},
+    transport::{
+        ChildHandle, HAPPY_EYEBALLS_HEAD_START, SessionTrackingClient, TRAFFIC_TAP_CAPACITY,
+        TappedTransport, build_proxy, connect_first, happy_eyeballs_order, spawn_child, url_host,
+    },
+    utils::{
+        catch_panic, dedup_tools, glob_match, normalize_fullname, to_header_map, validate_fullname,
+    },
+};
+use anyhow::{Context, Result, anyhow, bail};
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, FuturesUnordered, StreamExt},
 };
-use anyhow::{Context, Result, anyhow};
-use futures::stream::StreamExt;
 use handlebars::{Handlebars, RenderErrorReason};
-use mdns_sd::{ServiceDaemon, ServiceEvent};
+use indexmap::IndexMap;
+use mdns_sd::{DaemonStatus, ServiceDaemon, ServiceEvent};
 use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
 use rmcp::{
     RoleClient, ServiceExt,
-    model::{GetPromptRequestParam, GetPromptResult, Prompt, Resource, ResourceTemplate, Tool},
-    service::{DynService, QuitReason, RunningService},
+    model::{
+        CallToolRequest, CallToolRequestParam, CallToolResult, CancelledNotification,
+        CancelledNotificationMethod, CancelledNotificationParam, ClientRequest, CompleteRequest,
+        CompleteRequestParam, CompleteResult, GetPromptRequest, GetPromptRequestParam,
+        GetPromptResult, ListPromptsRequest, ListResourceTemplatesRequest, ListResourcesRequest,
+        ListToolsRequest, Meta, PaginatedRequestParam, PingRequest, Prompt, PromptArgument,
+        ReadResourceRequest, ReadResourceRequestParam, ReadResourceResult, Resource,
+        ResourceTemplate, Root, ServerResult, SubscribeRequest, SubscribeRequestParam, Tool,
+        UnsubscribeRequest, UnsubscribeRequestParam,
+    },
+    service::{DynService, Peer, PeerRequestOptions, QuitReason, RunningService, ServiceError},
     transport::{
-        SseClientTransport, child_process::TokioChildProcess, sse_client::SseClientConfig,
+        IntoTransport, SseClientTransport, StreamableHttpClientTransport,
+        async_rw::TransportAdapterAsyncRW, sse_client::SseClientConfig,
+        streamable_http_client::StreamableHttpClientTransportConfig,
     },
 };
-use serde_json::json;
-use std::{collections::HashMap, fmt, process::Stdio, sync::Arc};
-use tokio::task::JoinHandle;
+use serde_json::{Map, Value, json};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::Future,
+    sync::{Arc, OnceLock, RwLock, Weak},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::ChildStderr,
+    sync::{Notify, OwnedSemaphorePermit, Semaphore, broadcast, mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::{Span, debug, error, info, instrument, warn};
 
 pub enum ServiceMessage {
     AddService {
         name: String,
-        service: McpClient,
+        service: Box<ActiveService>,
     },
     CancelService {
         name: String,
         reply: RpcReplyPort<Result<QuitReason>>,
     },
+    /// Stops every active service, replying with each one's outcome.
+    StopAllServices {
+        reply: RpcReplyPort<Result<StopResults>>,
+    },
+    /// Marks the manager as shutting down: launches are no longer claimed,
+    /// and services added from then on are stopped instead of tracked.
+    BeginShutdown { reply: RpcReplyPort<Result<()>> },
+    CancelServiceWithRetry {
+        name: String,
+        attempts: usize,
+        backoff: Duration,
+        reply: RpcReplyPort<Result<StopOutcome>>,
+    },
     ListAllTools {
         service_name: String,
         reply: RpcReplyPort<Result<Vec<Tool>>>,
@@ -41,6 +104,11 @@ pub enum ServiceMessage {
         service_name: String,
         reply: RpcReplyPort<Result<Vec<Prompt>>>,
     },
+    PromptArguments {
+        service_name: String,
+        prompt_name: String,
+        reply: RpcReplyPort<Result<Vec<PromptArgument>>>,
+    },
     ListAllResources {
         service_name: String,
         reply: RpcReplyPort<Result<Vec<Resource>>>,
@@ -49,11 +117,234 @@ pub enum ServiceMessage {
         service_name: String,
         reply: RpcReplyPort<Result<Vec<ResourceTemplate>>>,
     },
+    ReadResource {
+        service_name: String,
+        uri: String,
+        reply: RpcReplyPort<Result<ReadResourceResult>>,
+    },
+    SubscribeResource {
+        service_name: String,
+        uri: String,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    UnsubscribeResource {
+        service_name: String,
+        uri: String,
+        reply: RpcReplyPort<Result<()>>,
+    },
     GetPrompt {
         service_name: String,
         prompt_request: GetPromptRequestParam,
+        meta: Option<Meta>,
         reply: RpcReplyPort<Result<GetPromptResult>>,
     },
+    Complete {
+        service_name: String,
+        params: CompleteRequestParam,
+        reply: RpcReplyPort<Result<CompleteResult>>,
+    },
+    CallTool {
+        service_name: String,
+        params: CallToolRequestParam,
+        timeout: Option<Duration>,
+        meta: Option<Meta>,
+        idempotency_key: Option<String>,
+        cancel: Option<CancellationToken>,
+        reply: RpcReplyPort<Result<CallToolResult>>,
+    },
+    /// A queued tool call finished; `outcome` is recorded in the service's health.
+    CallFinished {
+        service_name: String,
+        outcome: std::result::Result<(), String>,
+    },
+    /// Sent by the actor to itself when a tool listing made off the actor completes.
+    ToolsListed {
+        service_name: String,
+        result: Result<Vec<Tool>>,
+        reply: ToolsReply,
+    },
+    /// Sent by the actor to itself when a prompt listing made off the actor completes.
+    PromptsListed {
+        service_name: String,
+        result: Result<Vec<Prompt>>,
+        reply: PromptsReply,
+    },
+    DiscoveryHistory {
+        window: Duration,
+        reply: RpcReplyPort<Result<Vec<TimeBucket>>>,
+    },
+    ServiceReplicas {
+        identity: String,
+        reply: RpcReplyPort<Result<Vec<DiscoveredService>>>,
+    },
+    ActiveServiceInfos {
+        reply: RpcReplyPort<Result<Vec<DiscoveredService>>>,
+    },
+    ListActiveServices {
+        reply: RpcReplyPort<Result<Vec<String>>>,
+    },
+    GetServiceInfo {
+        service_name: String,
+        reply: RpcReplyPort<Result<DiscoveredService>>,
+    },
+    /// Renders each template against an active service, seeded with
+    /// `inputs` and prompting for missing variables through the handler.
+    /// Replies with the rendered values and every input they used.
+    RenderTemplates {
+        service_name: String,
+        templates: HashMap<String, String>,
+        inputs: Option<Map<String, Value>>,
+        reply: RpcReplyPort<Result<RenderedTemplates>>,
+    },
+    ExportState {
+        reply: RpcReplyPort<Result<ManagerState>>,
+    },
+    HealthSummary {
+        reply: RpcReplyPort<Result<BTreeMap<String, HealthStatus>>>,
+    },
+    SessionId {
+        service_name: String,
+        reply: RpcReplyPort<Result<Option<String>>>,
+    },
+    ServiceHealth {
+        service_name: String,
+        reply: RpcReplyPort<Result<HealthStatus>>,
+    },
+    ToolsDiff {
+        service_name: String,
+        reply: RpcReplyPort<Result<ToolsDiff>>,
+    },
+    RefreshDiscovery {
+        service_type: Option<String>,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    StreamingPeer {
+        service_name: String,
+        reply: RpcReplyPort<Result<StreamingCall>>,
+    },
+    SetLaunchOverride {
+        fullname: String,
+        config: Option<Box<McpConfig>>,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    LaunchOverride {
+        fullname: String,
+        reply: RpcReplyPort<Result<Option<McpConfig>>>,
+    },
+    SetRoots {
+        service_name: String,
+        roots: Vec<Root>,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    MemoryStats {
+        reply: RpcReplyPort<Result<MemoryStats>>,
+    },
+    StartupTimings {
+        service_name: String,
+        reply: RpcReplyPort<Result<StartupTimings>>,
+    },
+    StartupHistogram {
+        reply: RpcReplyPort<Result<StartupHistogram>>,
+    },
+    ServicesWithCapability {
+        capability: Capability,
+        reply: RpcReplyPort<Result<Vec<String>>>,
+    },
+    StoppedAt {
+        service_name: String,
+        reply: RpcReplyPort<Result<Option<Instant>>>,
+    },
+    /// Pings a service, replying with the round-trip time. Without a
+    /// `timeout`, the service's `request_timeout_ms` applies.
+    Ping {
+        service_name: String,
+        timeout: Option<Duration>,
+        reply: RpcReplyPort<Result<Duration>>,
+    },
+    /// Removes a service whose transport closed without it being stopped,
+    /// replying whether it was still tracked as the instance with `pid`.
+    RemoveExited {
+        service_name: String,
+        pid: Option<u32>,
+        reply: RpcReplyPort<Result<bool>>,
+    },
+    TapTraffic {
+        service_name: String,
+        reply: RpcReplyPort<Result<broadcast::Receiver<TrafficEvent>>>,
+    },
+    /// Stops the service taking new tool calls and replies once it has no
+    /// calls or requests outstanding, with whether it was active to drain.
+    DrainCalls {
+        service_name: String,
+        reply: RpcReplyPort<Result<bool>>,
+    },
+    /// Lets a drained service take tool calls again.
+    ResumeCalls {
+        service_name: String,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    LaunchSynthetic {
+        service: Box<DiscoveredService>,
+        config: Box<McpConfig>,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    RestartService {
+        service_name: String,
+        reply: RpcReplyPort<Result<()>>,
+    },
+    /// Marks a launch of the service as in progress, replying `None` if it
+    /// is already active or being launched. Otherwise replies with a token
+    /// that's cancelled if the service is stopped before the launch ends.
+    ClaimLaunch {
+        service_name: String,
+        reply: RpcReplyPort<Result<Option<CancellationToken>>>,
+    },
+    /// Ends a launch claimed with `ClaimLaunch`, however it went. A
+    /// resolution recorded meanwhile with `SupersedeLaunch` is relaunched.
+    ReleaseLaunch { service_name: String },
+    /// Records a changed resolution that arrived while the service was being
+    /// launched, to relaunch it once that launch ends. Replies `false` if no
+    /// launch is in flight.
+    SupersedeLaunch {
+        service: Box<DiscoveredService>,
+        config: Box<McpConfig>,
+        reply: RpcReplyPort<Result<bool>>,
+    },
+    /// Replies with the services whose `health_check_interval_ms` has passed
+    /// since they were last picked, marking them as picked now.
+    DueHealthChecks {
+        reply: RpcReplyPort<Result<Vec<String>>>,
+    },
+    /// Replies with the services that have gone their `idle_timeout_ms`
+    /// without a request.
+    IdleServices {
+        reply: RpcReplyPort<Result<Vec<String>>>,
+    },
+    /// Sent when unread stream fragments take memory past the budget, so it's
+    /// enforced without waiting for another message.
+    EnforceMemoryBudget,
+}
+
+impl ServiceMessage {
+    /// Returns the service a tool call, prompt or resource request is for,
+    /// which keeps it from counting as idle.
+    fn requested_service(&self) -> Option<&str> {
+        match self {
+            Self::CallTool { service_name, .. }
+            | Self::StreamingPeer { service_name, .. }
+            | Self::GetPrompt { service_name, .. }
+            | Self::Complete { service_name, .. }
+            | Self::ListAllTools { service_name, .. }
+            | Self::ListAllPrompts { service_name, .. }
+            | Self::PromptArguments { service_name, .. }
+            | Self::ListAllResources { service_name, .. }
+            | Self::ListAllResourceTemplates { service_name, .. }
+            | Self::ReadResource { service_name, .. }
+            | Self::SubscribeResource { service_name, .. }
+            | Self::UnsubscribeResource { service_name, .. } => Some(service_name),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for ServiceMessage {
@@ -73,6 +364,26 @@ impl fmt::Debug for ServiceMessage {
                 .field("name", name)
                 .field("reply", reply)
                 .finish(),
+            Self::StopAllServices { reply } => f
+                .debug_struct("StopAllServices")
+                .field("reply", reply)
+                .finish(),
+            Self::BeginShutdown { reply } => f
+                .debug_struct("BeginShutdown")
+                .field("reply", reply)
+                .finish(),
+            Self::CancelServiceWithRetry {
+                name,
+                attempts,
+                backoff,
+                reply,
+            } => f
+                .debug_struct("CancelServiceWithRetry")
+                .field("name", name)
+                .field("attempts", attempts)
+                .field("backoff", backoff)
+                .field("reply", reply)
+                .finish(),
 
             Self::ListAllTools {
                 service_name,
@@ -90,6 +401,16 @@ impl fmt::Debug for ServiceMessage {
                 .field("service_name", service_name)
                 .field("reply", reply)
                 .finish(),
+            Self::PromptArguments {
+                service_name,
+                prompt_name,
+                reply,
+            } => f
+                .debug_struct("PromptArguments")
+                .field("service_name", service_name)
+                .field("prompt_name", prompt_name)
+                .field("reply", reply)
+                .finish(),
             Self::ListAllResources {
                 service_name,
                 reply,
@@ -106,167 +427,2650 @@ impl fmt::Debug for ServiceMessage {
                 .field("service_name", service_name)
                 .field("reply", reply)
                 .finish(),
+            Self::ReadResource {
+                service_name,
+                uri,
+                reply,
+            } => f
+                .debug_struct("ReadResource")
+                .field("service_name", service_name)
+                .field("uri", uri)
+                .field("reply", reply)
+                .finish(),
+            Self::SubscribeResource {
+                service_name,
+                uri,
+                reply,
+            } => f
+                .debug_struct("SubscribeResource")
+                .field("service_name", service_name)
+                .field("uri", uri)
+                .field("reply", reply)
+                .finish(),
+            Self::UnsubscribeResource {
+                service_name,
+                uri,
+                reply,
+            } => f
+                .debug_struct("UnsubscribeResource")
+                .field("service_name", service_name)
+                .field("uri", uri)
+                .field("reply", reply)
+                .finish(),
             Self::GetPrompt {
                 service_name,
                 prompt_request,
+                meta,
                 reply,
             } => f
                 .debug_struct("GetPrompt")
                 .field("service_name", service_name)
                 .field("prompt_request", prompt_request)
+                .field("meta", meta)
                 .field("reply", reply)
                 .finish(),
-        }
-    }
-}
-
-pub struct ActorState {
-    active_services: HashMap<String, McpClient>,
-}
-
-pub struct ServiceActor;
-pub type McpClient = RunningService<RoleClient, Box<dyn DynService<RoleClient>>>;
-
-#[async_trait::async_trait]
-impl Actor for ServiceActor {
-    type Msg = ServiceMessage;
-    type State = ActorState;
-    type Arguments = ();
-
-    async fn pre_start(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        _args: Self::Arguments,
-    ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(ActorState {
-            active_services: HashMap::new(),
-        })
-    }
-
-    #[instrument(name = "service_actor_handle", skip(self, _myself, state), fields(message_type = std::any::type_name::<ServiceMessage>()))]
-    async fn handle(
-        &self,
-        _myself: ActorRef<Self::Msg>,
-        message: Self::Msg,
-        state: &mut Self::State,
-    ) -> Result<(), ActorProcessingErr> {
-        match message {
-            ServiceMessage::AddService { name, service } => {
-                info!("Tracking new active service: {}", name);
-                state.active_services.insert(name, service);
-            }
-            ServiceMessage::CancelService { name, reply } => {
-                let result = if let Some(service) = state.active_services.remove(&name) {
-                    service.cancel().await.map_err(|e| e.into())
-                } else {
-                    Err(anyhow!("Service '{}' not found for cancellation.", name))
-                };
-                if let Err(e) = &result {
-                    warn!(
-                        "Failed to cleanly cancel service '{}': {}",
-                        name,
-                        e.to_string()
-                    );
-                }
-                let _ = reply.send(result);
-            }
-            ServiceMessage::ListAllTools {
+            Self::Complete {
                 service_name,
+                params,
                 reply,
-            } => {
-                let result = if let Some(service) = state.active_services.get(&service_name) {
-                    service.list_all_tools().await.map_err(|e| e.into())
-                } else {
-                    Err(anyhow!(
-                        "Service '{}' not found to list tools.",
-                        service_name
-                    ))
-                };
-                let _ = reply.send(result);
-            }
-            ServiceMessage::ListAllPrompts {
+            } => f
+                .debug_struct("Complete")
+                .field("service_name", service_name)
+                .field("params", params)
+                .field("reply", reply)
+                .finish(),
+            Self::CallTool {
                 service_name,
+                params,
+                timeout,
+                meta,
+                idempotency_key,
+                cancel,
                 reply,
-            } => {
-                let result = if let Some(service) = state.active_services.get(&service_name) {
-                    service.list_all_prompts().await.map_err(|e| e.into())
-                } else {
-                    Err(anyhow!(
-                        "Service '{}' not found to list prompts.",
-                        service_name
-                    ))
-                };
-                let _ = reply.send(result);
-            }
-            ServiceMessage::ListAllResources {
+            } => f
+                .debug_struct("CallTool")
+                .field("service_name", service_name)
+                .field("params", params)
+                .field("timeout", timeout)
+                .field("meta", meta)
+                .field("idempotency_key", idempotency_key)
+                .field("cancel", cancel)
+                .field("reply", reply)
+                .finish(),
+            Self::CallFinished {
+                service_name,
+                outcome,
+            } => f
+                .debug_struct("CallFinished")
+                .field("service_name", service_name)
+                .field("outcome", outcome)
+                .finish(),
+            Self::ToolsListed {
                 service_name,
+                result,
                 reply,
-            } => {
-                let result = if let Some(service) = state.active_services.get(&service_name) {
-                    service.list_all_resources().await.map_err(|e| e.into())
-                } else {
-                    Err(anyhow!(
-                        "Service '{}' not found to list resources.",
-                        service_name
-                    ))
-                };
-                let _ = reply.send(result);
-            }
-            ServiceMessage::ListAllResourceTemplates {
+            } => f
+                .debug_struct("ToolsListed")
+                .field("service_name", service_name)
+                .field("result", result)
+                .field("reply", reply)
+                .finish(),
+            Self::PromptsListed {
                 service_name,
+                result,
                 reply,
-            } => {
-                let result = if let Some(service) = state.active_services.get(&service_name) {
-                    service
-                        .list_all_resource_templates()
-                        .await
-                        .map_err(|e| e.into())
-                } else {
-                    Err(anyhow!(
-                        "Service '{}' not found to list resource templates.",
-                        service_name
-                    ))
-                };
-                let _ = reply.send(result);
-            }
-            ServiceMessage::GetPrompt {
+            } => f
+                .debug_struct("PromptsListed")
+                .field("service_name", service_name)
+                .field("result", result)
+                .field("reply", reply)
+                .finish(),
+            Self::DiscoveryHistory { window, reply } => f
+                .debug_struct("DiscoveryHistory")
+                .field("window", window)
+                .field("reply", reply)
+                .finish(),
+            Self::ServiceReplicas { identity, reply } => f
+                .debug_struct("ServiceReplicas")
+                .field("identity", identity)
+                .field("reply", reply)
+                .finish(),
+            Self::ActiveServiceInfos { reply } => f
+                .debug_struct("ActiveServiceInfos")
+                .field("reply", reply)
+                .finish(),
+            Self::ListActiveServices { reply } => f
+                .debug_struct("ListActiveServices")
+                .field("reply", reply)
+                .finish(),
+            Self::GetServiceInfo {
                 service_name,
-                prompt_request,
                 reply,
-            } => {
-                let result = if let Some(service) = state.active_services.get(&service_name) {
-                    service
-                        .get_prompt(prompt_request)
-                        .await
-                        .map_err(|e| e.into())
-                } else {
-                    Err(anyhow!(
-                        "Service '{}' not found to get prompt '{:?}'.",
-                        service_name,
-                        prompt_request
-                    ))
-                };
-                let _ = reply.send(result);
+            } => f
+                .debug_struct("GetServiceInfo")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::RenderTemplates {
+                service_name,
+                templates,
+                inputs,
+                reply,
+            } => f
+                .debug_struct("RenderTemplates")
+                .field("service_name", service_name)
+                .field("templates", &templates.keys().collect::<Vec<_>>())
+                .field(
+                    "inputs",
+                    &inputs.as_ref().map(|i| i.keys().collect::<Vec<_>>()),
+                )
+                .field("reply", reply)
+                .finish(),
+            Self::ExportState { reply } => {
+                f.debug_struct("ExportState").field("reply", reply).finish()
             }
-        }
-        Ok(())
-    }
-}
-
-pub struct ServiceManager<M: MdnsBrowser> {
-    actor: ActorRef<ServiceMessage>,
-    config: ZeroConfig,
-    mdns: M,
-    app_handler: Arc<dyn ZeroHandler>,
-}
-
-impl<M: MdnsBrowser> fmt::Debug for ServiceManager<M> {
+            Self::HealthSummary { reply } => f
+                .debug_struct("HealthSummary")
+                .field("reply", reply)
+                .finish(),
+            Self::SessionId {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("SessionId")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::ServiceHealth {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("ServiceHealth")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::ToolsDiff {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("ToolsDiff")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::RefreshDiscovery {
+                service_type,
+                reply,
+            } => f
+                .debug_struct("RefreshDiscovery")
+                .field("service_type", service_type)
+                .field("reply", reply)
+                .finish(),
+            Self::StreamingPeer {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("StreamingPeer")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::SetLaunchOverride {
+                fullname,
+                config,
+                reply,
+            } => f
+                .debug_struct("SetLaunchOverride")
+                .field("fullname", fullname)
+                .field("config", config)
+                .field("reply", reply)
+                .finish(),
+            Self::LaunchOverride { fullname, reply } => f
+                .debug_struct("LaunchOverride")
+                .field("fullname", fullname)
+                .field("reply", reply)
+                .finish(),
+            Self::SetRoots {
+                service_name,
+                roots,
+                reply,
+            } => f
+                .debug_struct("SetRoots")
+                .field("service_name", service_name)
+                .field("roots", roots)
+                .field("reply", reply)
+                .finish(),
+            Self::MemoryStats { reply } => {
+                f.debug_struct("MemoryStats").field("reply", reply).finish()
+            }
+            Self::StartupTimings {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("StartupTimings")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::StartupHistogram { reply } => f
+                .debug_struct("StartupHistogram")
+                .field("reply", reply)
+                .finish(),
+            Self::ServicesWithCapability { capability, reply } => f
+                .debug_struct("ServicesWithCapability")
+                .field("capability", capability)
+                .field("reply", reply)
+                .finish(),
+            Self::TapTraffic {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("TapTraffic")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::StoppedAt {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("StoppedAt")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::DrainCalls {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("DrainCalls")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::ResumeCalls {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("ResumeCalls")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::Ping {
+                service_name,
+                timeout,
+                reply,
+            } => f
+                .debug_struct("Ping")
+                .field("service_name", service_name)
+                .field("timeout", timeout)
+                .field("reply", reply)
+                .finish(),
+            Self::RemoveExited {
+                service_name,
+                pid,
+                reply,
+            } => f
+                .debug_struct("RemoveExited")
+                .field("service_name", service_name)
+                .field("pid", pid)
+                .field("reply", reply)
+                .finish(),
+            Self::LaunchSynthetic {
+                service,
+                config,
+                reply,
+            } => f
+                .debug_struct("LaunchSynthetic")
+                .field("service", service)
+                .field("config", config)
+                .field("reply", reply)
+                .finish(),
+            Self::RestartService {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("RestartService")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::ClaimLaunch {
+                service_name,
+                reply,
+            } => f
+                .debug_struct("ClaimLaunch")
+                .field("service_name", service_name)
+                .field("reply", reply)
+                .finish(),
+            Self::ReleaseLaunch { service_name } => f
+                .debug_struct("ReleaseLaunch")
+                .field("service_name", service_name)
+                .finish(),
+            Self::SupersedeLaunch {
+                service,
+                config,
+                reply,
+            } => f
+                .debug_struct("SupersedeLaunch")
+                .field("service", service)
+                .field("config", config)
+                .field("reply", reply)
+                .finish(),
+            Self::DueHealthChecks { reply } => f
+                .debug_struct("DueHealthChecks")
+                .field("reply", reply)
+                .finish(),
+            Self::IdleServices { reply } => f
+                .debug_struct("IdleServices")
+                .field("reply", reply)
+                .finish(),
+            Self::EnforceMemoryBudget => f.write_str("EnforceMemoryBudget"),
+        }
+    }
+}
+
+pub struct ActorState {
+    active_services: HashMap<String, ActiveService>,
+    history: DiscoveryHistory,
+    replica_identity_key: Option<String>,
+    /// Per-instance launch configs that take precedence over the type mapping.
+    launch_overrides: HashMap<String, McpConfig>,
+    /// Publishes the sorted names of active services after every change.
+    active_set: watch::Sender<Vec<String>>,
+    /// The configured Zeroconf service types.
+    service_types: Vec<String>,
+    /// Sends commands to the discovery loop.
+    discovery: mpsc::UnboundedSender<DiscoveryCommand>,
+    /// In-flight tool calls callers can join, by service name and idempotency key.
+    idempotent_calls: HashMap<(String, String), SharedCall>,
+    /// Cap on the bytes of cached listings and unread stream fragments, from
+    /// `memory_budget_bytes`.
+    memory_budget: Option<usize>,
+    /// The fragment buffers of streaming tool calls, to count against it.
+    fragment_buffers: Vec<Weak<FragmentBuffer>>,
+    /// The budget as the buffers see it, so they can ask for it to be
+    /// enforced as they fill up.
+    fragment_budget: Option<Arc<FragmentBudget>>,
+    evictions: u64,
+    evicted_bytes: u64,
+    /// The application's handler, set once it has been created.
+    handler: Arc<OnceLock<Arc<dyn ZeroHandler>>>,
+    /// When each service was last stopped, with its `relaunch_cooldown_ms`,
+    /// until that has passed.
+    stopped_at: HashMap<String, (Instant, Duration)>,
+    /// Services with a launch in progress, so they aren't launched twice,
+    /// each with the token cancelled if it's stopped meanwhile.
+    launching: HashMap<String, CancellationToken>,
+    /// Changed resolutions that arrived during a service's launch, to
+    /// relaunch once it ends.
+    superseded: HashMap<String, (DiscoveredService, McpConfig)>,
+    /// Set by `BeginShutdown`, after which no service is tracked again.
+    shutting_down: bool,
+    /// Launch phase durations of every service added so far.
+    startup_histogram: StartupHistogram,
+    /// Where stops are reported, as they happen here whoever asked for them.
+    metrics: Metrics,
+    /// Notified when a service with its own health-check or idle settings is added.
+    liveness: Arc<Notify>,
+}
+
+impl ActorState {
+    /// Drops the least recently used cached listings, and the unread
+    /// fragments that have waited longest, until the rest fit in the memory
+    /// budget.
+    fn enforce_memory_budget(&mut self) {
+        self.fragment_buffers
+            .retain(|buffer| buffer.strong_count() > 0);
+        let Some(budget) = self.memory_budget else {
+            return;
+        };
+        let prompts = self.active_services.iter().filter_map(|(name, service)| {
+            let cached = service.prompts_snapshot.as_ref()?;
+            Some((
+                Evictable::Prompts(name.clone()),
+                cached.bytes(),
+                cached.last_used(),
+            ))
+        });
+        let fragments = self
+            .fragment_buffers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter_map(|buffer| {
+                let (bytes, oldest) = buffer.usage()?;
+                Some((Evictable::Fragments(buffer), bytes, oldest))
+            });
+        let entries = prompts.chain(fragments).collect();
+        for evictable in lru_evictions(entries, budget) {
+            let bytes = match evictable {
+                Evictable::Prompts(name) => {
+                    let bytes = self
+                        .active_services
+                        .get_mut(&name)
+                        .and_then(|service| service.prompts_snapshot.take())
+                        .map_or(0, |cached| cached.bytes());
+                    debug!(service.name = %name, bytes, "Evicted cached prompts to stay within the memory budget");
+                    bytes
+                }
+                Evictable::Fragments(buffer) => {
+                    let bytes = buffer.shed();
+                    warn!(
+                        bytes,
+                        "Shed unread stream fragments to stay within the memory budget"
+                    );
+                    bytes
+                }
+            };
+            self.evictions += 1;
+            self.evicted_bytes += bytes as u64;
+            self.metrics.memory_evicted(bytes);
+        }
+        if let Some(fragment_budget) = &self.fragment_budget {
+            fragment_budget.enforced(self.memory_stats().cached_bytes);
+        }
+    }
+
+    fn memory_stats(&self) -> MemoryStats {
+        let listings: Vec<usize> = self
+            .active_services
+            .values()
+            .filter_map(|service| service.prompts_snapshot.as_ref())
+            .map(|cached| cached.bytes())
+            .collect();
+        MemoryStats {
+            cached_bytes: listings.iter().sum(),
+            cached_listings: listings.len(),
+            buffered_bytes: self
+                .fragment_buffers
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter_map(|buffer| buffer.usage())
+                .map(|(bytes, _)| bytes)
+                .sum(),
+            budget_bytes: self.memory_budget,
+            evictions: self.evictions,
+            evicted_bytes: self.evicted_bytes,
+        }
+    }
+
+    /// Takes a service out of rotation to be stopped, recording its removal.
+    fn take_service(&mut self, name: &str) -> Option<ActiveService> {
+        let service = self.active_services.remove(name)?;
+        self.history.record(LifecycleEvent::Removed);
+        self.record_stop(name, &service);
+        self.publish_active_set();
+        Some(service)
+    }
+
+    /// Remembers when a service stopped, for its relaunch cooldown, and
+    /// forgets the stops whose cooldown has passed.
+    fn record_stop(&mut self, name: &str, service: &ActiveService) {
+        self.stopped_at
+            .retain(|_, (stopped, cooldown)| stopped.elapsed() < *cooldown);
+        if let Some(cooldown) = service.config.options().relaunch_cooldown_ms {
+            let cooldown = Duration::from_millis(cooldown);
+            self.stopped_at
+                .insert(name.to_string(), (Instant::now(), cooldown));
+        }
+    }
+
+    /// Tells whoever is launching `name` that it has been stopped meanwhile,
+    /// e.g. a supervisor backing off after discovery removed the service.
+    fn withdraw_launch(&self, name: &str) {
+        if let Some(withdrawn) = self.launching.get(name) {
+            info!("Withdrawing the launch in progress of '{}'", name);
+            withdrawn.cancel();
+        }
+    }
+
+    fn publish_active_set(&self) {
+        let mut names: Vec<String> = self.active_services.keys().cloned().collect();
+        names.sort();
+        self.active_set.send_if_modified(|current| {
+            let changed = *current != names;
+            *current = names;
+            changed
+        });
+    }
+}
+
+pub struct ServiceActor;
+
+/// Everything the service actor needs from the manager that spawns it.
+pub struct ServiceActorArgs {
+    pub(crate) config: ZeroConfig,
+    pub(crate) active_set: watch::Sender<Vec<String>>,
+    pub(crate) discovery: mpsc::UnboundedSender<DiscoveryCommand>,
+    pub(crate) handler: Arc<OnceLock<Arc<dyn ZeroHandler>>>,
+    pub(crate) metrics: Metrics,
+    /// Starts the liveness loop if the config alone didn't.
+    pub(crate) liveness: Arc<Notify>,
+}
+
+/// Requests handled by the discovery loop.
+#[derive(Debug)]
+pub(crate) enum DiscoveryCommand {
+    /// Re-query a service type.
+    Refresh(String),
+    /// Report which service types are being browsed.
+    Probe(oneshot::Sender<DiscoveryStatus>),
+    /// Launch a service that wasn't discovered, as if it had been.
+    Launch {
+        service: Box<DiscoveredService>,
+        config: Box<McpConfig>,
+    },
+    /// Relaunch a service saved by `ZeroMcp::export_state`, counting it as
+    /// resolved so an unchanged rediscovery doesn't launch it again.
+    Restore(Box<SavedService>),
+    /// Launch a service's latest resolution in place of any running instance,
+    /// once the launch it superseded has ended.
+    Relaunch {
+        service: Box<DiscoveredService>,
+        config: Box<McpConfig>,
+    },
+    /// Launch a service again after `ZeroClient::restart_service` stopped it.
+    Restart {
+        service: Box<DiscoveredService>,
+        config: Box<McpConfig>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Forget a service's last resolution because launching it failed, so
+    /// the next resolution launches it again even if nothing changed. Ignored
+    /// if the service has since resolved differently.
+    Forget(Box<DiscoveredService>),
+    /// Stop browsing and return from `run`.
+    Shutdown,
+}
+
+/// The outcome of stopping each service, by name.
+pub type StopResults = Vec<(String, Result<QuitReason>)>;
+/// Rendered templates by key, and the template inputs used to render them.
+pub type RenderedTemplates = (HashMap<String, String>, Map<String, Value>);
+pub type McpClient = RunningService<RoleClient, Box<dyn DynService<RoleClient>>>;
+
+/// What a streaming tool call needs to run off the actor.
+#[derive(Debug)]
+pub struct StreamingCall {
+    pub(crate) peer: Peer<RoleClient>,
+    pub(crate) progress: ProgressRouter,
+    /// The service's `request_meta`.
+    pub(crate) meta: Meta,
+    /// The service's `tool_call_timeout_ms`.
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) limit: CallLimit,
+    pub(crate) slot: QueueSlot,
+    /// Where fragments wait for the caller, counted against the memory budget.
+    pub(crate) buffer: Arc<FragmentBuffer>,
+}
+
+/// Everything needed to launch a service again after its process exits.
+struct Launch<'a> {
+    cfg: &'a McpConfig,
+    service: &'a DiscoveredService,
+    app_handler: &'a Arc<dyn ZeroHandler>,
+    hooks: &'a ManagerHooks,
+    redact_keys: &'a [String],
+}
+
+/// How a supervised service is brought back after it goes away on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recovery {
+    /// Relaunch the stdio process from the service's config.
+    Restart,
+    /// Connect to the same url with the same headers again.
+    Reconnect,
+}
+
+/// A running service being watched by [`ServiceManager::supervise`].
+struct Supervision {
+    peer: Peer<RoleClient>,
+    pid: Option<u32>,
+    policy: RestartPolicy,
+    recovery: Recovery,
+    /// The template context to reconnect with.
+    context: Value,
+}
+
+/// A rendered SSE endpoint to try, along with the advertised address it targets.
+#[derive(Clone)]
+struct SseCandidate {
+    address: Option<String>,
+    url: String,
+}
+
+impl fmt::Display for SseCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Who is waiting on a tool listing made off the actor.
+#[derive(Debug)]
+pub enum ToolsReply {
+    List(RpcReplyPort<Result<Vec<Tool>>>),
+    Diff(RpcReplyPort<Result<ToolsDiff>>),
+}
+
+impl ToolsReply {
+    fn fail(self, error: anyhow::Error) {
+        match self {
+            Self::List(reply) => {
+                let _ = reply.send(Err(error));
+            }
+            Self::Diff(reply) => {
+                let _ = reply.send(Err(error));
+            }
+        }
+    }
+}
+
+/// Who is waiting on a prompt listing made off the actor.
+#[derive(Debug)]
+pub enum PromptsReply {
+    List(RpcReplyPort<Result<Vec<Prompt>>>),
+    Arguments {
+        prompt_name: String,
+        reply: RpcReplyPort<Result<Vec<PromptArgument>>>,
+    },
+}
+
+/// Returns the arguments of `prompt_name`, if the listing includes it.
+fn prompt_arguments(prompts: &[Prompt], prompt_name: &str) -> Option<Vec<PromptArgument>> {
+    prompts
+        .iter()
+        .find(|p| p.name == prompt_name)
+        .map(|p| p.arguments.clone().unwrap_or_default())
+}
+
+/// What the memory budget can free.
+enum Evictable {
+    /// A service's cached prompt listing.
+    Prompts(String),
+    /// A streaming tool call's unread fragments.
+    Fragments(Arc<FragmentBuffer>),
+}
+
+/// A running MCP client together with the bookkeeping needed to tear it down.
+pub struct ActiveService {
+    client: McpClient,
+    info: DiscoveredService,
+    config: McpConfig,
+    /// Process ID of the spawned child for stdio services.
+    pid: Option<u32>,
+    /// The spawned child for stdio services, to signal it directly.
+    child: Option<ChildHandle>,
+    /// Session ID negotiated by the SSE transport.
+    session_id: Option<String>,
+    health: HealthTracker,
+    /// The most recent tool listing, kept to diff against the next one. Only
+    /// its fingerprints are kept, so it's left out of the memory budget.
+    tools_baseline: Option<ToolFingerprints>,
+    /// The most recent prompt listing, used to answer argument lookups.
+    prompts_snapshot: Option<Cached<Vec<Prompt>>>,
+    /// Delivers progress messages to streaming tool calls.
+    progress: ProgressRouter,
+    /// The roots reported to the server, shared with its client handler.
+    roots: Arc<RwLock<Vec<Root>>>,
+    /// Bounds the service's outstanding tool calls.
+    calls: CallQueue,
+    /// Bounds the requests in flight to the service, per `max_concurrent_calls`.
+    limit: CallLimit,
+    /// The template context the service was rendered from, including any
+    /// inputs, so it can be reconnected without asking for them again.
+    context: Value,
+    /// Publishes every JSON-RPC message exchanged with the service.
+    traffic: broadcast::Sender<TrafficEvent>,
+    /// How long each phase of the launch took.
+    startup: StartupTimings,
+    /// When the service last got a tool call, prompt or resource request, for
+    /// `idle_timeout_ms`.
+    last_active: Instant,
+    /// When the service was last picked for a health check.
+    last_health_check: Instant,
+}
+
+/// Bounds the tool calls outstanding on a service, whether waiting for a
+/// `max_concurrent_calls` slot or running, per its `max_queue_depth`, and
+/// counts them and the service's other requests so they can be drained.
+/// Calls run concurrently; the queue only sheds load.
+struct CallQueue {
+    depth: Option<Arc<Semaphore>>,
+    max_depth: Option<usize>,
+    outstanding: Arc<watch::Sender<usize>>,
+    /// Set while the service is drained, so new calls are refused.
+    draining: bool,
+}
+
+/// A place in a service's call queue, held until the call finishes.
+#[derive(Debug)]
+pub(crate) struct QueueSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+    outstanding: Arc<watch::Sender<usize>>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        self.outstanding.send_modify(|n| *n -= 1);
+    }
+}
+
+impl CallQueue {
+    fn new(max_depth: Option<usize>) -> Self {
+        Self {
+            depth: max_depth.map(|depth| Arc::new(Semaphore::new(depth))),
+            max_depth,
+            outstanding: Arc::new(watch::channel(0).0),
+            draining: false,
+        }
+    }
+
+    /// Takes a place for a new call, failing with `ZeroError::QueueFull` if
+    /// `max_queue_depth` calls are already outstanding, or with
+    /// `ZeroError::Draining` while the service is drained.
+    fn reserve(&self, service_name: &str) -> Result<QueueSlot> {
+        if self.draining {
+            return Err(ZeroError::Draining {
+                service: service_name.to_string(),
+            }
+            .into());
+        }
+        let permit = match &self.depth {
+            Some(depth) => {
+                Some(
+                    depth
+                        .clone()
+                        .try_acquire_owned()
+                        .map_err(|_| ZeroError::QueueFull {
+                            service: service_name.to_string(),
+                            depth: self.max_depth.unwrap_or_default(),
+                        })?,
+                )
+            }
+            None => None,
+        };
+        let mut slot = self.track();
+        slot._permit = permit;
+        Ok(slot)
+    }
+
+    /// Counts a request outside `max_queue_depth`, so draining waits for it too.
+    fn track(&self) -> QueueSlot {
+        self.outstanding.send_modify(|n| *n += 1);
+        QueueSlot {
+            _permit: None,
+            outstanding: self.outstanding.clone(),
+        }
+    }
+
+    /// Resolves once no calls or requests are outstanding.
+    fn drained(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut outstanding = self.outstanding.subscribe();
+        async move {
+            let _ = outstanding.wait_for(|n| *n == 0).await;
+        }
+    }
+}
+
+/// The outcome of a tool call shared by several callers.
+type SharedResult = std::result::Result<CallToolResult, Arc<anyhow::Error>>;
+
+/// A tool call made with an idempotency key, which later calls with the same
+/// key join instead of reaching the server.
+struct SharedCall {
+    params: CallToolRequestParam,
+    result: watch::Receiver<Option<SharedResult>>,
+    /// Held by each waiting caller. The call is cancelled once the last one
+    /// lets go, and is stale once none are left.
+    waiters: Weak<DropGuard>,
+}
+
+impl SharedCall {
+    /// Starts `call` off the actor, cancelled through `cancel` once every
+    /// caller has stopped waiting for it. `finished` gets the call's health
+    /// outcome once its result is out.
+    fn start(
+        params: CallToolRequestParam,
+        call: impl Future<Output = Result<CallToolResult>> + Send + 'static,
+        cancel: CancellationToken,
+        finished: impl FnOnce(std::result::Result<(), String>) + Send + 'static,
+    ) -> (Self, Arc<DropGuard>) {
+        let (done, result) = watch::channel(None);
+        let guard = Arc::new(cancel.drop_guard());
+        tokio::spawn(async move {
+            let result = call.await;
+            let outcome = HealthTracker::outcome(&result);
+            done.send_replace(Some(result.map_err(Arc::new)));
+            finished(outcome);
+        });
+        let call = Self {
+            params,
+            result,
+            waiters: Arc::downgrade(&guard),
+        };
+        (call, guard)
+    }
+
+    /// Returns a hold on the call for a new caller, unless it has finished
+    /// or been abandoned.
+    fn join(&self) -> Option<Arc<DropGuard>> {
+        if self.is_over() {
+            return None;
+        }
+        self.waiters.upgrade()
+    }
+
+    /// Returns whether the call has a result or no one is waiting for it.
+    fn is_over(&self) -> bool {
+        self.result.borrow().is_some() || self.waiters.strong_count() == 0
+    }
+
+    /// Replies to one caller with the call's result, or with
+    /// `ZeroError::ToolCallCancelled` as soon as `cancel` fires. Other callers
+    /// keep waiting.
+    fn wait(
+        &self,
+        hold: Arc<DropGuard>,
+        tool: String,
+        cancel: Option<CancellationToken>,
+        reply: RpcReplyPort<Result<CallToolResult>>,
+    ) {
+        let mut result = self.result.clone();
+        let cancel = cancel.unwrap_or_default();
+        tokio::spawn(async move {
+            let outcome = tokio::select! {
+                finished = result.wait_for(Option::is_some) => match finished.as_deref() {
+                    Ok(Some(Ok(result))) => Ok(result.clone()),
+                    Ok(Some(Err(e))) => Err(SharedError::copy(e)),
+                    _ => Err(anyhow!("Tool call '{}' ended without a result.", tool)),
+                },
+                _ = cancel.cancelled() => Err(ZeroError::ToolCallCancelled { tool }.into()),
+            };
+            drop(hold);
+            let _ = reply.send(outcome);
+        });
+    }
+}
+
+/// Caps how many requests are in flight to a service at once, per its
+/// `max_concurrent_calls`. Shared by every request to the service.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CallLimit(Option<Arc<Semaphore>>);
+
+impl CallLimit {
+    fn new(max_concurrent_calls: Option<usize>) -> Self {
+        Self(max_concurrent_calls.map(|max| Arc::new(Semaphore::new(max.max(1)))))
+    }
+
+    /// Waits for a free slot, which is held until the permit is dropped.
+    /// Resolves at once when the service is unlimited.
+    pub(crate) async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>> {
+        match &self.0 {
+            Some(permits) => Ok(Some(permits.clone().acquire_owned().await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Sends requests to a service without borrowing it, so they can run off the
+/// actor while it handles messages for other services.
+#[derive(Clone)]
+struct ServiceRequester {
+    peer: Peer<RoleClient>,
+    config: McpConfig,
+    limit: CallLimit,
+    /// Counts the requests made through it as outstanding until it's dropped.
+    _tracked: Arc<QueueSlot>,
+}
+
+impl ServiceRequester {
+    /// Returns the service's `request_meta` with `meta` merged over it.
+    pub(crate) fn request_meta(&self, meta: Option<Meta>) -> Meta {
+        let mut merged = Meta::new();
+        if let Some(configured) = &self.config.options().request_meta {
+            merged.0.extend(configured.clone());
+        }
+        if let Some(meta) = meta {
+            merged.0.extend(meta.0);
+        }
+        merged
+    }
+
+    /// Sends a request carrying the service's `request_meta` merged with `meta`,
+    /// bounded by its `request_timeout_ms` once it gets a slot under
+    /// `max_concurrent_calls`.
+    async fn request(&self, request: ClientRequest, meta: Option<Meta>) -> Result<ServerResult> {
+        let _permit = self.limit.acquire().await?;
+        let meta = self.request_meta(meta);
+        let timeout = self
+            .config
+            .options()
+            .request_timeout_ms
+            .map(Duration::from_millis);
+        let handle = self
+            .peer
+            .send_cancellable_request(
+                request,
+                PeerRequestOptions {
+                    timeout,
+                    meta: (!meta.0.is_empty()).then_some(meta),
+                },
+            )
+            .await?;
+        match handle.await_response().await {
+            Ok(result) => Ok(result),
+            Err(ServiceError::Timeout { timeout }) => {
+                Err(ZeroError::RequestTimeout { timeout }.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Requests every page of a paginated listing.
+    ///
+    /// A server that didn't advertise `capability` has nothing to list, so it
+    /// isn't asked and the listing is empty.
+    async fn list_paginated<T>(
+        &self,
+        capability: Capability,
+        request: impl Fn(PaginatedRequestParam) -> ClientRequest,
+        page: impl Fn(ServerResult) -> Option<(Vec<T>, Option<String>)>,
+    ) -> Result<Vec<T>> {
+        if self
+            .peer
+            .peer_info()
+            .is_some_and(|info| !capability.offered_by(&info.capabilities))
+        {
+            debug!(capability = ?capability, "Server lacks the capability; listing nothing");
+            return Ok(Vec::new());
+        }
+        let mut items = Vec::new();
+        let mut cursor = None;
+        loop {
+            let result = self
+                .request(request(PaginatedRequestParam { cursor }), None)
+                .await?;
+            let (page, next_cursor) = page(result).ok_or(ServiceError::UnexpectedResponse)?;
+            items.extend(page);
+            cursor = next_cursor;
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    async fn list_tools(&self) -> Result<Vec<Tool>> {
+        let tools = self
+            .list_paginated(
+                Capability::Tools,
+                |p| ClientRequest::ListToolsRequest(ListToolsRequest::with_param(p)),
+                |result| match result {
+                    ServerResult::ListToolsResult(r) => Some((r.tools, r.next_cursor)),
+                    _ => None,
+                },
+            )
+            .await?;
+        dedup_tools(
+            tools,
+            self.config.options().duplicate_tools.unwrap_or_default(),
+        )
+    }
+
+    async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        self.list_paginated(
+            Capability::Prompts,
+            |p| ClientRequest::ListPromptsRequest(ListPromptsRequest::with_param(p)),
+            |result| match result {
+                ServerResult::ListPromptsResult(r) => Some((r.prompts, r.next_cursor)),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    async fn list_resources(&self) -> Result<Vec<Resource>> {
+        self.list_paginated(
+            Capability::Resources,
+            |p| ClientRequest::ListResourcesRequest(ListResourcesRequest::with_param(p)),
+            |result| match result {
+                ServerResult::ListResourcesResult(r) => Some((r.resources, r.next_cursor)),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    async fn list_resource_templates(&self) -> Result<Vec<ResourceTemplate>> {
+        self.list_paginated(
+            Capability::Resources,
+            |p| {
+                ClientRequest::ListResourceTemplatesRequest(
+                    ListResourceTemplatesRequest::with_param(p),
+                )
+            },
+            |result| match result {
+                ServerResult::ListResourceTemplatesResult(r) => {
+                    Some((r.resource_templates, r.next_cursor))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    async fn read_resource(&self, params: ReadResourceRequestParam) -> Result<ReadResourceResult> {
+        let request = ClientRequest::ReadResourceRequest(ReadResourceRequest::new(params));
+        match self.request(request, None).await? {
+            ServerResult::ReadResourceResult(result) => Ok(result),
+            _ => Err(ServiceError::UnexpectedResponse.into()),
+        }
+    }
+
+    async fn subscribe(&self, params: SubscribeRequestParam) -> Result<()> {
+        let request = ClientRequest::SubscribeRequest(SubscribeRequest::new(params));
+        match self.request(request, None).await? {
+            ServerResult::EmptyResult(_) => Ok(()),
+            _ => Err(ServiceError::UnexpectedResponse.into()),
+        }
+    }
+
+    async fn unsubscribe(&self, params: UnsubscribeRequestParam) -> Result<()> {
+        let request = ClientRequest::UnsubscribeRequest(UnsubscribeRequest::new(params));
+        match self.request(request, None).await? {
+            ServerResult::EmptyResult(_) => Ok(()),
+            _ => Err(ServiceError::UnexpectedResponse.into()),
+        }
+    }
+
+    async fn get_prompt(
+        &self,
+        params: GetPromptRequestParam,
+        meta: Option<Meta>,
+    ) -> Result<GetPromptResult> {
+        let request = ClientRequest::GetPromptRequest(GetPromptRequest::new(params));
+        match self.request(request, meta).await? {
+            ServerResult::GetPromptResult(result) => Ok(result),
+            _ => Err(ServiceError::UnexpectedResponse.into()),
+        }
+    }
+
+    async fn complete(&self, params: CompleteRequestParam) -> Result<CompleteResult> {
+        let request = ClientRequest::CompleteRequest(CompleteRequest::new(params));
+        match self.request(request, None).await? {
+            ServerResult::CompleteResult(result) => Ok(result),
+            _ => Err(ServiceError::UnexpectedResponse.into()),
+        }
+    }
+
+    /// Sends an MCP ping, resolving to the round-trip time. Without a
+    /// `timeout`, the service's `request_timeout_ms` applies.
+    ///
+    /// The returned future doesn't borrow the requester, so it can be spawned.
+    fn ping(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Result<Duration>> + Send + 'static {
+        let peer = self.peer.clone();
+        let timeout = timeout.or_else(|| {
+            self.config
+                .options()
+                .request_timeout_ms
+                .map(Duration::from_millis)
+        });
+        async move {
+            let started = Instant::now();
+            let handle = peer
+                .send_cancellable_request(
+                    ClientRequest::PingRequest(PingRequest::default()),
+                    PeerRequestOptions {
+                        timeout,
+                        meta: None,
+                    },
+                )
+                .await?;
+            match handle.await_response().await {
+                Ok(ServerResult::EmptyResult(_)) => Ok(started.elapsed()),
+                Ok(_) => Err(ServiceError::UnexpectedResponse.into()),
+                Err(ServiceError::Timeout { timeout }) => {
+                    Err(ZeroError::RequestTimeout { timeout }.into())
+                }
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+
+    /// Calls a tool once it gets a slot under `max_concurrent_calls`, bounded
+    /// by `timeout` or else the service's configured `tool_call_timeout_ms`,
+    /// and abandoned if `cancel` is cancelled. On expiry or cancellation the
+    /// server is sent a cancellation.
+    ///
+    /// The returned future doesn't borrow the requester, so it can be spawned.
+    fn call_tool(
+        &self,
+        params: CallToolRequestParam,
+        timeout: Option<Duration>,
+        meta: Option<Meta>,
+        idempotency_key: Option<String>,
+        cancel: Option<CancellationToken>,
+    ) -> impl Future<Output = Result<CallToolResult>> + Send + 'static {
+        let peer = self.peer.clone();
+        let limit = self.limit.clone();
+        let timeout = timeout.or(self
+            .config
+            .options()
+            .tool_call_timeout_ms
+            .map(Duration::from_millis));
+        let mut meta = self.request_meta(meta);
+        if let Some(key) = idempotency_key {
+            meta.0
+                .insert(IDEMPOTENCY_KEY_META.to_string(), Value::String(key));
+        }
+        let meta = (!meta.0.is_empty()).then_some(meta);
+        // The timeout runs from when the call is made, so time spent waiting
+        // for a `max_concurrent_calls` slot counts towards it.
+        let started = tokio::time::Instant::now();
+        let deadline = timeout.map(|timeout| started + timeout);
+
+        async move {
+            let tool = params.name.to_string();
+            let cancel = cancel.unwrap_or_default();
+            if cancel.is_cancelled() {
+                return Err(ZeroError::ToolCallCancelled { tool }.into());
+            }
+            let expired = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let _permit = tokio::select! {
+                permit = limit.acquire() => permit?,
+                _ = cancel.cancelled() => return Err(ZeroError::ToolCallCancelled { tool }.into()),
+                _ = expired => {
+                    return Err(ZeroError::ToolCallTimeout {
+                        tool,
+                        elapsed: started.elapsed(),
+                    }
+                    .into());
+                }
+            };
+            let timeout =
+                deadline.map(|deadline| deadline.duration_since(tokio::time::Instant::now()));
+            let handle = peer
+                .send_cancellable_request(
+                    ClientRequest::CallToolRequest(CallToolRequest::new(params)),
+                    PeerRequestOptions { timeout, meta },
+                )
+                .await?;
+            let request_id = handle.id.clone();
+            let response = tokio::select! {
+                response = handle.await_response() => response,
+                _ = cancel.cancelled() => {
+                    let notification = CancelledNotification {
+                        params: CancelledNotificationParam {
+                            request_id,
+                            reason: Some("cancelled by caller".to_string()),
+                        },
+                        method: CancelledNotificationMethod,
+                        extensions: Default::default(),
+                    };
+                    let _ = peer.send_notification(notification.into()).await;
+                    return Err(ZeroError::ToolCallCancelled { tool }.into());
+                }
+            };
+            match response {
+                Ok(ServerResult::CallToolResult(result)) => Ok(result),
+                Ok(_) => Err(ServiceError::UnexpectedResponse.into()),
+                Err(ServiceError::Timeout { .. }) => Err(ZeroError::ToolCallTimeout {
+                    tool,
+                    elapsed: started.elapsed(),
+                }
+                .into()),
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+}
+
+impl ActiveService {
+    /// Returns what [`ServiceManager::supervise`] needs to watch the service,
+    /// if its config asks for it: a `restart` policy for stdio services, or a
+    /// `reconnect` policy for SSE and streamable HTTP ones.
+    fn supervision(&self) -> Option<Supervision> {
+        let options = self.config.options();
+        let (recovery, policy) = match &self.config {
+            McpConfig::Stdio { .. } => (Recovery::Restart, options.restart?),
+            McpConfig::Sse { .. } | McpConfig::StreamableHttp { .. } => {
+                (Recovery::Reconnect, options.reconnect?)
+            }
+        };
+        Some(Supervision {
+            peer: self.client.peer().clone(),
+            pid: self.pid,
+            policy,
+            recovery,
+            context: self.context.clone(),
+        })
+    }
+
+    /// Returns a handle for sending requests to the service off the actor.
+    fn requester(&self) -> ServiceRequester {
+        ServiceRequester {
+            peer: self.client.peer().clone(),
+            config: self.config.clone(),
+            limit: self.limit.clone(),
+            _tracked: Arc::new(self.calls.track()),
+        }
+    }
+
+    /// Waits out the service's `min_stable_uptime_ms`, failing if the
+    /// connection closes before it elapses.
+    async fn wait_until_stable(&self) -> Result<()> {
+        let Some(window) = self
+            .config
+            .options()
+            .min_stable_uptime_ms
+            .map(Duration::from_millis)
+        else {
+            return Ok(());
+        };
+        let deadline = Instant::now() + window;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if self.client.peer().is_transport_closed() {
+                bail!(
+                    "Service disconnected {:?} after starting, within its minimum stable uptime of {:?}",
+                    window - remaining,
+                    window
+                );
+            }
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            tokio::time::sleep(remaining.min(STABILITY_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Returns the error to fail a tool call with while the service's
+    /// `circuit_breaker` is open.
+    fn open_circuit(&self, service_name: &str) -> Option<anyhow::Error> {
+        let breaker = self.config.options().circuit_breaker.as_ref()?;
+        self.health.circuit_open(breaker).then(|| {
+            ZeroError::CircuitOpen {
+                service: service_name.to_string(),
+                failures: self.health.status().consecutive_failures,
+            }
+            .into()
+        })
+    }
+
+    /// Checks the connected service against its post-connect requirements,
+    /// returning the reason it should be rejected, if any.
+    async fn rejection_reason(&self) -> Option<String> {
+        let pattern = self.config.options().require_tool.as_deref()?;
+        match self.requester().list_tools().await {
+            Ok(tools) if tools.iter().any(|t| glob_match(pattern, &t.name)) => None,
+            Ok(_) => Some(format!("no tool matches required pattern '{}'", pattern)),
+            Err(e) => Some(format!(
+                "failed to list tools to check '{}': {}",
+                pattern, e
+            )),
+        }
+    }
+
+    /// Returns whether the server advertised `capability` when it connected.
+    fn supports(&self, capability: Capability) -> bool {
+        self.client
+            .peer_info()
+            .is_some_and(|info| capability.offered_by(&info.capabilities))
+    }
+
+    /// Returns whether the server offers no tools, prompts or resources.
+    ///
+    /// Only the capabilities the server declared are listed. A listing that
+    /// fails counts as non-empty, so errors never get a service dropped here.
+    async fn offers_nothing(&self) -> bool {
+        let Some(info) = self.client.peer_info() else {
+            return false;
+        };
+        let capabilities = &info.capabilities;
+        let requester = self.requester();
+        let non_empty = |len: Result<usize>| len.map_or(true, |len| len > 0);
+        !((capabilities.tools.is_some()
+            && non_empty(requester.list_tools().await.map(|t| t.len())))
+            || (capabilities.prompts.is_some()
+                && non_empty(requester.list_prompts().await.map(|p| p.len())))
+            || (capabilities.resources.is_some()
+                && non_empty(requester.list_resources().await.map(|r| r.len()))))
+    }
+
+    /// Stops the service in up to `attempts` steps, waiting `backoff` after
+    /// each. The first cancels the client, which closes a stdio process's
+    /// stdin; later ones send the process SIGTERM. A stdio process still
+    /// running after the last one is killed. Other transports have nothing
+    /// to escalate to, so for them each attempt only waits longer.
+    async fn cancel_with_retry(self, attempts: usize, backoff: Duration) -> Result<StopOutcome> {
+        let ActiveService {
+            client, pid, child, ..
+        } = self;
+        let mut cancel = Box::pin(client.cancel());
+        let mut reason = None;
+
+        for attempt in 1..=attempts.max(1) {
+            if attempt > 1
+                && let Some(child) = &child
+                && let Err(e) = child.terminate().await
+            {
+                warn!(attempt, error = %e, "Failed to send SIGTERM to the service process");
+            }
+            let stopped = tokio::time::timeout(backoff, async {
+                if reason.is_none() {
+                    reason = Some((&mut cancel).await?);
+                }
+                if let Some(child) = &child {
+                    child.wait().await?;
+                }
+                anyhow::Ok(())
+            })
+            .await;
+            match stopped {
+                Ok(result) => {
+                    result?;
+                    return Ok(StopOutcome::Cancelled {
+                        reason: reason.unwrap_or(QuitReason::Cancelled),
+                        attempts: attempt,
+                    });
+                }
+                Err(_) => {
+                    warn!(
+                        attempt,
+                        "Service did not stop within {:?}; retrying", backoff
+                    );
+                }
+            }
+        }
+
+        match (child, pid) {
+            (Some(child), Some(pid)) => {
+                warn!(pid, "Service process did not exit; killing it");
+                child.kill().await?;
+                Ok(StopOutcome::Killed { pid })
+            }
+            _ => Err(anyhow!(
+                "Service did not stop after {} cancellation attempts",
+                attempts
+            )),
+        }
+    }
+}
+
+impl ServiceActor {
+    /// Runs `request` off the actor, so a slow service doesn't hold up messages
+    /// for the others, then records its outcome in the service's health.
+    fn spawn_request<T>(
+        myself: ActorRef<ServiceMessage>,
+        service_name: String,
+        request: impl Future<Output = Result<T>> + Send + 'static,
+        reply: RpcReplyPort<Result<T>>,
+    ) where
+        T: Send + 'static,
+    {
+        tokio::spawn(async move {
+            let result = request.await;
+            let _ = myself.cast(ServiceMessage::CallFinished {
+                service_name,
+                outcome: HealthTracker::outcome(&result),
+            });
+            let _ = reply.send(result);
+        });
+    }
+
+    /// Lists a service's tools off the actor, handing the result back in a
+    /// `ToolsListed` message so the snapshot is updated on the actor.
+    fn spawn_tools_listing(
+        myself: ActorRef<ServiceMessage>,
+        service: &ActiveService,
+        service_name: String,
+        reply: ToolsReply,
+    ) {
+        let requester = service.requester();
+        tokio::spawn(async move {
+            let result = requester.list_tools().await;
+            let _ = myself.cast(ServiceMessage::ToolsListed {
+                service_name,
+                result,
+                reply,
+            });
+        });
+    }
+
+    /// Lists a service's prompts off the actor, handing the result back in a
+    /// `PromptsListed` message so the snapshot is updated on the actor.
+    fn spawn_prompts_listing(
+        myself: ActorRef<ServiceMessage>,
+        service: &ActiveService,
+        service_name: String,
+        reply: PromptsReply,
+    ) {
+        let requester = service.requester();
+        tokio::spawn(async move {
+            let result = requester.list_prompts().await;
+            let _ = myself.cast(ServiceMessage::PromptsListed {
+                service_name,
+                result,
+                reply,
+            });
+        });
+    }
+
+    /// Runs `on_before_service_stop`, if there's a handler yet, for a service
+    /// already taken out of rotation.
+    async fn before_stop(handler: Option<&Arc<dyn ZeroHandler>>, name: &str) {
+        if let Some(handler) = handler
+            && let Err(panic) = catch_panic(handler.on_before_service_stop(name)).await
+        {
+            error!(panic = %panic, "on_before_service_stop panicked for '{}'", name);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Actor for ServiceActor {
+    type Msg = ServiceMessage;
+    type State = ActorState;
+    type Arguments = ServiceActorArgs;
+
+    async fn pre_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        let ServiceActorArgs {
+            config,
+            active_set,
+            discovery,
+            handler,
+            metrics,
+            liveness,
+        } = args;
+        Ok(ActorState {
+            active_services: HashMap::new(),
+            history: DiscoveryHistory::new(&config.discovery_history),
+            replica_identity_key: config.replica_identity_key,
+            launch_overrides: HashMap::new(),
+            active_set,
+            service_types: config
+                .service_mappings
+                .iter()
+                .flat_map(ServiceMcpMapping::browse_types)
+                .collect(),
+            discovery,
+            idempotent_calls: HashMap::new(),
+            memory_budget: config.memory_budget_bytes,
+            fragment_buffers: Vec::new(),
+            fragment_budget: config.memory_budget_bytes.map(|limit| {
+                Arc::new(FragmentBudget::new(limit, move || {
+                    let _ = myself.cast(ServiceMessage::EnforceMemoryBudget);
+                }))
+            }),
+            evictions: 0,
+            evicted_bytes: 0,
+            handler,
+            stopped_at: HashMap::new(),
+            launching: HashMap::new(),
+            superseded: HashMap::new(),
+            shutting_down: false,
+            startup_histogram: StartupHistogram::default(),
+            metrics,
+            liveness,
+        })
+    }
+
+    #[instrument(name = "service_actor_handle", skip(self, myself, state), fields(message_type = std::any::type_name::<ServiceMessage>()))]
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(service) = message
+            .requested_service()
+            .and_then(|name| state.active_services.get_mut(name))
+        {
+            service.last_active = Instant::now();
+        }
+        match message {
+            // Launches and supervisors still running when shutdown began
+            // mustn't leave a process behind.
+            ServiceMessage::AddService { name, service } if state.shutting_down => {
+                info!("Shutting down; stopping '{}' instead of tracking it", name);
+                tokio::spawn(async move {
+                    if let Err(e) = service.client.cancel().await {
+                        warn!("Failed to cleanly cancel service '{}': {}", name, e);
+                    }
+                });
+            }
+            ServiceMessage::AddService { name, service } => {
+                info!("Tracking new active service: {}", name);
+                let key = state.replica_identity_key.as_deref();
+                let identity = service.info.identity(key);
+                let replicas: Vec<&str> = state
+                    .active_services
+                    .iter()
+                    .filter(|(other, s)| **other != name && s.info.identity(key) == identity)
+                    .map(|(other, _)| other.as_str())
+                    .collect();
+                if !replicas.is_empty() {
+                    info!(
+                        "Service '{}' is a replica of {:?} (identity '{}')",
+                        name, replicas, identity
+                    );
+                }
+                state.startup_histogram.record(&service.startup);
+                let options = service.config.options();
+                if options.health_check_interval_ms.is_some() || options.idle_timeout_ms.is_some() {
+                    state.liveness.notify_one();
+                }
+                state.active_services.insert(name, *service);
+                state.history.record(LifecycleEvent::Added);
+                state.publish_active_set();
+            }
+            // Stops take the service out of rotation at once, so nothing else
+            // uses or stops it, then run the hook and cancel off the actor.
+            ServiceMessage::CancelService { name, reply } => {
+                state.superseded.remove(&name);
+                let Some(service) = state.take_service(&name) else {
+                    state.withdraw_launch(&name);
+                    let _ =
+                        reply.send(Err(ZeroError::service_not_found(&name, "for cancellation")));
+                    return Ok(());
+                };
+                let handler = state.handler.get().cloned();
+                let metrics = state.metrics.clone();
+                tokio::spawn(async move {
+                    Self::before_stop(handler.as_ref(), &name).await;
+                    let result = service.client.cancel().await.map_err(|e| e.into());
+                    match &result {
+                        Ok(reason) => metrics.service_stopped(&name, reason),
+                        Err(e) => warn!("Failed to cleanly cancel service '{}': {}", name, e),
+                    }
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::CancelServiceWithRetry {
+                name,
+                attempts,
+                backoff,
+                reply,
+            } => {
+                state.superseded.remove(&name);
+                let Some(service) = state.take_service(&name) else {
+                    state.withdraw_launch(&name);
+                    let _ =
+                        reply.send(Err(ZeroError::service_not_found(&name, "for cancellation")));
+                    return Ok(());
+                };
+                let handler = state.handler.get().cloned();
+                let metrics = state.metrics.clone();
+                tokio::spawn(async move {
+                    Self::before_stop(handler.as_ref(), &name).await;
+                    let result = service.cancel_with_retry(attempts, backoff).await;
+                    match &result {
+                        Ok(outcome) => {
+                            info!("Service '{}' stopped: {:?}", name, outcome);
+                            let reason = match outcome {
+                                StopOutcome::Cancelled { reason, .. } => reason,
+                                StopOutcome::Killed { .. } => &QuitReason::Cancelled,
+                            };
+                            metrics.service_stopped(&name, reason);
+                        }
+                        Err(e) => warn!("Failed to stop service '{}': {}", name, e),
+                    }
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::BeginShutdown { reply } => {
+                state.shutting_down = true;
+                state.superseded.clear();
+                let _ = reply.send(Ok(()));
+            }
+            ServiceMessage::StopAllServices { reply } => {
+                let mut names: Vec<String> = state.active_services.keys().cloned().collect();
+                names.sort();
+                let services: Vec<_> = names
+                    .into_iter()
+                    .filter_map(|name| Some((name.clone(), state.take_service(&name)?)))
+                    .collect();
+                let handler = state.handler.get().cloned();
+                let metrics = state.metrics.clone();
+                tokio::spawn(async move {
+                    let handler = handler.as_ref();
+                    futures::future::join_all(
+                        services
+                            .iter()
+                            .map(|(name, _)| Self::before_stop(handler, name)),
+                    )
+                    .await;
+                    let metrics = &metrics;
+                    let results = futures::future::join_all(services.into_iter().map(
+                        |(name, service)| async move {
+                            let result = service.client.cancel().await.map_err(|e| e.into());
+                            match &result {
+                                Ok(reason) => metrics.service_stopped(&name, reason),
+                                Err(e) => {
+                                    warn!("Failed to cleanly cancel service '{}': {}", name, e)
+                                }
+                            }
+                            (name, result)
+                        },
+                    ))
+                    .await;
+                    info!("Stopped {} services", results.len());
+                    let _ = reply.send(Ok(results));
+                });
+            }
+            ServiceMessage::ListAllTools {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to list tools",
+                    )));
+                    return Ok(());
+                };
+                Self::spawn_tools_listing(myself, service, service_name, ToolsReply::List(reply));
+            }
+            ServiceMessage::ListAllPrompts {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to list prompts",
+                    )));
+                    return Ok(());
+                };
+                Self::spawn_prompts_listing(
+                    myself,
+                    service,
+                    service_name,
+                    PromptsReply::List(reply),
+                );
+            }
+            ServiceMessage::PromptArguments {
+                service_name,
+                prompt_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get_mut(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to get arguments of prompt '{}'", prompt_name),
+                    )));
+                    return Ok(());
+                };
+                // Answer from the last listing when it includes the prompt.
+                let cached = service
+                    .prompts_snapshot
+                    .as_mut()
+                    .and_then(|c| prompt_arguments(c.get(), &prompt_name));
+                if let Some(arguments) = cached {
+                    let _ = reply.send(Ok(arguments));
+                    return Ok(());
+                }
+                Self::spawn_prompts_listing(
+                    myself,
+                    service,
+                    service_name,
+                    PromptsReply::Arguments { prompt_name, reply },
+                );
+            }
+            ServiceMessage::ListAllResources {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to list resources",
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request = async move { requester.list_resources().await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::ListAllResourceTemplates {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to list resource templates",
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request = async move { requester.list_resource_templates().await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::ReadResource {
+                service_name,
+                uri,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to read resource '{}'", uri),
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request = async move {
+                    requester
+                        .read_resource(ReadResourceRequestParam { uri })
+                        .await
+                };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::SubscribeResource {
+                service_name,
+                uri,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to subscribe to resource '{}'", uri),
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request =
+                    async move { requester.subscribe(SubscribeRequestParam { uri }).await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::UnsubscribeResource {
+                service_name,
+                uri,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to unsubscribe from resource '{}'", uri),
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request =
+                    async move { requester.unsubscribe(UnsubscribeRequestParam { uri }).await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::GetPrompt {
+                service_name,
+                prompt_request,
+                meta,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to get prompt '{:?}'", prompt_request),
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request = async move { requester.get_prompt(prompt_request, meta).await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::Complete {
+                service_name,
+                params,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to complete an argument",
+                    )));
+                    return Ok(());
+                };
+                let requester = service.requester();
+                let request = async move { requester.complete(params).await };
+                Self::spawn_request(myself, service_name, request, reply);
+            }
+            ServiceMessage::ToolsListed {
+                service_name,
+                result,
+                reply,
+            } => {
+                if let Some(service) = state.active_services.get_mut(&service_name) {
+                    service.health.observe(&result);
+                }
+                let tools = match result {
+                    Ok(tools) => tools,
+                    Err(e) => {
+                        reply.fail(e);
+                        return Ok(());
+                    }
+                };
+                match reply {
+                    ToolsReply::List(reply) => {
+                        if let Some(service) = state.active_services.get_mut(&service_name) {
+                            service.tools_baseline = Some(ToolFingerprints::of(&tools));
+                        }
+                        let _ = reply.send(Ok(tools));
+                    }
+                    ToolsReply::Diff(reply) => {
+                        let Some(service) = state.active_services.get_mut(&service_name) else {
+                            let _ = reply.send(Err(anyhow!(
+                                "Service '{}' stopped while diffing tools.",
+                                service_name
+                            )));
+                            return Ok(());
+                        };
+                        let previous = service.tools_baseline.take().unwrap_or_default();
+                        let diff = ToolsDiff::since(&previous, &tools);
+                        service.tools_baseline = Some(ToolFingerprints::of(&tools));
+                        let _ = reply.send(Ok(diff));
+                    }
+                }
+            }
+            ServiceMessage::PromptsListed {
+                service_name,
+                result,
+                reply,
+            } => {
+                if let Some(service) = state.active_services.get_mut(&service_name) {
+                    service.health.observe(&result);
+                    if let Ok(prompts) = &result {
+                        service.prompts_snapshot = Some(Cached::new(prompts.clone()));
+                    }
+                }
+                match reply {
+                    PromptsReply::List(reply) => {
+                        let _ = reply.send(result);
+                    }
+                    PromptsReply::Arguments { prompt_name, reply } => {
+                        let result = result.and_then(|prompts| {
+                            prompt_arguments(&prompts, &prompt_name).ok_or_else(|| {
+                                anyhow!(
+                                    "Prompt '{}' not found on service '{}'.",
+                                    prompt_name,
+                                    service_name
+                                )
+                            })
+                        });
+                        let _ = reply.send(result);
+                    }
+                }
+            }
+            ServiceMessage::CallTool {
+                service_name,
+                params,
+                timeout,
+                meta,
+                idempotency_key: Some(key),
+                cancel,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to call tool '{}'", params.name),
+                    )));
+                    return Ok(());
+                };
+                let in_flight = (service_name, key);
+                if let Some(call) = state.idempotent_calls.get(&in_flight)
+                    && let Some(hold) = call.join()
+                {
+                    if call.params != params {
+                        let _ = reply.send(Err(anyhow!(
+                            "Idempotency key '{}' is already in use on '{}' by a call with different arguments.",
+                            in_flight.1,
+                            in_flight.0
+                        )));
+                        return Ok(());
+                    }
+                    debug!(key = %in_flight.1, "Joining in-flight call with the same idempotency key");
+                    call.wait(hold, params.name.to_string(), cancel, reply);
+                    return Ok(());
+                }
+                if let Some(e) = service.open_circuit(&in_flight.0) {
+                    let _ = reply.send(Err(e));
+                    return Ok(());
+                }
+                // Callers who join the call share its place in the queue.
+                let slot = match service.calls.reserve(&in_flight.0) {
+                    Ok(slot) => slot,
+                    Err(e) => {
+                        let _ = reply.send(Err(e));
+                        return Ok(());
+                    }
+                };
+                // The call has its own token, so one caller giving up doesn't
+                // cancel it for the others who joined.
+                let shared_cancel = CancellationToken::new();
+                let call = service.requester().call_tool(
+                    params.clone(),
+                    timeout,
+                    meta,
+                    Some(in_flight.1.clone()),
+                    Some(shared_cancel.clone()),
+                );
+                let service_name = in_flight.0.clone();
+                let tool = params.name.to_string();
+                let (call, hold) = SharedCall::start(params, call, shared_cancel, move |outcome| {
+                    drop(slot);
+                    let _ = myself.cast(ServiceMessage::CallFinished {
+                        service_name,
+                        outcome,
+                    });
+                });
+                call.wait(hold, tool, cancel, reply);
+                state.idempotent_calls.insert(in_flight, call);
+            }
+            ServiceMessage::CallTool {
+                service_name,
+                params,
+                timeout,
+                meta,
+                idempotency_key: None,
+                cancel,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        format!("to call tool '{}'", params.name),
+                    )));
+                    return Ok(());
+                };
+                if let Some(e) = service.open_circuit(&service_name) {
+                    let _ = reply.send(Err(e));
+                    return Ok(());
+                }
+                let slot = match service.calls.reserve(&service_name) {
+                    Ok(slot) => slot,
+                    Err(e) => {
+                        let _ = reply.send(Err(e));
+                        return Ok(());
+                    }
+                };
+                // Calls run off the actor, so a slow call doesn't hold up
+                // messages for other calls or services.
+                let call = service
+                    .requester()
+                    .call_tool(params, timeout, meta, None, cancel);
+                tokio::spawn(async move {
+                    let result = call.await;
+                    drop(slot);
+                    let _ = myself.cast(ServiceMessage::CallFinished {
+                        service_name,
+                        outcome: HealthTracker::outcome(&result),
+                    });
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::DrainCalls {
+                service_name,
+                reply,
+            } => match state.active_services.get_mut(&service_name) {
+                Some(service) => {
+                    service.calls.draining = true;
+                    let drained = service.calls.drained();
+                    tokio::spawn(async move {
+                        drained.await;
+                        let _ = reply.send(Ok(true));
+                    });
+                }
+                None => {
+                    let _ = reply.send(Ok(false));
+                }
+            },
+            ServiceMessage::ResumeCalls {
+                service_name,
+                reply,
+            } => {
+                let result = match state.active_services.get_mut(&service_name) {
+                    Some(service) => {
+                        service.calls.draining = false;
+                        Ok(())
+                    }
+                    None => Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to resume calls",
+                    )),
+                };
+                let _ = reply.send(result);
+            }
+            ServiceMessage::CallFinished {
+                service_name,
+                outcome,
+            } => {
+                if let Some(service) = state.active_services.get_mut(&service_name) {
+                    service.health.record(outcome);
+                }
+                state.idempotent_calls.retain(|_, call| !call.is_over());
+            }
+            ServiceMessage::DiscoveryHistory { window, reply } => {
+                let _ = reply.send(Ok(state.history.query(window)));
+            }
+            ServiceMessage::ServiceReplicas { identity, reply } => {
+                let key = state.replica_identity_key.as_deref();
+                let replicas = state
+                    .active_services
+                    .values()
+                    .filter(|s| s.info.identity(key) == identity)
+                    .map(|s| s.info.clone())
+                    .collect();
+                let _ = reply.send(Ok(replicas));
+            }
+            ServiceMessage::ActiveServiceInfos { reply } => {
+                let infos = state
+                    .active_services
+                    .values()
+                    .map(|s| s.info.clone())
+                    .collect();
+                let _ = reply.send(Ok(infos));
+            }
+            ServiceMessage::GetServiceInfo {
+                service_name,
+                reply,
+            } => {
+                let result = state
+                    .active_services
+                    .get(&service_name)
+                    .map(|s| s.info.clone())
+                    .ok_or_else(|| ZeroError::service_not_found(&service_name, "to get info"));
+                let _ = reply.send(result);
+            }
+            ServiceMessage::RenderTemplates {
+                service_name,
+                templates,
+                inputs,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to render templates",
+                    )));
+                    return Ok(());
+                };
+                let Some(handler) = state.handler.get().cloned() else {
+                    let _ = reply.send(Err(anyhow!("No handler to request input from.")));
+                    return Ok(());
+                };
+                let info = service.info.clone();
+                // Input may take a person a while, so it's gathered off the actor.
+                tokio::spawn(async move {
+                    let result = async {
+                        let mut ctx = template_context(&info);
+                        if let Some(inputs) = &inputs {
+                            seed_context(&mut ctx, inputs);
+                        }
+                        let mut rendered = HashMap::with_capacity(templates.len());
+                        for (key, tpl) in &templates {
+                            let value =
+                                render_template_with_input(tpl, &mut ctx, &info, &handler).await?;
+                            rendered.insert(key.clone(), value);
+                        }
+                        Ok((rendered, context_inputs(&ctx)))
+                    }
+                    .await;
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::ListActiveServices { reply } => {
+                // The published active set is already sorted.
+                let _ = reply.send(Ok(state.active_set.borrow().clone()));
+            }
+            ServiceMessage::ExportState { reply } => {
+                let services = state
+                    .active_services
+                    .values()
+                    .map(|s| {
+                        let mut config = s.config.clone();
+                        // Proxies may carry credentials; restoring takes them from the config.
+                        if let McpConfig::Sse { proxy, .. }
+                        | McpConfig::StreamableHttp { proxy, .. } = &mut config
+                        {
+                            *proxy = None;
+                        }
+                        SavedService {
+                            service: s.info.clone(),
+                            config,
+                        }
+                    })
+                    .collect();
+                let _ = reply.send(Ok(ManagerState {
+                    services,
+                    ..Default::default()
+                }));
+            }
+            ServiceMessage::HealthSummary { reply } => {
+                let summary = state
+                    .active_services
+                    .iter()
+                    .map(|(name, service)| (name.clone(), service.health.status()))
+                    .collect();
+                let _ = reply.send(Ok(summary));
+            }
+            ServiceMessage::SessionId {
+                service_name,
+                reply,
+            } => {
+                let result = if let Some(service) = state.active_services.get(&service_name) {
+                    Ok(service.session_id.clone())
+                } else {
+                    Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to get session ID",
+                    ))
+                };
+                let _ = reply.send(result);
+            }
+            ServiceMessage::ServiceHealth {
+                service_name,
+                reply,
+            } => {
+                let result = if let Some(service) = state.active_services.get(&service_name) {
+                    Ok(service.health.status())
+                } else {
+                    Err(ZeroError::service_not_found(&service_name, "to get health"))
+                };
+                let _ = reply.send(result);
+            }
+            ServiceMessage::ToolsDiff {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to diff tools",
+                    )));
+                    return Ok(());
+                };
+                Self::spawn_tools_listing(myself, service, service_name, ToolsReply::Diff(reply));
+            }
+            ServiceMessage::RefreshDiscovery {
+                service_type,
+                reply,
+            } => {
+                let result = match service_type {
+                    Some(ty) if !state.service_types.contains(&ty) => Err(anyhow!(
+                        "Service type '{}' is not configured for discovery.",
+                        ty
+                    )),
+                    Some(ty) => state
+                        .discovery
+                        .send(DiscoveryCommand::Refresh(ty))
+                        .map_err(|_| anyhow!("Discovery is no longer running.")),
+                    None => state
+                        .service_types
+                        .iter()
+                        .try_for_each(|ty| {
+                            state.discovery.send(DiscoveryCommand::Refresh(ty.clone()))
+                        })
+                        .map_err(|_| anyhow!("Discovery is no longer running.")),
+                };
+                let _ = reply.send(result);
+            }
+            ServiceMessage::StreamingPeer {
+                service_name,
+                reply,
+            } => {
+                let result = if let Some(service) = state.active_services.get(&service_name) {
+                    let admitted = match service.open_circuit(&service_name) {
+                        Some(e) => Err(e),
+                        None => service.calls.reserve(&service_name),
+                    };
+                    admitted.map(|slot| {
+                        let buffer = Arc::new(FragmentBuffer::new(state.fragment_budget.clone()));
+                        state.fragment_buffers.push(Arc::downgrade(&buffer));
+                        StreamingCall {
+                            peer: service.client.peer().clone(),
+                            progress: service.progress.clone(),
+                            meta: service.requester().request_meta(None),
+                            timeout: service
+                                .config
+                                .options()
+                                .tool_call_timeout_ms
+                                .map(Duration::from_millis),
+                            limit: service.limit.clone(),
+                            slot,
+                            buffer,
+                        }
+                    })
+                } else {
+                    Err(ZeroError::service_not_found(
+                        &service_name,
+                        "to stream a tool call",
+                    ))
+                };
+                let _ = reply.send(result);
+            }
+            ServiceMessage::SetLaunchOverride {
+                fullname,
+                config,
+                reply,
+            } => {
+                match config {
+                    Some(config) => {
+                        info!("Setting launch override for '{}'", fullname);
+                        state.launch_overrides.insert(fullname, *config);
+                    }
+                    None => {
+                        if state.launch_overrides.remove(&fullname).is_some() {
+                            info!("Cleared launch override for '{}'", fullname);
+                        }
+                    }
+                }
+                let _ = reply.send(Ok(()));
+            }
+            ServiceMessage::LaunchOverride { fullname, reply } => {
+                let _ = reply.send(Ok(state.launch_overrides.get(&fullname).cloned()));
+            }
+            ServiceMessage::SetRoots {
+                service_name,
+                roots,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get_mut(&service_name) else {
+                    let result = Err(ZeroError::service_not_found(&service_name, "to set roots"));
+                    let _ = reply.send(result);
+                    return Ok(());
+                };
+                *service
+                    .roots
+                    .write()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = roots;
+                // Notify off the actor, so a stalled transport doesn't block it.
+                let peer = service.client.peer().clone();
+                tokio::spawn(async move {
+                    let result = peer.notify_roots_list_changed().await.map_err(|e| e.into());
+                    let _ = myself.cast(ServiceMessage::CallFinished {
+                        service_name,
+                        outcome: HealthTracker::outcome(&result),
+                    });
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::StartupTimings {
+                service_name,
+                reply,
+            } => {
+                let result = state
+                    .active_services
+                    .get(&service_name)
+                    .map(|s| s.startup)
+                    .ok_or_else(|| {
+                        ZeroError::service_not_found(&service_name, "to get startup timings")
+                    });
+                let _ = reply.send(result);
+            }
+            ServiceMessage::StartupHistogram { reply } => {
+                let _ = reply.send(Ok(state.startup_histogram.clone()));
+            }
+            ServiceMessage::MemoryStats { reply } => {
+                let _ = reply.send(Ok(state.memory_stats()));
+            }
+            ServiceMessage::ServicesWithCapability { capability, reply } => {
+                let mut names: Vec<String> = state
+                    .active_services
+                    .iter()
+                    .filter(|(_, service)| service.supports(capability))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                names.sort();
+                let _ = reply.send(Ok(names));
+            }
+            ServiceMessage::TapTraffic {
+                service_name,
+                reply,
+            } => {
+                let result = state
+                    .active_services
+                    .get(&service_name)
+                    .map(|service| service.traffic.subscribe())
+                    .ok_or_else(|| ZeroError::service_not_found(&service_name, "to tap"));
+                let _ = reply.send(result);
+            }
+            ServiceMessage::StoppedAt {
+                service_name,
+                reply,
+            } => {
+                let stopped = state.stopped_at.get(&service_name).map(|(at, _)| *at);
+                let _ = reply.send(Ok(stopped));
+            }
+            ServiceMessage::Ping {
+                service_name,
+                timeout,
+                reply,
+            } => {
+                let Some(service) = state.active_services.get(&service_name) else {
+                    let _ = reply.send(Err(ZeroError::service_not_found(&service_name, "to ping")));
+                    return Ok(());
+                };
+                // Wait off the actor, so an unresponsive service doesn't stall it.
+                let ping = service.requester().ping(timeout);
+                tokio::spawn(async move {
+                    let result = ping.await;
+                    let _ = myself.cast(ServiceMessage::CallFinished {
+                        service_name,
+                        outcome: HealthTracker::outcome(&result),
+                    });
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::RemoveExited {
+                service_name,
+                pid,
+                reply,
+            } => {
+                let exited = state
+                    .active_services
+                    .get(&service_name)
+                    .is_some_and(|s| s.pid == pid && s.client.peer().is_transport_closed());
+                if exited && let Some(service) = state.active_services.remove(&service_name) {
+                    state.history.record(LifecycleEvent::Removed);
+                    state.record_stop(&service_name, &service);
+                    state.publish_active_set();
+                    state
+                        .metrics
+                        .service_stopped(&service_name, &QuitReason::Closed);
+                }
+                let _ = reply.send(Ok(exited));
+            }
+            ServiceMessage::LaunchSynthetic {
+                service,
+                config,
+                reply,
+            } => {
+                let result = state
+                    .discovery
+                    .send(DiscoveryCommand::Launch { service, config })
+                    .map_err(|_| anyhow!("Discovery is no longer running."));
+                let _ = reply.send(result);
+            }
+            ServiceMessage::RestartService {
+                service_name,
+                reply,
+            } => {
+                let Some(service) = state.take_service(&service_name) else {
+                    let _ = reply.send(Err(anyhow!(
+                        "Service '{}' is not running, so it can't be restarted.",
+                        service_name
+                    )));
+                    return Ok(());
+                };
+                info!("Restarting service '{}'", service_name);
+                let handler = state.handler.get().cloned();
+                let metrics = state.metrics.clone();
+                let discovery = state.discovery.clone();
+                // As for stops, the hook, cancel and relaunch run off the actor.
+                tokio::spawn(async move {
+                    Self::before_stop(handler.as_ref(), &service_name).await;
+                    let ActiveService {
+                        client,
+                        info,
+                        config,
+                        ..
+                    } = service;
+                    match client.cancel().await {
+                        Ok(reason) => metrics.service_stopped(&service_name, &reason),
+                        Err(e) => {
+                            warn!("Failed to cleanly cancel service '{}': {}", service_name, e)
+                        }
+                    }
+                    let (tx, rx) = oneshot::channel();
+                    let command = DiscoveryCommand::Restart {
+                        service: Box::new(info),
+                        config: Box::new(config),
+                        reply: tx,
+                    };
+                    let result = match discovery.send(command) {
+                        Ok(()) => rx.await.unwrap_or_else(|_| {
+                            Err(anyhow!("Discovery stopped during the restart."))
+                        }),
+                        Err(_) => Err(anyhow!("Discovery is no longer running.")),
+                    };
+                    let _ = reply.send(result);
+                });
+            }
+            ServiceMessage::ClaimLaunch {
+                service_name,
+                reply,
+            } => {
+                let claimed = (!state.shutting_down
+                    && !state.active_services.contains_key(&service_name)
+                    && !state.launching.contains_key(&service_name))
+                .then(|| {
+                    let withdrawn = CancellationToken::new();
+                    state.launching.insert(service_name, withdrawn.clone());
+                    withdrawn
+                });
+                let _ = reply.send(Ok(claimed));
+            }
+            ServiceMessage::ReleaseLaunch { service_name } => {
+                state.launching.remove(&service_name);
+                if let Some((service, config)) = state.superseded.remove(&service_name) {
+                    info!("Relaunching '{}' with its latest resolution", service_name);
+                    let _ = state.discovery.send(DiscoveryCommand::Relaunch {
+                        service: Box::new(service),
+                        config: Box::new(config),
+                    });
+                }
+            }
+            ServiceMessage::SupersedeLaunch {
+                service,
+                config,
+                reply,
+            } => {
+                let launching = state.launching.contains_key(&service.fullname);
+                if launching {
+                    state
+                        .superseded
+                        .insert(service.fullname.clone(), (*service, *config));
+                }
+                let _ = reply.send(Ok(launching));
+            }
+            ServiceMessage::DueHealthChecks { reply } => {
+                let now = Instant::now();
+                let due = state
+                    .active_services
+                    .iter_mut()
+                    .filter_map(|(name, service)| {
+                        let interval = service.config.options().health_check_interval_ms?;
+                        let elapsed = now.duration_since(service.last_health_check);
+                        (elapsed >= Duration::from_millis(interval)).then(|| {
+                            service.last_health_check = now;
+                            name.clone()
+                        })
+                    })
+                    .collect();
+                let _ = reply.send(Ok(due));
+            }
+            // The budget is enforced after every message, below.
+            ServiceMessage::EnforceMemoryBudget => {}
+            ServiceMessage::IdleServices { reply } => {
+                let idle = state
+                    .active_services
+                    .iter()
+                    .filter(|(_, service)| {
+                        service.config.options().idle_timeout_ms.is_some_and(|ms| {
+                            service.last_active.elapsed() >= Duration::from_millis(ms)
+                        })
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                let _ = reply.send(Ok(idle));
+            }
+        }
+        state.enforce_memory_budget();
+        Ok(())
+    }
+}
+
+/// How often a new service is checked for disconnection during its minimum stable uptime.
+const STABILITY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a service with a restart policy is checked for an unexpected exit.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How many times in a row browsing a service type may fail before `run` gives up.
+const MAX_BROWSE_FAILURES: u32 = 5;
+/// Delay before the first attempt to re-create a failed browse; doubles each time.
+const BROWSE_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Tags a browse stream's events with their service type and marks its end
+/// with a final `None`, so the discovery loop can notice and recover. A
+/// browse ended by `stop_browse` ends quietly instead, as it's being replaced.
+fn browse_stream(
+    service_type: String,
+    receiver: mdns_sd::Receiver<ServiceEvent>,
+) -> BoxStream<'static, (String, Option<ServiceEvent>)> {
+    receiver
+        .into_stream()
+        .map(Some)
+        .chain(futures::stream::once(async { None }))
+        .take_while(|event| {
+            futures::future::ready(!matches!(event, Some(ServiceEvent::SearchStopped(_))))
+        })
+        .map(move |event| (service_type.clone(), event))
+        .boxed()
+}
+
+/// Builds the context templates are rendered against: the service's fields,
+/// plus its `primary_address`, under `service`.
+fn template_context(service: &DiscoveredService) -> Value {
+    let mut ctx = json!({ "service": service });
+    ctx["service"]["primary_address"] = json!(service.primary_address());
+    ctx
+}
+
+/// Fails with [`ZeroError::LaunchTimeout`] if a launch step outlasts `timeout`.
+async fn within_launch_timeout<T, E: Into<anyhow::Error>>(
+    timeout: Duration,
+    step: impl Future<Output = std::result::Result<T, E>>,
+) -> Result<T> {
+    match tokio::time::timeout(timeout, step).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(_) => Err(ZeroError::LaunchTimeout { timeout }.into()),
+    }
+}
+
+/// Records a service's failure and passes it to `on_service_error`, shielding
+/// the caller from panics.
+pub(crate) async fn report_service_error(
+    client: &ZeroClient,
+    app_handler: &Arc<dyn ZeroHandler>,
+    service_name: &str,
+    error: &anyhow::Error,
+) {
+    client.metrics.service_failed(service_name, error);
+    if let Err(panic) = catch_panic(app_handler.on_service_error(service_name, error)).await {
+        error!(panic = %panic, "on_service_error panicked for '{}'", service_name);
+    }
+}
+
+/// Reports a panic raised by a handler callback and applies the configured policy.
+pub(crate) async fn handle_handler_panic(
+    client: &ZeroClient,
+    app_handler: &Arc<dyn ZeroHandler>,
+    policy: HandlerPanicPolicy,
+    service_name: &str,
+    message: &str,
+) {
+    error!(
+        panic = %message,
+        policy = ?policy,
+        "Handler callback panicked for service '{}'", service_name
+    );
+    if let Err(nested) = catch_panic(app_handler.on_handler_panic(service_name, message)).await {
+        error!(panic = %nested, "on_handler_panic itself panicked");
+    }
+    if policy == HandlerPanicPolicy::Stop {
+        match client.stop_service(service_name).await {
+            Ok(reason) => info!(reason = ?reason, "Stopped service after handler panic"),
+            Err(e) => debug!(error = %e, "Service already gone after handler panic"),
+        }
+    }
+}
+
+/// Renders a Handlebars template, prompting for user input if variables are missing.
+#[instrument(name = "render_template", skip(ctx, service, app_handler), fields(service.name = %service.fullname, template = %tpl))]
+pub(crate) async fn render_template_with_input(
+    tpl: &str,
+    ctx: &mut serde_json::Value,
+    service: &DiscoveredService,
+    app_handler: &Arc<dyn ZeroHandler>,
+) -> Result<String> {
+    let mut reg = Handlebars::new();
+    reg.set_strict_mode(true); // Ensures we fail on missing variables.
+
+    loop {
+        match reg.render_template(tpl, ctx) {
+            Ok(rendered) => return Ok(rendered),
+            Err(e) => match e.reason() {
+                RenderErrorReason::MissingVariable(Some(var)) => {
+                    info!(variable = %var, "Template requires input");
+                    let val = app_handler
+                        .request_input_for(service, var)
+                        .await
+                        .with_context(|| format!("Failed to get user input for key '{}'", var))?;
+
+                    if let Some(obj) = ctx.as_object_mut() {
+                        obj.insert(var.clone(), json!(val));
+                    }
+                }
+                _ => return Err(e).context("Failed to render Handlebars template"),
+            },
+        }
+    }
+}
+
+/// A callback that rewrites a discovered service before it is launched.
+pub type ServiceTransform = Arc<dyn Fn(&mut DiscoveredService) + Send + Sync>;
+
+/// Optional extension points for the manager, configured via `ZeroMcpBuilder`.
+#[derive(Default, Clone)]
+pub(crate) struct ManagerHooks {
+    pub(crate) transform: Option<ServiceTransform>,
+    pub(crate) sampling: Option<Arc<dyn SamplingHandler>>,
+    pub(crate) metrics: Option<Arc<dyn MetricsSink>>,
+}
+
+pub struct ServiceManager<M: MdnsBrowser> {
+    client: ZeroClient,
+    config: ZeroConfig,
+    mdns: M,
+    app_handler: Arc<dyn ZeroHandler>,
+    hooks: ManagerHooks,
+    /// Refreshes requested through `ZeroClient::refresh_discovery`, and probes
+    /// from `ZeroMcp::self_test`.
+    discovery_rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<DiscoveryCommand>>,
+    /// Lets launches running off the discovery loop report back to it.
+    discovery_tx: mpsc::UnboundedSender<DiscoveryCommand>,
+}
+
+impl<M: MdnsBrowser> fmt::Debug for ServiceManager<M> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ServiceManager")
-            .field("actor", &self.actor)
+            .field("client", &self.client)
             .field("config", &self.config)
             .field("mdns", &"<ServiceDaemon>")
             .field("app_handler", &"<dyn ZeroHandler>")
+            .field("transform", &self.hooks.transform.as_ref().map(|_| "<fn>"))
+            .field(
+                "sampling",
+                &self
+                    .hooks
+                    .sampling
+                    .as_ref()
+                    .map(|_| "<dyn SamplingHandler>"),
+            )
             .finish()
     }
 }
@@ -274,46 +3078,289 @@ impl<M: MdnsBrowser> fmt::Debug for ServiceManager<M> {
 impl<M: MdnsBrowser + 'static> ServiceManager<M> {
     #[instrument(name = "service_manager_run", skip(self))]
     pub async fn run(&self) -> Result<()> {
-        let mcp_map: HashMap<String, McpConfig> = self
+        let mcp_map: HashMap<String, &ServiceMcpMapping> = self
             .config
             .service_mappings
             .iter()
-            .map(|m| (m.zeroconf_service.clone(), m.mcp.clone()))
+            .flat_map(|m| m.browse_types().map(move |ty| (ty, m)))
             .collect();
 
         let mut streams = Vec::new();
         for service_type in mcp_map.keys() {
             let receiver = self.mdns.browse(service_type)?;
-            streams.push(receiver.into_stream());
+            streams.push(browse_stream(service_type.clone(), receiver));
             info!("Browsing for Zeroconf service type '{}'...", service_type);
         }
 
+        let mut static_names = Vec::with_capacity(self.config.static_services.len());
+        for static_service in &self.config.static_services {
+            let service = DiscoveredService::new_static(&static_service.service_name);
+            info!(service.fullname = %service.fullname, "Connecting static service");
+            static_names.push(service.fullname.clone());
+            self.client.metrics.service_discovered(&service);
+            self.handle_service_appeared(service, static_service.mcp.clone(), false);
+        }
+
         let mut merged_stream = futures::stream::select_all(streams);
         info!("Service discovery started. Awaiting events.");
 
-        while let Some(event) = merged_stream.next().await {
+        // The last resolution of each discovered service and its launch config,
+        // to spot re-resolutions that don't change anything relevant and to
+        // pick standby replicas to fail over to.
+        let mut resolved: HashMap<String, (DiscoveredService, McpConfig)> = HashMap::new();
+
+        let mut discovery_rx = self.discovery_rx.lock().await;
+        // Browse streams only end if the daemon fails, so ended streams are
+        // re-created with backoff. `browse_failures` counts consecutive failures
+        // per service type and is reset once browsing it succeeds again.
+        let mut browse_failures: HashMap<String, u32> = HashMap::new();
+        let mut browse_retries: FuturesUnordered<BoxFuture<'static, String>> =
+            FuturesUnordered::new();
+        // Removals wait out `debounce_ms` in case the service resolves again.
+        // Each is tagged with a generation, so a removal cancelled by a
+        // re-resolution and then reported again isn't applied early.
+        let debounce = Duration::from_millis(self.config.debounce_ms);
+        let mut pending_removals: HashMap<String, u64> = HashMap::new();
+        let mut removal_generation = 0u64;
+        let mut removals: FuturesUnordered<BoxFuture<'static, (String, u64)>> =
+            FuturesUnordered::new();
+        loop {
+            if merged_stream.is_empty() && browse_retries.is_empty() {
+                break;
+            }
+            let mut browse_failed = None;
+            let event = tokio::select! {
+                Some((service_type, event)) = merged_stream.next() => match event {
+                    Some(event) => Some(event),
+                    None => {
+                        browse_failed = Some((service_type, anyhow!("browse stream ended")));
+                        None
+                    }
+                },
+                Some((service_name, generation)) = removals.next() => {
+                    if pending_removals.get(&service_name) == Some(&generation) {
+                        pending_removals.remove(&service_name);
+                        self.remove_resolved(&mut resolved, &service_name);
+                    }
+                    None
+                }
+                Some(service_type) = browse_retries.next() => {
+                    match self.mdns.browse(&service_type) {
+                        Ok(receiver) => {
+                            info!("Resumed browsing for '{}'", service_type);
+                            browse_failures.remove(&service_type);
+                            merged_stream.push(browse_stream(service_type, receiver));
+                            None
+                        }
+                        Err(e) => {
+                            browse_failed = Some((service_type, e));
+                            None
+                        }
+                    }
+                }
+                Some(command) = discovery_rx.recv() => {
+                    match command {
+                        DiscoveryCommand::Refresh(service_type) => {
+                            // The old browse is stopped first, or the daemon would
+                            // keep re-sending its query. Browsing again sends a
+                            // fresh query.
+                            info!("Refreshing discovery for '{}'", service_type);
+                            if let Err(e) = self.mdns.stop_browse(&service_type) {
+                                debug!(error = %e, "Failed to stop the previous browse for '{}'", service_type);
+                            }
+                            match self.mdns.browse(&service_type) {
+                                Ok(receiver) => merged_stream.push(browse_stream(service_type, receiver)),
+                                Err(e) => warn!(error = %e, "Failed to refresh discovery for '{}'", service_type),
+                            }
+                        }
+                        DiscoveryCommand::Launch { service, config } => {
+                            info!(service.fullname = %service.fullname, "Launching synthetic service");
+                            self.handle_service_appeared(*service, *config, false);
+                        }
+                        DiscoveryCommand::Restore(saved) => {
+                            let SavedService { service, config } = *saved;
+                            let span = tracing::info_span!("service_restored", service.fullname = %service.fullname);
+                            let _enter = span.enter();
+                            // The config connects static services anyway.
+                            if static_names.contains(&service.fullname) {
+                                continue;
+                            }
+                            let mapping = match self.restore_mapping(&service) {
+                                Ok(mapping) => mapping,
+                                Err(reason) => {
+                                    warn!("Not restoring saved service: {}", reason);
+                                    continue;
+                                }
+                            };
+                            info!("Restoring saved service");
+                            let config = config.with_default_proxy(mapping.mcp.proxy());
+                            resolved.insert(service.fullname.clone(), (service.clone(), config.clone()));
+                            self.handle_service_appeared(service, config, false);
+                        }
+                        DiscoveryCommand::Restart { service, config, reply } => {
+                            self.handle_service_restart(*service, *config, reply);
+                        }
+                        DiscoveryCommand::Relaunch { service, config } => {
+                            self.handle_service_appeared(*service, *config, true);
+                        }
+                        DiscoveryCommand::Forget(service) => {
+                            let unchanged = resolved.get(&service.fullname).is_some_and(|(last, _)| {
+                                !last.differs_from(&service, self.config.change_policy)
+                            });
+                            if unchanged {
+                                resolved.remove(&service.fullname);
+                            }
+                        }
+                        DiscoveryCommand::Probe(reply) => {
+                            let (retrying, browsing) = mcp_map
+                                .keys()
+                                .cloned()
+                                .partition(|ty| browse_failures.contains_key(ty));
+                            let status = self.mdns.status();
+                            // The daemon answers on its own thread, so the wait
+                            // happens off the loop, within the self-test's timeout.
+                            tokio::spawn(async move {
+                                let daemon_running = match status {
+                                    Ok(Some(status)) => matches!(
+                                        tokio::time::timeout(SELF_TEST_TIMEOUT / 2, status.recv_async()).await,
+                                        Ok(Ok(DaemonStatus::Running))
+                                    ),
+                                    Ok(None) => true,
+                                    Err(e) => {
+                                        warn!(error = %e, "Failed to ask the mDNS daemon for its status");
+                                        false
+                                    }
+                                };
+                                let _ = reply.send(DiscoveryStatus { daemon_running, browsing, retrying });
+                            });
+                        }
+                        DiscoveryCommand::Shutdown => {
+                            info!("Service discovery stopped.");
+                            break;
+                        }
+                    }
+                    None
+                }
+                else => break,
+            };
+            if let Some((service_type, error)) = browse_failed {
+                let failures = browse_failures.entry(service_type.clone()).or_default();
+                *failures += 1;
+                if *failures > MAX_BROWSE_FAILURES {
+                    return Err(error.context(format!(
+                        "mDNS browsing for '{}' failed {} times in a row",
+                        service_type, failures
+                    )));
+                }
+                let delay = BROWSE_RETRY_BASE_DELAY * 2u32.pow(*failures - 1);
+                warn!(error = %error, attempt = *failures, "Discovery for '{}' stopped; retrying in {:?}", service_type, delay);
+                browse_retries.push(Box::pin(async move {
+                    tokio::time::sleep(delay).await;
+                    service_type
+                }));
+                continue;
+            }
+            let Some(event) = event else {
+                continue;
+            };
             match event {
                 ServiceEvent::ServiceResolved(info) => {
-                    let service_fullname = info.get_fullname().to_string();
+                    let service_fullname = match validate_fullname(info.get_fullname()) {
+                        Ok(name) => name,
+                        Err(e) => {
+                            warn!(error = %e, "Ignoring service with invalid fullname");
+                            continue;
+                        }
+                    };
                     let service_type = info.get_type().to_string();
                     let span = tracing::info_span!("service_resolved", service.fullname = %service_fullname, service.type = %service_type);
                     let _enter = span.enter();
 
                     info!("Resolved service");
-                    if let Some(mcp_config) = mcp_map.get(info.get_type()) {
-                        let service = DiscoveredService::from(&info);
-                        self.handle_service_appeared(service, mcp_config.clone());
+                    // A mapping for the service's subtype takes precedence over
+                    // one for its plain type.
+                    let mapping = info
+                        .get_subtype()
+                        .as_ref()
+                        .and_then(|subtype| mcp_map.get(subtype))
+                        .or_else(|| mcp_map.get(info.get_type()));
+                    if let Some(mapping) = mapping {
+                        let mut service = DiscoveredService::from(&info);
+                        // Checked before and after `transform`, so it can't
+                        // point the service at a host outside the allowlist.
+                        if !self.config.host_allowed(&service.hostname) {
+                            debug!(hostname = %service.hostname, "Ignoring service on a host outside the allowlist");
+                            continue;
+                        }
+                        if let Some(transform) = &self.hooks.transform {
+                            transform(&mut service);
+                            if service.fullname != service_fullname {
+                                warn!(
+                                    "Transform changed the service fullname; restoring '{}'",
+                                    service_fullname
+                                );
+                                service.fullname = service_fullname.clone();
+                            }
+                        }
+                        service.sort_addresses(self.config.address_family);
+                        if !self.config.host_allowed(&service.hostname) {
+                            warn!(hostname = %service.hostname, "Ignoring service the transform moved to a host outside the allowlist");
+                            continue;
+                        }
+                        if let Some(missing) = mapping.missing_capability(&service) {
+                            debug!(capability = %missing, "Ignoring service missing a required capability");
+                            continue;
+                        }
+                        if let Some(key) = mapping.unmatched_filter(&service) {
+                            info!(property = %key, "Ignoring service whose TXT properties don't match the filter");
+                            continue;
+                        }
+                        self.client.metrics.service_discovered(&service);
+                        if pending_removals.remove(&service_fullname).is_some() {
+                            info!(
+                                "Service resolved again before its removal took effect; keeping it"
+                            );
+                        }
+                        if let Some((previous, _)) = resolved.get(&service_fullname)
+                            && !service.differs_from(previous, self.config.change_policy)
+                        {
+                            debug!("Re-resolution is unchanged under the change policy; ignoring");
+                            continue;
+                        }
+                        let changed = resolved
+                            .insert(service_fullname, (service.clone(), mapping.mcp.clone()))
+                            .is_some();
+                        self.handle_service_appeared(service, mapping.mcp.clone(), changed);
                     } else {
                         warn!("No mapping found in config for service type");
                     }
                 }
-                ServiceEvent::ServiceRemoved(service_name, reason) => {
+                ServiceEvent::ServiceRemoved(service_type, service_name) => {
+                    let service_name = normalize_fullname(&service_name);
+                    if static_names.contains(&service_name) {
+                        debug!(service.fullname = %service_name, "Ignoring removal of static service");
+                        continue;
+                    }
                     let span =
                         tracing::info_span!("service_removed", service.fullname = %service_name);
                     let _enter = span.enter();
 
-                    info!("Service '{}' removed {}", service_name, reason);
-                    self.handle_service_disappeared(&service_name);
+                    info!(
+                        "Service '{}' of type '{}' removed",
+                        service_name, service_type
+                    );
+                    if debounce.is_zero() {
+                        self.remove_resolved(&mut resolved, &service_name);
+                        continue;
+                    }
+                    debug!("Waiting {:?} before stopping the removed service", debounce);
+                    removal_generation += 1;
+                    pending_removals.insert(service_name.clone(), removal_generation);
+                    let generation = removal_generation;
+                    removals.push(Box::pin(async move {
+                        tokio::time::sleep(debounce).await;
+                        (service_name, generation)
+                    }));
                 }
                 _ => {}
             }
@@ -321,165 +3368,1227 @@ impl<M: MdnsBrowser + 'static> ServiceManager<M> {
         Ok(())
     }
 
-    /// Renders a Handlebars template, prompting for user input if variables are missing.
-    #[instrument(name = "render_template", skip(ctx, app_handler), fields(service.name = %service_name, template = %tpl))]
-    async fn render_template_with_input(
-        tpl: &str,
-        ctx: &mut serde_json::Value,
-        service_name: &str,
-        app_handler: &Arc<dyn ZeroHandler>,
-    ) -> Result<String> {
-        let mut reg = Handlebars::new();
-        reg.set_strict_mode(true); // Ensures we fail on missing variables.
+    /// Returns the mapping a saved service is restored under, or why it isn't
+    /// restored when discovery wouldn't admit it under the current config.
+    fn restore_mapping(
+        &self,
+        service: &DiscoveredService,
+    ) -> std::result::Result<&ServiceMcpMapping, String> {
+        validate_fullname(&service.fullname).map_err(|e| e.to_string())?;
+        if !self.config.host_allowed(&service.hostname) {
+            return Err(format!(
+                "host '{}' is outside the allowlist",
+                service.hostname
+            ));
+        }
+        let mut mappings = self
+            .config
+            .service_mappings
+            .iter()
+            .filter(|mapping| mapping.covers(service))
+            .peekable();
+        if mappings.peek().is_none() {
+            return Err("no mapping covers its service type".to_string());
+        }
+        mappings
+            .find(|mapping| {
+                mapping.missing_capability(service).is_none()
+                    && mapping.unmatched_filter(service).is_none()
+            })
+            .ok_or_else(|| "it lacks a required capability or doesn't match the filter".to_string())
+    }
 
-        loop {
-            match reg.render_template(tpl, ctx) {
-                Ok(rendered) => return Ok(rendered),
-                Err(e) => match &*e.reason() {
-                    RenderErrorReason::MissingVariable(Some(var)) => {
-                        info!(variable = %var, "Template requires input");
-                        let val = app_handler
-                            .request_input(service_name, var)
-                            .await
-                            .with_context(|| {
-                                format!("Failed to get user input for key '{}'", var)
-                            })?;
+    /// Forgets a removed service's last resolution and stops it, failing over
+    /// to a standby replica if there is one.
+    fn remove_resolved(
+        &self,
+        resolved: &mut HashMap<String, (DiscoveredService, McpConfig)>,
+        service_name: &str,
+    ) {
+        let failover = resolved
+            .remove(service_name)
+            .and_then(|(removed, _)| self.failover_candidate(resolved, &removed));
+        self.handle_service_disappeared(service_name, failover);
+    }
 
-                        if let Some(obj) = ctx.as_object_mut() {
-                            obj.insert(var.clone(), json!(val));
-                        }
+    /// Renders every string within a JSON value as a template, prompting for input as needed.
+    fn render_json_with_input<'a>(
+        value: &'a Value,
+        ctx: &'a mut Value,
+        service: &'a DiscoveredService,
+        app_handler: &'a Arc<dyn ZeroHandler>,
+    ) -> BoxFuture<'a, Result<Value>> {
+        Box::pin(async move {
+            match value {
+                Value::String(tpl) => Ok(Value::String(
+                    render_template_with_input(tpl, ctx, service, app_handler).await?,
+                )),
+                Value::Array(items) => {
+                    let mut rendered = Vec::with_capacity(items.len());
+                    for item in items {
+                        rendered.push(
+                            Self::render_json_with_input(item, ctx, service, app_handler).await?,
+                        );
                     }
-                    _ => return Err(e).context("Failed to render Handlebars template"),
-                },
+                    Ok(Value::Array(rendered))
+                }
+                Value::Object(map) => {
+                    let mut rendered = serde_json::Map::with_capacity(map.len());
+                    for (k, v) in map {
+                        let v = Self::render_json_with_input(v, ctx, service, app_handler).await?;
+                        rendered.insert(k.clone(), v);
+                    }
+                    Ok(Value::Object(rendered))
+                }
+                other => Ok(other.clone()),
+            }
+        })
+    }
+
+    /// Builds the MCP client handler, rendering the configured `init_options`.
+    async fn build_client_handler(
+        cfg: &McpConfig,
+        ctx: &mut Value,
+        service: &DiscoveredService,
+        app_handler: &Arc<dyn ZeroHandler>,
+        hooks: &ManagerHooks,
+    ) -> Result<ZeroClientHandler> {
+        let options = match &cfg.options().init_options {
+            Some(tpl) => {
+                let rendered = Self::render_json_with_input(tpl, ctx, service, app_handler).await?;
+                Some(
+                    serde_json::from_value::<InitOptions>(rendered)
+                        .context("Invalid init_options")?,
+                )
             }
+            None => None,
+        };
+        Ok(ZeroClientHandler::new(
+            &service.fullname,
+            options,
+            hooks.sampling.clone(),
+            app_handler.clone(),
+        ))
+    }
+
+    /// Passes the redacted launch parameters to `on_launch`, failing if it vetoes them.
+    async fn notify_launch(
+        service_name: &str,
+        launch: RenderedLaunch,
+        redact_keys: &[String],
+        app_handler: &Arc<dyn ZeroHandler>,
+    ) -> Result<()> {
+        match catch_panic(app_handler.on_launch(service_name, launch.redacted(redact_keys))).await {
+            Ok(result) => result.context("Launch vetoed by on_launch"),
+            Err(panic) => Err(anyhow!("on_launch panicked: {}", panic)),
         }
     }
 
     /// Processes a discovered service's configuration to launch it.
-    #[instrument(name = "process_service", skip(cfg, service, app_handler), fields(service.name = %service.fullname))]
+    #[instrument(name = "process_service", skip(cfg, service, app_handler, hooks, redact_keys), fields(service.name = %service.fullname))]
     async fn process_service_config(
         cfg: &McpConfig,
         service: &DiscoveredService,
         app_handler: &Arc<dyn ZeroHandler>,
-    ) -> Result<McpClient> {
-        let mut ctx = json!({ "service": service });
+        hooks: &ManagerHooks,
+        redact_keys: &[String],
+        inputs: Option<&InputCache>,
+    ) -> Result<ActiveService> {
+        let mut ctx = template_context(service);
+        if let Some(inputs) = inputs {
+            inputs.seed(&service.fullname, &mut ctx);
+        }
+        let active_service =
+            Self::launch_service(cfg, service, &mut ctx, app_handler, hooks, redact_keys).await?;
+        // Only inputs that got the service running are worth reusing.
+        if let Some(inputs) = inputs {
+            inputs.store(&service.fullname, &ctx);
+        }
+        Ok(active_service)
+    }
+
+    /// Renders `cfg` against `ctx`, which gains any inputs requested, and
+    /// launches the service.
+    async fn launch_service(
+        cfg: &McpConfig,
+        service: &DiscoveredService,
+        ctx: &mut Value,
+        app_handler: &Arc<dyn ZeroHandler>,
+        hooks: &ManagerHooks,
+        redact_keys: &[String],
+    ) -> Result<ActiveService> {
+        let render_started = Instant::now();
+        let mut startup = StartupTimings::default();
+        let launch_timeout = cfg.options().launch_timeout();
+        let handler = Self::build_client_handler(cfg, ctx, service, app_handler, hooks).await?;
+        let progress = handler.progress();
+        let roots = handler.roots();
+        let (traffic, _) = broadcast::channel(TRAFFIC_TAP_CAPACITY);
 
         match cfg {
             McpConfig::Stdio {
                 command,
                 args,
                 envs,
+                cwd,
+                clear_env,
                 ..
             } => {
                 let mut final_args = Vec::with_capacity(args.len());
                 for a_tpl in args {
-                    let arg = Self::render_template_with_input(
-                        a_tpl,
-                        &mut ctx,
-                        &service.fullname,
-                        app_handler,
-                    )
-                    .await?;
+                    let arg = render_template_with_input(a_tpl, ctx, service, app_handler).await?;
                     final_args.push(arg);
                 }
 
-                let mut child_cmd = tokio::process::Command::new(command);
+                let mut final_envs = IndexMap::with_capacity(envs.len());
                 for (k, v_tpl) in envs {
-                    let v = Self::render_template_with_input(
-                        v_tpl,
-                        &mut ctx,
-                        &service.fullname,
-                        app_handler,
-                    )
-                    .await?;
-                    child_cmd.env(k, v);
+                    let v = render_template_with_input(v_tpl, ctx, service, app_handler).await?;
+                    final_envs.insert(k.clone(), v);
+                }
+                let final_cwd = match cwd {
+                    Some(tpl) => {
+                        Some(render_template_with_input(tpl, ctx, service, app_handler).await?)
+                    }
+                    None => None,
+                };
+                let launch = RenderedLaunch::Stdio {
+                    command: command.clone(),
+                    args: final_args.clone(),
+                    envs: final_envs.clone(),
+                    cwd: final_cwd.clone(),
+                    clear_env: *clear_env,
+                };
+                Self::notify_launch(&service.fullname, launch, redact_keys, app_handler).await?;
+                startup.render = render_started.elapsed();
+
+                let connect_started = Instant::now();
+                let mut child_cmd = tokio::process::Command::new(command);
+                if *clear_env {
+                    child_cmd.env_clear();
+                }
+                child_cmd.envs(final_envs);
+                if let Some(cwd) = &final_cwd {
+                    child_cmd.current_dir(cwd);
                 }
 
                 info!(command = %command, args = ?final_args, "Spawning stdio process");
-                child_cmd
-                    .args(&final_args)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped());
-                let transport = TokioChildProcess::new(child_cmd)?;
-                Ok(().into_dyn().serve(transport).await?)
-            }
-            McpConfig::Sse { url, headers, .. } => {
-                let url_str =
-                    Self::render_template_with_input(url, &mut ctx, &service.fullname, app_handler)
-                        .await?;
-                let client_builder = reqwest::ClientBuilder::new();
-
-                let client = if let Some(hdr) = headers {
-                    let mut rendered_map = HashMap::new();
-                    for (k, v_tpl) in hdr.iter() {
-                        let v = Self::render_template_with_input(
-                            v_tpl,
-                            &mut ctx,
-                            &service.fullname,
-                            app_handler,
-                        )
-                        .await?;
-                        rendered_map.insert(k.clone(), v);
+                child_cmd.args(&final_args);
+                let (output, stdin, stderr) = spawn_child(child_cmd).map_err(|e| {
+                    if ZeroError::is_resource_exhaustion(&e) {
+                        anyhow::Error::from(ZeroError::ResourceExhausted {
+                            command: command.clone(),
+                            source: e,
+                        })
+                    } else {
+                        let context = match &final_cwd {
+                            Some(cwd) => format!("Failed to spawn '{}' in '{}'", command, cwd),
+                            None => format!("Failed to spawn '{}'", command),
+                        };
+                        anyhow::Error::from(e).context(context)
                     }
-                    let default_headers = hashmap_to_header_map(&rendered_map)?;
-                    client_builder.default_headers(default_headers).build()?
+                })?;
+                let pid = output.id();
+                let child = output.handle();
+                Self::forward_stderr(stderr, &service.fullname, app_handler);
+                let transport = TappedTransport::new(
+                    IntoTransport::<RoleClient, std::io::Error, TransportAdapterAsyncRW>::into_transport(
+                        (output, stdin),
+                    ),
+                    traffic.clone(),
+                );
+                startup.connect = connect_started.elapsed();
+                let initialize_started = Instant::now();
+                let client =
+                    within_launch_timeout(launch_timeout, handler.into_dyn().serve(transport))
+                        .await?;
+                startup.initialize = initialize_started.elapsed();
+                Ok(ActiveService {
+                    client,
+                    info: service.clone(),
+                    config: cfg.clone(),
+                    pid,
+                    child: Some(child),
+                    session_id: None,
+                    health: HealthTracker::default(),
+                    tools_baseline: None,
+                    prompts_snapshot: None,
+                    progress,
+                    roots,
+                    calls: CallQueue::new(cfg.options().max_queue_depth),
+                    limit: CallLimit::new(cfg.options().max_concurrent_calls),
+                    context: ctx.clone(),
+                    traffic,
+                    startup,
+                    last_active: Instant::now(),
+                    last_health_check: Instant::now(),
+                })
+            }
+            McpConfig::Sse {
+                url,
+                headers,
+                proxy,
+                compression,
+                ..
+            } => {
+                // With `{{service.address}}` in the URL, every advertised
+                // address is a candidate and they're raced happy-eyeballs style.
+                let addresses = if url.contains("service.address") {
+                    happy_eyeballs_order(&service.addresses)
                 } else {
-                    client_builder.build()?
+                    Vec::new()
+                };
+                let mut candidates = Vec::with_capacity(addresses.len().max(1));
+                for addr in &addresses {
+                    ctx["service"]["address"] = json!(url_host(addr));
+                    let url_str =
+                        render_template_with_input(url, ctx, service, app_handler).await?;
+                    candidates.push(SseCandidate {
+                        address: Some(addr.to_string()),
+                        url: url_str,
+                    });
+                }
+                if candidates.is_empty() {
+                    let url_str =
+                        render_template_with_input(url, ctx, service, app_handler).await?;
+                    candidates.push(SseCandidate {
+                        address: None,
+                        url: url_str,
+                    });
+                }
+
+                let (client_builder, rendered_map) = Self::http_client_builder(
+                    proxy.as_ref(),
+                    *compression,
+                    headers.as_ref(),
+                    ctx,
+                    service,
+                    app_handler,
+                )
+                .await?;
+                let launch = RenderedLaunch::Sse {
+                    urls: candidates.iter().map(|c| c.url.clone()).collect(),
+                    headers: rendered_map.clone(),
+                };
+                Self::notify_launch(&service.fullname, launch, redact_keys, app_handler).await?;
+                startup.render = render_started.elapsed();
+                let client = client_builder
+                    .default_headers(to_header_map(&rendered_map)?)
+                    .build()?;
+
+                let connecting =
+                    connect_first(candidates, HAPPY_EYEBALLS_HEAD_START, |candidate| {
+                        let client = SessionTrackingClient::new(client.clone());
+                        let handler = handler.clone();
+                        let traffic = traffic.clone();
+                        async move {
+                            info!(url = %candidate.url, "Starting SSE transport");
+                            let connect_started = Instant::now();
+                            let transport = SseClientTransport::start_with_client(
+                                client.clone(),
+                                SseClientConfig {
+                                    sse_endpoint: candidate.url.into(),
+                                    ..Default::default()
+                                },
+                            )
+                            .await?;
+                            let connect = connect_started.elapsed();
+                            let initialize_started = Instant::now();
+                            let mcp_client = handler
+                                .into_dyn()
+                                .serve(TappedTransport::new(transport, traffic))
+                                .await?;
+                            Ok((mcp_client, client, connect, initialize_started.elapsed()))
+                        }
+                    });
+                let (winner, (mcp_client, client, connect, initialize)) =
+                    within_launch_timeout(launch_timeout, connecting).await?;
+                if let Some(address) = &winner.address {
+                    info!(address = %address, "Connected to SSE service");
+                }
+
+                startup.connect = connect;
+                startup.initialize = initialize;
+
+                let session_id = client.session_id();
+                if let Some(id) = &session_id {
+                    info!(session_id = %id, "SSE session established");
+                }
+                let mut info = service.clone();
+                info.address = winner.address;
+                Ok(ActiveService {
+                    client: mcp_client,
+                    info,
+                    config: cfg.clone(),
+                    pid: None,
+                    child: None,
+                    session_id,
+                    health: HealthTracker::default(),
+                    tools_baseline: None,
+                    prompts_snapshot: None,
+                    progress,
+                    roots,
+                    calls: CallQueue::new(cfg.options().max_queue_depth),
+                    limit: CallLimit::new(cfg.options().max_concurrent_calls),
+                    context: ctx.clone(),
+                    traffic,
+                    startup,
+                    last_active: Instant::now(),
+                    last_health_check: Instant::now(),
+                })
+            }
+            McpConfig::StreamableHttp {
+                url,
+                headers,
+                proxy,
+                compression,
+                ..
+            } => {
+                let url_str = render_template_with_input(url, ctx, service, app_handler).await?;
+                let (client_builder, rendered_map) = Self::http_client_builder(
+                    proxy.as_ref(),
+                    *compression,
+                    headers.as_ref(),
+                    ctx,
+                    service,
+                    app_handler,
+                )
+                .await?;
+                let launch = RenderedLaunch::StreamableHttp {
+                    url: url_str.clone(),
+                    headers: rendered_map.clone(),
                 };
+                Self::notify_launch(&service.fullname, launch, redact_keys, app_handler).await?;
+                startup.render = render_started.elapsed();
+                let client = client_builder
+                    .default_headers(to_header_map(&rendered_map)?)
+                    .build()?;
 
-                info!(url = %url_str, "Starting SSE transport");
-                let transport = SseClientTransport::start_with_client(
+                info!(url = %url_str, "Starting streamable HTTP transport");
+                // The transport connects lazily, so connecting is part of the handshake.
+                let transport = StreamableHttpClientTransport::with_client(
                     client,
-                    SseClientConfig {
-                        sse_endpoint: url_str.into(),
-                        ..Default::default()
-                    },
+                    StreamableHttpClientTransportConfig::with_uri(url_str),
+                );
+                let initialize_started = Instant::now();
+                let client = within_launch_timeout(
+                    launch_timeout,
+                    handler
+                        .into_dyn()
+                        .serve(TappedTransport::new(transport, traffic.clone())),
                 )
                 .await?;
-                Ok(().into_dyn().serve(transport).await?)
+                startup.initialize = initialize_started.elapsed();
+                Ok(ActiveService {
+                    client,
+                    info: service.clone(),
+                    config: cfg.clone(),
+                    pid: None,
+                    child: None,
+                    session_id: None,
+                    health: HealthTracker::default(),
+                    tools_baseline: None,
+                    prompts_snapshot: None,
+                    progress,
+                    roots,
+                    calls: CallQueue::new(cfg.options().max_queue_depth),
+                    limit: CallLimit::new(cfg.options().max_concurrent_calls),
+                    context: ctx.clone(),
+                    traffic,
+                    startup,
+                    last_active: Instant::now(),
+                    last_health_check: Instant::now(),
+                })
+            }
+        }
+    }
+
+    /// Reads a stdio service's stderr until it closes, passing each line to
+    /// `on_service_log`. Reading it also keeps a chatty process from
+    /// blocking on a full pipe.
+    fn forward_stderr(stderr: ChildStderr, service_name: &str, app_handler: &Arc<dyn ZeroHandler>) {
+        let service_name = service_name.to_string();
+        let app_handler = app_handler.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        debug!(service = %service_name, "stderr: {}", line);
+                        if let Err(panic) =
+                            catch_panic(app_handler.on_service_log(&service_name, &line)).await
+                        {
+                            error!(panic = %panic, "on_service_log panicked for '{}'", service_name);
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        debug!(service = %service_name, error = %e, "Stopped reading stderr");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Renders the proxy and headers of an HTTP-based service, returning a
+    /// client builder configured with the proxy and compression, along with
+    /// the rendered headers.
+    async fn http_client_builder(
+        proxy: Option<&ProxyConfig>,
+        compression: Option<bool>,
+        headers: Option<&IndexMap<String, String>>,
+        ctx: &mut serde_json::Value,
+        service: &DiscoveredService,
+        app_handler: &Arc<dyn ZeroHandler>,
+    ) -> Result<(reqwest::ClientBuilder, IndexMap<String, String>)> {
+        // reqwest decompresses by default once its features are on, so
+        // the setting is always applied explicitly.
+        let compression = compression.unwrap_or(false);
+        let mut client_builder = reqwest::ClientBuilder::new()
+            .gzip(compression)
+            .deflate(compression);
+        if let Some(proxy) = proxy {
+            let mut render =
+                async |tpl: &str| render_template_with_input(tpl, ctx, service, app_handler).await;
+            let proxy_url = render(&proxy.url).await?;
+            let username = match &proxy.username {
+                Some(tpl) => Some(render(tpl).await?),
+                None => None,
+            };
+            let password = match &proxy.password {
+                Some(tpl) => render(tpl).await?,
+                None => String::new(),
+            };
+            info!(proxy = %proxy_url, "Connecting through proxy");
+            client_builder = client_builder.proxy(build_proxy(
+                &proxy_url,
+                username.as_deref().map(|u| (u, password.as_str())),
+                proxy.no_proxy.as_deref(),
+            )?);
+        }
+
+        let mut rendered_map = IndexMap::new();
+        for (k, v_tpl) in headers.into_iter().flatten() {
+            let v = render_template_with_input(v_tpl, ctx, service, app_handler).await?;
+            rendered_map.insert(k.clone(), v);
+        }
+        Ok((client_builder, rendered_map))
+    }
+
+    /// Launches a service in the background, unless it is already active or
+    /// being launched. With `replace`, a running instance is stopped first,
+    /// e.g. because it re-resolved with changes.
+    fn handle_service_appeared(&self, service: DiscoveredService, cfg: McpConfig, replace: bool) {
+        let client = self.client.clone();
+        let app_handler = self.app_handler.clone();
+        let panic_policy = self.config.handler_panic_policy;
+        let config = self.config.clone();
+        let hooks = self.hooks.clone();
+        let redact_keys = self.config.redact_keys.clone();
+        let identity_key = self.config.replica_identity_key.clone();
+        let priority_key = self.config.replica_priority_key.clone();
+        let discovery = self.discovery_tx.clone();
+
+        tokio::spawn(async move {
+            // Inherit the span from the parent task for better context in logs
+            let span = Span::current();
+            let _enter = span.enter();
+
+            let service_fullname = service.fullname.clone();
+            let claim = loop {
+                if replace {
+                    info!("Stopping the running instance before relaunching the changed service");
+                    match client.stop_service(&service_fullname).await {
+                        Ok(reason) => {
+                            if let Err(panic) = catch_panic(
+                                app_handler.on_service_stopped(&service_fullname, reason),
+                            )
+                            .await
+                            {
+                                // The old instance is already gone, so only the report applies.
+                                handle_handler_panic(
+                                    &client,
+                                    &app_handler,
+                                    HandlerPanicPolicy::Keep,
+                                    &service_fullname,
+                                    &panic,
+                                )
+                                .await;
+                            }
+                        }
+                        Err(e) => debug!(error = %e, "No running instance to stop"),
+                    }
+                }
+                match client.claim_launch(&service_fullname).await {
+                    Ok(Some(claim)) => break claim,
+                    // A launch of the old resolution is in flight, so this one
+                    // follows once it ends.
+                    Ok(None) if replace => {
+                        match client.supersede_launch(&service, &cfg).await {
+                            Ok(true) => {
+                                info!("Relaunching once the launch in flight ends");
+                                return;
+                            }
+                            // The launch ended and the service is active; stop it.
+                            Ok(false) => continue,
+                            Err(e) => {
+                                warn!(error = %e, "Failed to queue the relaunch");
+                                return;
+                            }
+                        }
+                    }
+                    _ => {
+                        info!(
+                            "Service is already active or being launched; not launching it again"
+                        );
+                        return;
+                    }
+                }
+            };
+            let cfg = match client.launch_override(&service_fullname).await {
+                Ok(Some(override_cfg)) => {
+                    info!("Using launch override instead of the type mapping");
+                    override_cfg
+                }
+                Ok(None) => cfg,
+                Err(e) => {
+                    warn!(error = %e, "Failed to look up launch override; using the type mapping");
+                    cfg
+                }
+            };
+            let cfg = cfg.with_defaults(&config);
+            if let Some(cooldown) = cfg
+                .options()
+                .relaunch_cooldown_ms
+                .map(Duration::from_millis)
+                && let Ok(Some(stopped)) = client.stopped_at(&service_fullname).await
+            {
+                let remaining = cooldown.saturating_sub(stopped.elapsed());
+                if !remaining.is_zero() {
+                    info!("Waiting {:?} for the relaunch cooldown", remaining);
+                    tokio::time::sleep(remaining).await;
+                }
+            }
+            let migration = match &priority_key {
+                Some(priority_key) => {
+                    match Self::plan_migration(
+                        &client,
+                        &service,
+                        identity_key.as_deref(),
+                        priority_key,
+                    )
+                    .await
+                    {
+                        Some(migration) => migration,
+                        None => return,
+                    }
+                }
+                None => None,
+            };
+            if let Some((identity, from)) = &migration {
+                info!("Migrating '{}' from {:?}", identity, from);
+                if let Err(panic) =
+                    catch_panic(app_handler.on_migration_started(identity, from, &service)).await
+                {
+                    handle_handler_panic(
+                        &client,
+                        &app_handler,
+                        HandlerPanicPolicy::Keep,
+                        &service_fullname,
+                        &panic,
+                    )
+                    .await;
+                }
+            }
+            if let Err(panic) = catch_panic(app_handler.on_service_discovered(&service, &cfg)).await
+            {
+                handle_handler_panic(
+                    &client,
+                    &app_handler,
+                    HandlerPanicPolicy::Keep,
+                    &service_fullname,
+                    &panic,
+                )
+                .await;
+            }
+            let process_fut = Self::process_service_config(
+                &cfg,
+                &service,
+                &app_handler,
+                &hooks,
+                &redact_keys,
+                client.inputs.as_ref(),
+            );
+            let launched = async {
+                let active_service = process_fut.await?;
+                let probe_started = Instant::now();
+                active_service.wait_until_stable().await?;
+                anyhow::Ok((active_service, probe_started))
+            };
+
+            match launched.await {
+                Ok((mut active_service, probe_started)) => {
+                    if let Some(reason) = active_service.rejection_reason().await {
+                        info!(reason = %reason, "Disconnecting service that failed requirements");
+                        if let Err(e) = active_service.client.cancel().await {
+                            warn!(error = %e, "Failed to cleanly cancel rejected service");
+                        }
+                        if let Err(panic) =
+                            catch_panic(app_handler.on_service_rejected(&service, &reason)).await
+                        {
+                            handle_handler_panic(
+                                &client,
+                                &app_handler,
+                                HandlerPanicPolicy::Keep,
+                                &service_fullname,
+                                &panic,
+                            )
+                            .await;
+                        }
+                        return;
+                    }
+
+                    let empty_policy = active_service
+                        .config
+                        .options()
+                        .empty_server_policy
+                        .unwrap_or_default();
+                    if empty_policy != EmptyServerPolicy::Keep
+                        && active_service.offers_nothing().await
+                    {
+                        if empty_policy == EmptyServerPolicy::Warn {
+                            warn!("Service offers no tools, prompts or resources");
+                        } else {
+                            info!(
+                                "Disconnecting service that offers no tools, prompts or resources"
+                            );
+                            if let Err(e) = active_service.client.cancel().await {
+                                warn!(error = %e, "Failed to cleanly cancel empty service");
+                            }
+                            if let Err(panic) =
+                                catch_panic(app_handler.on_service_empty(&service)).await
+                            {
+                                handle_handler_panic(
+                                    &client,
+                                    &app_handler,
+                                    HandlerPanicPolicy::Keep,
+                                    &service_fullname,
+                                    &panic,
+                                )
+                                .await;
+                            }
+                            return;
+                        }
+                    }
+
+                    active_service.startup.probe = probe_started.elapsed();
+                    info!(
+                        startup = ?active_service.startup,
+                        "Service ready after {:?}",
+                        active_service.startup.total()
+                    );
+                    let supervised = active_service.supervision();
+                    let msg = ServiceMessage::AddService {
+                        name: service_fullname.clone(),
+                        service: Box::new(active_service),
+                    };
+
+                    if let Err(e) = client.actor.cast(msg) {
+                        error!(error = %e, "Failed to send AddService message to actor");
+                        return;
+                    }
+                    // Being active now keeps it from being launched again.
+                    drop(claim);
+                    client.metrics.service_launched(&service);
+                    // Notify the user's application logic, shielding the manager from panics.
+                    if let Err(panic) = catch_panic(app_handler.on_service_started(&service)).await
+                    {
+                        handle_handler_panic(
+                            &client,
+                            &app_handler,
+                            panic_policy,
+                            &service_fullname,
+                            &panic,
+                        )
+                        .await;
+                    }
+                    if let Some((identity, from)) = &migration {
+                        let check_timeout = Duration::from_millis(config.health_check_timeout_ms);
+                        if !Self::complete_migration(
+                            &client,
+                            &app_handler,
+                            identity,
+                            from,
+                            &service,
+                            check_timeout,
+                        )
+                        .await
+                        {
+                            return;
+                        }
+                    }
+                    if let Some(supervision) = supervised {
+                        let launch = Launch {
+                            cfg: &cfg,
+                            service: &service,
+                            app_handler: &app_handler,
+                            hooks: &hooks,
+                            redact_keys: &redact_keys,
+                        };
+                        Self::supervise(&client, launch, supervision).await;
+                    }
+                }
+                Err(e) => {
+                    error!(error = ?e, "Failed to start MCP for service");
+                    // The resolution only counts as handled once it launched.
+                    let _ = discovery.send(DiscoveryCommand::Forget(Box::new(service.clone())));
+                    report_service_error(&client, &app_handler, &service_fullname, &e).await;
+                    if migration.is_some() {
+                        info!("Migration abandoned; the current replicas stay active");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Launches a service again from its stored config, once it is stable.
+    async fn relaunch(client: &ZeroClient, launch: &Launch<'_>) -> Result<ActiveService> {
+        let active_service = Self::process_service_config(
+            launch.cfg,
+            launch.service,
+            launch.app_handler,
+            launch.hooks,
+            launch.redact_keys,
+            client.inputs.as_ref(),
+        )
+        .await?;
+        active_service.wait_until_stable().await?;
+        Ok(active_service)
+    }
+
+    /// Connects to an SSE or streamable HTTP service again, rendering its url
+    /// and headers from the `context` it was first launched with, once it is
+    /// stable.
+    async fn reconnect(launch: &Launch<'_>, context: &Value) -> Result<ActiveService> {
+        let mut ctx = context.clone();
+        let active_service = Self::launch_service(
+            launch.cfg,
+            launch.service,
+            &mut ctx,
+            launch.app_handler,
+            launch.hooks,
+            launch.redact_keys,
+        )
+        .await?;
+        active_service.wait_until_stable().await?;
+        Ok(active_service)
+    }
+
+    /// Relaunches a service stopped by `ZeroClient::restart_service`, replying
+    /// once it is active again, and supervises it if it has a restart or
+    /// reconnect policy.
+    fn handle_service_restart(
+        &self,
+        service: DiscoveredService,
+        cfg: McpConfig,
+        reply: oneshot::Sender<Result<()>>,
+    ) {
+        let client = self.client.clone();
+        let app_handler = self.app_handler.clone();
+        let hooks = self.hooks.clone();
+        let redact_keys = self.config.redact_keys.clone();
+        let discovery = self.discovery_tx.clone();
+
+        tokio::spawn(async move {
+            let span = Span::current();
+            let _enter = span.enter();
+
+            let name = &service.fullname;
+            let launch = Launch {
+                cfg: &cfg,
+                service: &service,
+                app_handler: &app_handler,
+                hooks: &hooks,
+                redact_keys: &redact_keys,
+            };
+            let claim = match client.claim_launch(name).await {
+                Ok(Some(claim)) => claim,
+                Ok(None) => {
+                    let _ = reply.send(Err(anyhow!(
+                        "Service '{}' is already being launched.",
+                        name
+                    )));
+                    return;
+                }
+                Err(e) => {
+                    let _ = reply.send(Err(e));
+                    return;
+                }
+            };
+            let active_service = match Self::relaunch(&client, &launch).await {
+                Ok(active_service) => active_service,
+                Err(e) => {
+                    error!(error = ?e, "Failed to restart service");
+                    let _ = discovery.send(DiscoveryCommand::Forget(Box::new(service.clone())));
+                    let e = e.context("restart failed");
+                    report_service_error(&client, &app_handler, name, &e).await;
+                    if let Err(panic) =
+                        catch_panic(app_handler.on_service_stopped(name, QuitReason::Closed)).await
+                    {
+                        // The service is already gone, so only the report applies.
+                        handle_handler_panic(
+                            &client,
+                            &app_handler,
+                            HandlerPanicPolicy::Keep,
+                            name,
+                            &panic,
+                        )
+                        .await;
+                    }
+                    let _ = reply.send(Err(e));
+                    return;
+                }
+            };
+            let supervised = active_service.supervision();
+            let msg = ServiceMessage::AddService {
+                name: name.clone(),
+                service: Box::new(active_service),
+            };
+            if let Err(e) = client.actor.cast(msg) {
+                error!(error = %e, "Failed to send AddService message to actor");
+                let _ = reply.send(Err(anyhow!("Manager is no longer running.")));
+                return;
+            }
+            drop(claim);
+            client.metrics.service_launched(&service);
+            info!("Service restarted");
+            let _ = reply.send(Ok(()));
+            if let Some(supervision) = supervised {
+                Self::supervise(&client, launch, supervision).await;
+            }
+        });
+    }
+
+    /// Watches a running service and brings it back, with backoff, each time
+    /// it goes away without having been stopped: a stdio service's process is
+    /// relaunched, while an SSE or streamable HTTP service is reconnected to.
+    /// Returns once the service is stopped deliberately, including while it's
+    /// being brought back, or the policy's retries run out.
+    async fn supervise(client: &ZeroClient, launch: Launch<'_>, supervision: Supervision) {
+        let recovery = supervision.recovery;
+        let context = supervision.context.clone();
+        let (launch, context) = (&launch, &context);
+        Self::supervise_with(
+            client,
+            launch.service,
+            launch.app_handler,
+            supervision,
+            move || async move {
+                match recovery {
+                    Recovery::Restart => Self::relaunch(client, launch).await,
+                    Recovery::Reconnect => Self::reconnect(launch, context).await,
+                }
+            },
+        )
+        .await;
+    }
+
+    /// Runs [`ServiceManager::supervise`], bringing the service back with `recover`.
+    async fn supervise_with<F, Fut>(
+        client: &ZeroClient,
+        service: &DiscoveredService,
+        app_handler: &Arc<dyn ZeroHandler>,
+        mut supervision: Supervision,
+        mut recover: F,
+    ) where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<ActiveService>>,
+    {
+        let name = &service.fullname;
+        let policy = supervision.policy;
+        let action = match supervision.recovery {
+            Recovery::Restart => "restart",
+            Recovery::Reconnect => "reconnect",
+        };
+        // Attempts are only counted afresh once the service stays up for
+        // `reset_after_ms`, so one that keeps crashing runs out of them.
+        let mut used = 0;
+        let mut up_since = Instant::now();
+        loop {
+            while !supervision.peer.is_transport_closed() {
+                tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+            }
+            let pid = supervision.pid;
+            // A service that was stopped, or replaced, is no longer ours to restart.
+            if !client.remove_exited(name, pid).await.unwrap_or(false) {
+                return;
+            }
+            // Nor is one that is being launched afresh, e.g. after re-resolving.
+            let Ok(Some(claim)) = client.claim_launch(name).await else {
+                return;
+            };
+            if up_since.elapsed() >= policy.reset_after() {
+                used = 0;
+            }
+            let error = match supervision.recovery {
+                Recovery::Restart => {
+                    warn!(pid = ?pid, "Service process exited unexpectedly");
+                    anyhow!("process {:?} exited unexpectedly; restarting", pid)
+                }
+                Recovery::Reconnect => {
+                    warn!("Service connection dropped");
+                    anyhow!("connection dropped; reconnecting")
+                }
+            };
+            report_service_error(client, app_handler, name, &error).await;
+
+            let mut restarted = None;
+            let mut last_error = None;
+            while restarted.is_none() && used < policy.max_retries {
+                used += 1;
+                let attempt = used;
+                // Discovery may remove the service while it's being brought back.
+                let result = tokio::select! {
+                    _ = claim.withdrawn.cancelled() => break,
+                    result = async {
+                        tokio::time::sleep(policy.backoff(attempt)).await;
+                        recover().await
+                    } => result,
+                };
+                match result {
+                    Ok(active_service) => restarted = Some((attempt, active_service)),
+                    Err(e) => {
+                        warn!(attempt, error = %e, "Failed to {} service", action);
+                        if supervision.recovery == Recovery::Reconnect {
+                            let e = anyhow!("reconnect attempt {} failed: {:#}", attempt, e);
+                            report_service_error(client, app_handler, name, &e).await;
+                        }
+                        last_error = Some(e);
+                    }
+                }
+            }
+
+            if claim.withdrawn.is_cancelled() {
+                info!("Service was stopped while being brought back; no longer supervising it");
+                if let Some((_, active_service)) = restarted
+                    && let Err(e) = active_service.client.cancel().await
+                {
+                    warn!(error = %e, "Failed to cleanly cancel the restarted service");
+                }
+                if let Err(panic) =
+                    catch_panic(app_handler.on_service_stopped(name, QuitReason::Cancelled)).await
+                {
+                    // The service is already gone, so only the report applies.
+                    handle_handler_panic(
+                        client,
+                        app_handler,
+                        HandlerPanicPolicy::Keep,
+                        name,
+                        &panic,
+                    )
+                    .await;
+                }
+                return;
+            }
+            let Some((attempt, active_service)) = restarted else {
+                error!(
+                    "Giving up on service after {} {} attempts",
+                    policy.max_retries, action
+                );
+                let error = last_error
+                    .unwrap_or_else(|| anyhow!("no {} attempts left", action))
+                    .context(format!(
+                        "gave up after {} {} attempts",
+                        policy.max_retries, action
+                    ));
+                report_service_error(client, app_handler, name, &error).await;
+                if let Err(panic) =
+                    catch_panic(app_handler.on_service_stopped(name, QuitReason::Closed)).await
+                {
+                    // The service is already gone, so only the report applies.
+                    handle_handler_panic(
+                        client,
+                        app_handler,
+                        HandlerPanicPolicy::Keep,
+                        name,
+                        &panic,
+                    )
+                    .await;
+                }
+                return;
+            };
+            supervision.peer = active_service.client.peer().clone();
+            supervision.pid = active_service.pid;
+            up_since = Instant::now();
+            let pid = supervision.pid;
+            let msg = ServiceMessage::AddService {
+                name: name.clone(),
+                service: Box::new(active_service),
+            };
+            if let Err(e) = client.actor.cast(msg) {
+                error!(error = %e, "Failed to send AddService message to actor");
+                return;
             }
+            drop(claim);
+            client.metrics.service_launched(service);
+            info!(pid = ?pid, attempt, "on_service_restarted: service is running again");
         }
     }
 
-    fn handle_service_appeared(&self, service: DiscoveredService, cfg: McpConfig) {
-        let actor_ref = self.actor.clone();
-        let app_handler = self.app_handler.clone();
-
-        tokio::spawn(async move {
-            // Inherit the span from the parent task for better context in logs
-            let span = Span::current();
-            let _enter = span.enter();
-
-            let service_fullname = service.fullname.clone();
-            let process_fut = Self::process_service_config(&cfg, &service, &app_handler);
-
-            match process_fut.await {
-                Ok(mcp_client) => {
-                    let msg = ServiceMessage::AddService {
-                        name: service_fullname.clone(),
-                        service: mcp_client,
-                    };
+    /// Decides how a service relates to the active replicas of its identity
+    /// under `replica_priority_key`.
+    ///
+    /// Returns `None` if an active replica is at least as preferred, so the
+    /// service should be kept as a standby. Otherwise returns the identity and
+    /// the active replicas it should replace, if there are any.
+    async fn plan_migration(
+        client: &ZeroClient,
+        service: &DiscoveredService,
+        identity_key: Option<&str>,
+        priority_key: &str,
+    ) -> Option<Option<(String, Vec<String>)>> {
+        let identity = service.identity(identity_key).to_string();
+        let priority = service.priority(priority_key);
+        let replicas: Vec<DiscoveredService> = match client.service_replicas(&identity).await {
+            Ok(replicas) => replicas
+                .into_iter()
+                .filter(|r| r.fullname != service.fullname)
+                .collect(),
+            Err(e) => {
+                warn!(error = %e, "Failed to look up replicas; launching without migration");
+                return Some(None);
+            }
+        };
+        if let Some(preferred) = replicas
+            .iter()
+            .find(|r| r.priority(priority_key) >= priority)
+        {
+            info!(
+                "Keeping service as a standby; '{}' has priority at least {}",
+                preferred.fullname, priority
+            );
+            return None;
+        }
+        if replicas.is_empty() {
+            return Some(None);
+        }
+        let from = replicas.into_iter().map(|r| r.fullname).collect();
+        Some(Some((identity, from)))
+    }
 
-                    if let Err(e) = actor_ref.cast(msg) {
-                        error!(error = %e, "Failed to send AddService message to actor");
-                    } else {
-                        // Notify the user's application logic.
-                        app_handler.on_service_started(&service).await;
+    /// Moves an identity onto a newly started replica, make-before-break.
+    ///
+    /// The new replica must pass a health check before the replicas it
+    /// supersedes are drained, refusing new calls, and stopped once all have
+    /// drained. If the check or a drain fails, the migration is abandoned:
+    /// the new replica is stopped, and the drained replicas take calls again
+    /// if they still pass a health check. Returns whether it completed.
+    async fn complete_migration(
+        client: &ZeroClient,
+        app_handler: &Arc<dyn ZeroHandler>,
+        identity: &str,
+        from: &[String],
+        to: &DiscoveredService,
+        check_timeout: Duration,
+    ) -> bool {
+        if let Err(e) = client.ping_within(&to.fullname, check_timeout).await {
+            warn!(error = %e, "New replica failed its health check; abandoning migration");
+            Self::stop_replica(client, app_handler, &to.fullname).await;
+            return false;
+        }
+        let mut drained = Vec::with_capacity(from.len());
+        for name in from {
+            match client.drain_calls(name).await {
+                Ok(true) => drained.push(name),
+                Ok(false) => debug!("'{}' stopped before it was drained", name),
+                Err(e) => {
+                    warn!(error = %e, "Failed to drain '{}'; abandoning migration", name);
+                    for name in drained {
+                        match client.ping_within(name, check_timeout).await {
+                            Ok(_) => {
+                                if let Err(e) = client.resume_calls(name).await {
+                                    debug!(error = %e, "Failed to resume calls on '{}'", name);
+                                }
+                            }
+                            Err(e) => {
+                                warn!(error = %e, "'{}' failed its health check after draining", name);
+                                Self::stop_replica(client, app_handler, name).await;
+                            }
+                        }
                     }
+                    Self::stop_replica(client, app_handler, &to.fullname).await;
+                    return false;
                 }
-                Err(e) => {
-                    error!(error = ?e, "Failed to start MCP for service");
+            }
+        }
+        for name in drained {
+            Self::stop_replica(client, app_handler, name).await;
+        }
+        info!("Migrated '{}' to '{}'", identity, to.fullname);
+        if let Err(panic) =
+            catch_panic(app_handler.on_migration_completed(identity, from, to)).await
+        {
+            handle_handler_panic(
+                client,
+                app_handler,
+                HandlerPanicPolicy::Keep,
+                &to.fullname,
+                &panic,
+            )
+            .await;
+        }
+        true
+    }
+
+    /// Stops a replica a migration is done with, reporting it to the handler.
+    async fn stop_replica(client: &ZeroClient, app_handler: &Arc<dyn ZeroHandler>, name: &str) {
+        match client.stop_service(name).await {
+            Ok(reason) => {
+                if let Err(panic) = catch_panic(app_handler.on_service_stopped(name, reason)).await
+                {
+                    // The replica is already gone, so only the report applies.
+                    handle_handler_panic(
+                        client,
+                        app_handler,
+                        HandlerPanicPolicy::Keep,
+                        name,
+                        &panic,
+                    )
+                    .await;
                 }
             }
-        });
+            Err(e) => {
+                debug!(error = %e, "Error stopping replica '{}'", name);
+            }
+        }
     }
 
-    fn handle_service_disappeared(&self, service_fullname: &str) {
-        let client = ZeroClient {
-            actor: self.actor.clone(),
-        };
+    /// Picks the standby to launch when `removed` goes away under
+    /// `replica_priority_key`: the highest-priority resolved replica left.
+    fn failover_candidate(
+        &self,
+        resolved: &HashMap<String, (DiscoveredService, McpConfig)>,
+        removed: &DiscoveredService,
+    ) -> Option<(DiscoveredService, McpConfig)> {
+        let priority_key = self.config.replica_priority_key.as_deref()?;
+        let identity_key = self.config.replica_identity_key.as_deref();
+        let identity = removed.identity(identity_key);
+        resolved
+            .values()
+            .filter(|(service, _)| service.identity(identity_key) == identity)
+            .max_by_key(|(service, _)| service.priority(priority_key))
+            .cloned()
+    }
+
+    fn handle_service_disappeared(
+        &self,
+        service_fullname: &str,
+        failover: Option<(DiscoveredService, McpConfig)>,
+    ) {
+        let client = self.client.clone();
         let name = service_fullname.to_string();
         let app_handler = self.app_handler.clone();
+        let identity_key = self.config.replica_identity_key.clone();
 
         tokio::spawn(async move {
             let span = Span::current();
@@ -488,20 +4597,50 @@ impl<M: MdnsBrowser + 'static> ServiceManager<M> {
             match client.stop_service(&name).await {
                 Ok(reason) => {
                     info!(reason = ?reason, "Service stopped successfully");
-                    app_handler.on_service_stopped(&name, reason).await;
+                    if let Err(panic) =
+                        catch_panic(app_handler.on_service_stopped(&name, reason)).await
+                    {
+                        // The service is already gone, so only the report applies.
+                        handle_handler_panic(
+                            &client,
+                            &app_handler,
+                            HandlerPanicPolicy::Keep,
+                            &name,
+                            &panic,
+                        )
+                        .await;
+                    }
                 }
                 Err(e) => {
                     debug!(error = %e, "Error stopping service (it may have already been removed)");
                 }
             }
+
+            // Fail over to the best standby, unless a replica is still active.
+            if let Some((standby, config)) = failover {
+                let identity = standby.identity(identity_key.as_deref()).to_string();
+                if client
+                    .service_replicas(&identity)
+                    .await
+                    .is_ok_and(|replicas| replicas.is_empty())
+                {
+                    info!("Failing over '{}' to '{}'", identity, standby.fullname);
+                    if let Err(e) = client.launch_synthetic(standby, config).await {
+                        warn!(error = %e, "Failed to launch standby replica");
+                    }
+                }
+            }
         });
     }
 }
 
 pub struct ZeroMcp {
     client: ZeroClient,
+    discovery: mpsc::UnboundedSender<DiscoveryCommand>,
     // this handle will resolve when the manager finishes (signal or error)
     task: JoinHandle<anyhow::Result<()>>,
+    // auxiliary tasks (e.g. the status server) that live as long as the manager
+    pub(crate) background_tasks: Vec<JoinHandle<()>>,
 }
 
 impl ZeroMcp {
@@ -510,10 +4649,94 @@ impl ZeroMcp {
         &self.client
     }
 
-    /// Signal the manager to shut down (if you build in a shutdown channel).
+    /// Checks that discovery and the service actor are working, and reports
+    /// services that aren't healthy.
+    ///
+    /// Fails only if the actor doesn't answer within [`SELF_TEST_TIMEOUT`]; every
+    /// other problem is recorded in the report. See [`SelfTestReport::is_ok`].
+    pub async fn self_test(&self) -> Result<SelfTestReport> {
+        let (probe_tx, probe_rx) = oneshot::channel();
+        let discovery = match self.discovery.send(DiscoveryCommand::Probe(probe_tx)) {
+            Ok(()) => tokio::time::timeout(SELF_TEST_TIMEOUT, probe_rx)
+                .await
+                .ok()
+                .and_then(Result::ok),
+            Err(_) => None,
+        };
+
+        let started = Instant::now();
+        let health = tokio::time::timeout(SELF_TEST_TIMEOUT, self.client.health_summary())
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Service actor did not respond within {:?}",
+                    SELF_TEST_TIMEOUT
+                )
+            })??;
+        let actor_round_trip = started.elapsed();
+
+        Ok(SelfTestReport {
+            discovery,
+            actor_round_trip,
+            active_services: health.len(),
+            impaired_services: health
+                .into_iter()
+                .filter(|(_, status)| status.state != HealthState::Healthy)
+                .collect(),
+        })
+    }
+
+    /// Exports the identities and configurations of the active services, and
+    /// the inputs cached for them, for [`start_from_state`] to reconnect to
+    /// after a restart.
+    pub async fn export_state(&self) -> Result<ManagerState> {
+        self.client.export_state().await
+    }
+
+    /// Reconnects to the services in `state`, with its cached inputs.
+    pub(crate) fn restore(&self, state: ManagerState) -> Result<()> {
+        if let Some(inputs) = &self.client.inputs {
+            inputs.restore(state.inputs);
+        }
+        for saved in state.services {
+            self.discovery
+                .send(DiscoveryCommand::Restore(Box::new(saved)))
+                .map_err(|_| anyhow!("Discovery is no longer running."))?;
+        }
+        Ok(())
+    }
+
+    /// Stops the background tasks and discovery, then every running service,
+    /// so no child processes are left behind, and finally the manager itself.
+    ///
+    /// Returns the error that ended discovery early, if any; services are
+    /// stopped either way.
     pub async fn shutdown(self) -> anyhow::Result<()> {
-        // e.g. drop client, send shutdown, await task.
-        self.task.await?
+        for task in &self.background_tasks {
+            task.abort();
+        }
+        // Discovery may already have ended with an error, which the task returns.
+        let _ = self.discovery.send(DiscoveryCommand::Shutdown);
+        let result = match self.task.await {
+            Ok(result) => result,
+            Err(e) => Err(anyhow!(e).context("Service discovery task failed")),
+        };
+        let client = self.client.internal();
+        if let Err(e) = client.begin_shutdown().await {
+            warn!(error = %e, "Failed to mark the manager as shutting down");
+        }
+        match client.stop_all_services().await {
+            Ok(outcomes) => {
+                for (name, outcome) in outcomes {
+                    if let Err(e) = outcome {
+                        warn!(error = %e, "Service '{}' did not stop cleanly", name);
+                    }
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to stop services during shutdown"),
+        }
+        self.client.actor.stop(None);
+        result
     }
 }
 
@@ -523,16 +4746,86 @@ pub async fn start<H, F>(config: ZeroConfig, make_handler: F) -> Result<ZeroMcp>
 where
     H: ZeroHandler + 'static,
     F: FnOnce(ZeroClient) -> Arc<H>,
+{
+    start_with(config, |client| {
+        std::future::ready(Ok(make_handler(client)))
+    })
+    .await
+}
+
+/// Start ZeroMCP like [`start`], with a handler factory that can await and
+/// fail, e.g. to open a database or read a file before the handler exists.
+///
+/// If `make_handler` fails, the manager is stopped again before any service
+/// is discovered, and its error is returned.
+///
+/// # Arguments
+/// * `config` - The configuration to run with.
+/// * `make_handler` - Builds the application handler from the client.
+pub async fn start_with<H, F, Fut>(config: ZeroConfig, make_handler: F) -> Result<ZeroMcp>
+where
+    H: ZeroHandler + 'static,
+    F: FnOnce(ZeroClient) -> Fut,
+    Fut: Future<Output = Result<Arc<H>>>,
 {
     let mdns = ServiceDaemon::new()?;
-    start_with_mdns(config, make_handler, mdns).await
+    let make_handler =
+        move |client| async move { Ok(make_handler(client).await? as Arc<dyn ZeroHandler>) };
+    start_with_hooks(config, make_handler, mdns, ManagerHooks::default()).await
+}
+
+/// Start ZeroMCP and eagerly reconnect to the services in `state`, as exported
+/// by [`ZeroMcp::export_state`] before a restart.
+///
+/// Discovery runs as usual: a restored service that is rediscovered unchanged
+/// is left alone, while a changed one is relaunched under `change_policy`.
+/// Saved services are checked like discovered ones, against the
+/// `host_allowlist` and their mapping's `required_capabilities` and `filter`,
+/// and skipped if they no longer pass. Saved static services are skipped too,
+/// since `config` connects them anyway.
+///
+/// Use [`ZeroMcpBuilder::restore`](crate::ZeroMcpBuilder::restore) to restore
+/// with other builder options.
+pub async fn start_from_state<H, F>(
+    state: ManagerState,
+    config: ZeroConfig,
+    make_handler: F,
+) -> Result<ZeroMcp>
+where
+    H: ZeroHandler + 'static,
+    F: FnOnce(ZeroClient) -> Arc<H>,
+{
+    crate::ZeroMcpBuilder::new()
+        .config(config)
+        .restore(state)
+        .start(make_handler)
+        .await
 }
 
-/// Start ZeroMCP with a specific `MdnsBrowser` implementation.
+/// Start ZeroMCP with a specific `MdnsBrowser` implementation, e.g. a mock in
+/// tests or a discovery mechanism other than `mdns_sd`. [`start`] is this with
+/// a real `mdns_sd::ServiceDaemon`.
 ///
-/// This is the core startup logic, made generic for testability. The public `start`
-/// function provides the real `mdns_sd::ServiceDaemon`.
-pub(crate) async fn start_with_mdns<H, F, M>(
+/// The browser's contract:
+/// - `browse(&self, service_type)` is called once per configured
+///   `zeroconf_service` at startup, with the type as written in the config
+///   (e.g. `"_mcp._tcp.local."`), or its subtype query if the mapping sets a
+///   `subtype` (e.g. `"_ai._sub._mcp._tcp.local."`), and returns the receiver
+///   of its events. It is called again for the same type on a refresh or to
+///   resume browsing.
+/// - Each `ServiceEvent::ServiceResolved` launches (or updates) the service;
+///   its `ServiceInfo::get_type`, or `get_subtype` for a subtype query, must
+///   equal `service_type` for it to match the mapping.
+///   `ServiceEvent::ServiceRemoved` stops it again.
+/// - An error from the first `browse` stops discovery. A receiver that
+///   disconnects later is browsed again with backoff, so a browser with
+///   nothing more to report should keep its sender alive.
+///
+/// # Arguments
+/// * `config` - The configuration to run with.
+/// * `make_handler` - Builds the application handler from the client.
+/// * `mdns` - The browser services are discovered through.
+pub async fn start_with_mdns<H, F, M>(
     config: ZeroConfig,
     make_handler: F,
     mdns: M,
@@ -542,25 +4835,1025 @@ where
     F: FnOnce(ZeroClient) -> Arc<H>,
     M: MdnsBrowser + 'static,
 {
-    let (actor, _handle) = Actor::spawn(None, ServiceActor, ()).await?;
+    let make_handler =
+        move |client| std::future::ready(Ok(make_handler(client) as Arc<dyn ZeroHandler>));
+    start_with_hooks(config, make_handler, mdns, ManagerHooks::default()).await
+}
+
+/// Start ZeroMCP with a specific `MdnsBrowser` and optional manager hooks.
+pub(crate) async fn start_with_hooks<F, Fut, M>(
+    config: ZeroConfig,
+    make_handler: F,
+    mdns: M,
+    hooks: ManagerHooks,
+) -> Result<ZeroMcp>
+where
+    F: FnOnce(ZeroClient) -> Fut,
+    Fut: Future<Output = Result<Arc<dyn ZeroHandler>>>,
+    M: MdnsBrowser + 'static,
+{
+    let (active_set_tx, mut active_set_rx) = watch::channel(Vec::new());
+    let (discovery_tx, discovery_rx) = mpsc::unbounded_channel();
+    let handler_cell: Arc<OnceLock<Arc<dyn ZeroHandler>>> = Arc::default();
+    let liveness = Arc::new(Notify::new());
+    let metrics = hooks
+        .metrics
+        .as_ref()
+        .map_or_else(Metrics::default, |sink| Metrics::new(sink.clone()));
+    let args = ServiceActorArgs {
+        config: config.clone(),
+        active_set: active_set_tx,
+        discovery: discovery_tx.clone(),
+        handler: handler_cell.clone(),
+        metrics: metrics.clone(),
+        liveness: liveness.clone(),
+    };
+    let (actor, _handle) = Actor::spawn(None, ServiceActor, args).await?;
+
+    let mut client = ZeroClient::new(actor, &config);
+    client.metrics = metrics;
 
-    let client = ZeroClient {
-        actor: actor.clone(),
+    let handler = match make_handler(client.clone()).await {
+        Ok(handler) => handler,
+        Err(e) => {
+            client.actor.stop(None);
+            return Err(e.context("failed to create the handler"));
+        }
     };
+    let _ = handler_cell.set(handler.clone());
+
+    // Deliver active-set changes off the actor, so slow handlers don't stall it.
+    let active_set_handler = handler.clone();
+    let active_set_task = tokio::spawn(async move {
+        while active_set_rx.changed().await.is_ok() {
+            let active = active_set_rx.borrow_and_update().clone();
+            if let Err(panic) = catch_panic(active_set_handler.on_active_set_changed(active)).await
+            {
+                error!(panic = %panic, "on_active_set_changed panicked");
+            }
+        }
+    });
 
-    let handler = make_handler(client.clone());
+    // Only calls made through the returned client are subject to admission.
+    let internal = client.internal();
+    let mut background_tasks = vec![active_set_task];
+    let app_handler: Arc<dyn ZeroHandler> = handler.clone();
+    background_tasks.extend(crate::schedule::spawn(
+        &config.schedules,
+        &internal,
+        &app_handler,
+    ));
+    background_tasks.push(crate::liveness::spawn(
+        &config,
+        &internal,
+        &app_handler,
+        liveness,
+    ));
 
     let manager = ServiceManager {
-        actor,
+        client: internal,
         config,
         mdns,
         app_handler: handler,
+        hooks,
+        discovery_rx: tokio::sync::Mutex::new(discovery_rx),
+        discovery_tx: discovery_tx.clone(),
     };
 
     let handle = tokio::spawn(async move { manager.run().await });
 
     Ok(ZeroMcp {
         client,
+        discovery: discovery_tx,
         task: handle,
+        background_tasks,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ServiceEventHandler, UserInputProvider};
+    use tokio::io::{AsyncWriteExt, DuplexStream};
+
+    /// Answers MCP requests on `io` like a minimal server, taking `delay` to
+    /// list or call its tools.
+    async fn fake_server(io: DuplexStream, delay: Duration) {
+        let (read, mut write) = tokio::io::split(io);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let message: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = message.get("id").cloned() else {
+                continue;
+            };
+            let result = match message["method"].as_str() {
+                Some("initialize") => json!({
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "fake", "version": "0.0.0" },
+                }),
+                Some("tools/list") => {
+                    tokio::time::sleep(delay).await;
+                    json!({ "tools": [] })
+                }
+                Some("tools/call") => {
+                    tokio::time::sleep(delay).await;
+                    json!({ "content": [] })
+                }
+                Some("completion/complete") => json!({
+                    "completion": { "values": [message["params"]["argument"]["value"]] },
+                }),
+                _ => json!({}),
+            };
+            let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            let mut response = serde_json::to_vec(&response).unwrap();
+            response.push(b'\n');
+            write.write_all(&response).await.unwrap();
+        }
+    }
+
+    async fn fake_service(name: &str, delay: Duration) -> ActiveService {
+        fake_service_for(name, delay, None).await
+    }
+
+    /// As [`fake_service`], with a server that goes away after `uptime`, if given.
+    async fn fake_service_for(
+        name: &str,
+        delay: Duration,
+        uptime: Option<Duration>,
+    ) -> ActiveService {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            match uptime {
+                Some(uptime) => {
+                    let _ = tokio::time::timeout(uptime, fake_server(server_io, delay)).await;
+                }
+                None => fake_server(server_io, delay).await,
+            }
+        });
+        let transport =
+            IntoTransport::<RoleClient, std::io::Error, TransportAdapterAsyncRW>::into_transport(
+                tokio::io::split(client_io),
+            );
+        let client = ().into_dyn().serve(transport).await.unwrap();
+        let config: McpConfig = serde_json::from_value(
+            json!({ "protocol": "sse", "name": "fake", "url": "http://localhost" }),
+        )
+        .unwrap();
+        ActiveService {
+            client,
+            info: DiscoveredService::new_static(name),
+            config,
+            pid: None,
+            child: None,
+            session_id: None,
+            health: HealthTracker::default(),
+            tools_baseline: None,
+            prompts_snapshot: None,
+            progress: ProgressRouter::default(),
+            roots: Arc::default(),
+            calls: CallQueue::new(None),
+            limit: CallLimit::default(),
+            context: json!({}),
+            traffic: broadcast::channel(1).0,
+            startup: StartupTimings::default(),
+            last_active: Instant::now(),
+            last_health_check: Instant::now(),
+        }
+    }
+
+    /// Records the hooks a supervisor calls, in order.
+    #[derive(Default)]
+    struct HookLog(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl ServiceEventHandler for HookLog {
+        async fn on_service_started(&self, _service: &DiscoveredService) {}
+        async fn on_service_stopped(&self, _service_name: &str, _reason: QuitReason) {
+            self.0.lock().unwrap().push("stopped".to_string());
+        }
+        async fn on_service_error(&self, _service_name: &str, error: &anyhow::Error) {
+            self.0.lock().unwrap().push(format!("error: {error:#}"));
+        }
+    }
+    #[async_trait::async_trait]
+    impl UserInputProvider for HookLog {
+        async fn request_input(&self, _service_name: &str, _key: &str) -> Result<String> {
+            bail!("no input in tests")
+        }
+    }
+    impl ZeroHandler for HookLog {}
+
+    /// Adds `service` to the actor behind `client` and returns what's needed
+    /// to supervise it under `policy`.
+    fn supervised(
+        client: &ZeroClient,
+        service: ActiveService,
+        policy: RestartPolicy,
+    ) -> Supervision {
+        let supervision = Supervision {
+            peer: service.client.peer().clone(),
+            pid: service.pid,
+            policy,
+            recovery: Recovery::Reconnect,
+            context: json!({}),
+        };
+        client
+            .actor
+            .cast(ServiceMessage::AddService {
+                name: service.info.fullname.clone(),
+                service: Box::new(service),
+            })
+            .unwrap();
+        supervision
+    }
+
+    /// Starts an actor tracking a fake service for each name and delay.
+    async fn client_with(services: &[(&str, Duration)]) -> ZeroClient {
+        client_with_handler(services, None).await
+    }
+
+    /// As [`client_with`], with `handler` as the application's handler.
+    async fn client_with_handler(
+        services: &[(&str, Duration)],
+        handler: Option<Arc<dyn ZeroHandler>>,
+    ) -> ZeroClient {
+        let config: ZeroConfig = toml::from_str("").unwrap();
+        let (active_set, _) = watch::channel(Vec::new());
+        let (discovery, _discovery_rx) = mpsc::unbounded_channel();
+        let args = ServiceActorArgs {
+            config: config.clone(),
+            active_set,
+            discovery,
+            handler: Arc::new(handler.map(OnceLock::from).unwrap_or_default()),
+            metrics: Default::default(),
+            liveness: Default::default(),
+        };
+        let (actor, _handle) = Actor::spawn(None, ServiceActor, args).await.unwrap();
+        let client = ZeroClient::new(actor, &config);
+        for &(name, delay) in services {
+            let service = fake_service(name, delay).await;
+            client
+                .actor
+                .cast(ServiceMessage::AddService {
+                    name: service.info.fullname.clone(),
+                    service: Box::new(service),
+                })
+                .unwrap();
+        }
+        client
+    }
+
+    #[tokio::test]
+    async fn test_slow_service_does_not_block_others() {
+        let client =
+            client_with(&[("slow", Duration::from_secs(30)), ("fast", Duration::ZERO)]).await;
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move { client.list_all_tools("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let fast = tokio::time::timeout(Duration::from_secs(5), client.list_all_tools("fast"))
+            .await
+            .expect("listing the fast service was blocked by the slow one");
+        assert!(fast.unwrap().is_empty());
+        assert!(!slow.is_finished());
+        slow.abort();
+    }
+
+    #[tokio::test]
+    async fn test_internal_calls_bypass_admission() {
+        let tracked = client_with(&[("slow", Duration::from_secs(30))]).await;
+        let config: ZeroConfig =
+            toml::from_str("max_pending_requests = 1\noverload_policy = \"error\"").unwrap();
+        let client = ZeroClient::new(tracked.actor.clone(), &config);
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move { client.list_all_tools("slow").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let err = client.claim_launch("new").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ZeroError>(),
+            Some(ZeroError::Overloaded { limit: 1 })
+        ));
+        assert!(
+            client
+                .internal()
+                .claim_launch("new")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        slow.abort();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_tool_call_fails_with_typed_error() {
+        let client = client_with(&[("slow", Duration::from_secs(30))]).await;
+        let cancel = CancellationToken::new();
+        let call = tokio::spawn({
+            let client = client.clone();
+            let cancel = cancel.clone();
+            let params = CallToolRequestParam {
+                name: "wait".into(),
+                arguments: None,
+            };
+            async move {
+                client
+                    .call_tool_cancellable("slow", params, None, cancel)
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.cancel();
+        let error = tokio::time::timeout(Duration::from_secs(5), call)
+            .await
+            .expect("cancelling didn't end the call")
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(ZeroError::ToolCallCancelled { tool }) if tool == "wait"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_joined_idempotent_call_shares_typed_error() {
+        let client = client_with(&[("slow", Duration::from_secs(30))]).await;
+        let params = |order: u32| CallToolRequestParam {
+            name: "order".into(),
+            arguments: json!({ "order": order }).as_object().cloned(),
+        };
+        let call = |order| {
+            let client = client.clone();
+            let params = params(order);
+            tokio::spawn(async move {
+                client
+                    .call_tool_idempotent(
+                        "slow",
+                        params,
+                        "order-42",
+                        Some(Duration::from_millis(200)),
+                    )
+                    .await
+            })
+        };
+        let first = call(1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let joined = call(1);
+        let mismatched = call(2);
+
+        let mismatch = mismatched.await.unwrap().unwrap_err();
+        assert!(mismatch.to_string().contains("different arguments"));
+        for waiter in [first, joined] {
+            let error = tokio::time::timeout(Duration::from_secs(5), waiter)
+                .await
+                .expect("the shared call never timed out")
+                .unwrap()
+                .unwrap_err();
+            assert!(matches!(
+                error.downcast_ref(),
+                Some(ZeroError::ToolCallTimeout { tool, .. }) if tool == "order"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_service_is_supervised_with_reconnect_policy() {
+        let mut service = fake_service("sse", Duration::ZERO).await;
+        assert!(service.supervision().is_none());
+
+        let policy = json!({ "max_retries": 2, "backoff_ms": 10 });
+        let config = |key: &str| {
+            serde_json::from_value(json!({
+                "protocol": "sse",
+                "name": "fake",
+                "url": "http://localhost",
+                key: policy,
+            }))
+            .unwrap()
+        };
+        service.config = config("restart");
+        assert!(
+            service.supervision().is_none(),
+            "restart only applies to stdio"
+        );
+        service.config = config("reconnect");
+        let supervision = service.supervision().unwrap();
+        assert_eq!(supervision.recovery, Recovery::Reconnect);
+        assert_eq!(
+            supervision.policy,
+            RestartPolicy {
+                max_retries: 2,
+                backoff_ms: 10,
+                reset_after_ms: 60_000,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_gives_up_on_a_crash_loop() {
+        let client = client_with(&[]).await;
+        let log = Arc::new(HookLog::default());
+        let app_handler: Arc<dyn ZeroHandler> = log.clone();
+        let uptime = Some(Duration::from_millis(100));
+        let service = fake_service_for("svc", Duration::ZERO, uptime).await;
+        let info = service.info.clone();
+        let policy = RestartPolicy {
+            max_retries: 3,
+            backoff_ms: 10,
+            reset_after_ms: 60_000,
+        };
+        let supervision = supervised(&client, service, policy);
+        let relaunches = std::sync::atomic::AtomicUsize::new(0);
+        let counter = &relaunches;
+        let recover = move || async move {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(fake_service_for("svc", Duration::ZERO, uptime).await)
+        };
+        let supervising = ServiceManager::<ServiceDaemon>::supervise_with(
+            &client,
+            &info,
+            &app_handler,
+            supervision,
+            recover,
+        );
+        tokio::time::timeout(Duration::from_secs(10), supervising)
+            .await
+            .expect("a crashing service was restarted indefinitely");
+        assert_eq!(relaunches.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(*log.0.lock().unwrap().last().unwrap(), "stopped");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_reports_each_attempt_then_stops() {
+        let client = client_with(&[]).await;
+        let log = Arc::new(HookLog::default());
+        let app_handler: Arc<dyn ZeroHandler> = log.clone();
+        let service =
+            fake_service_for("svc", Duration::ZERO, Some(Duration::from_millis(50))).await;
+        let info = service.info.clone();
+        let policy = RestartPolicy {
+            max_retries: 2,
+            backoff_ms: 10,
+            reset_after_ms: 60_000,
+        };
+        let supervision = supervised(&client, service, policy);
+        let recover = || async { bail!("connection refused") };
+        let supervising = ServiceManager::<ServiceDaemon>::supervise_with(
+            &client,
+            &info,
+            &app_handler,
+            supervision,
+            recover,
+        );
+        tokio::time::timeout(Duration::from_secs(5), supervising)
+            .await
+            .expect("supervision didn't give up");
+        assert_eq!(
+            *log.0.lock().unwrap(),
+            [
+                "error: connection dropped; reconnecting",
+                "error: reconnect attempt 1 failed: connection refused",
+                "error: reconnect attempt 2 failed: connection refused",
+                "error: gave up after 2 reconnect attempts: connection refused",
+                "stopped",
+            ]
+        );
+        assert!(client.ping("svc").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_removal_during_backoff_ends_supervision() {
+        let client = client_with(&[]).await;
+        let log = Arc::new(HookLog::default());
+        let app_handler: Arc<dyn ZeroHandler> = log.clone();
+        let service =
+            fake_service_for("svc", Duration::ZERO, Some(Duration::from_millis(50))).await;
+        let info = service.info.clone();
+        let policy = RestartPolicy {
+            max_retries: 3,
+            backoff_ms: 5_000,
+            reset_after_ms: 60_000,
+        };
+        let supervision = supervised(&client, service, policy);
+        let supervising = tokio::spawn({
+            let client = client.clone();
+            async move {
+                let recover = || async { bail!("relaunched a removed service") };
+                ServiceManager::<ServiceDaemon>::supervise_with(
+                    &client,
+                    &info,
+                    &app_handler,
+                    supervision,
+                    recover,
+                )
+                .await
+            }
+        });
+        // Past the crash and into the first backoff.
+        tokio::time::sleep(EXIT_POLL_INTERVAL + Duration::from_millis(300)).await;
+        assert!(client.stop_service("svc").await.is_err());
+        tokio::time::timeout(Duration::from_secs(1), supervising)
+            .await
+            .expect("supervision outlived the service's removal")
+            .unwrap();
+        assert_eq!(*log.0.lock().unwrap().last().unwrap(), "stopped");
+        assert!(client.claim_launch("svc").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_complete_returns_server_suggestions() {
+        let client = client_with(&[("svc", Duration::ZERO)]).await;
+        let params: CompleteRequestParam = serde_json::from_value(json!({
+            "ref": { "type": "ref/prompt", "name": "greet" },
+            "argument": { "name": "who", "value": "wor" },
+        }))
+        .unwrap();
+        let result = client.complete("svc", params.clone()).await.unwrap();
+        assert_eq!(result.completion.values, ["wor"]);
+
+        let error = client.complete("missing", params).await.unwrap_err();
+        assert!(error.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_ping_returns_round_trip_time() {
+        let client = client_with(&[("svc", Duration::ZERO)]).await;
+        let rtt = client.ping("svc").await.unwrap();
+        assert!(rtt < Duration::from_secs(5));
+
+        let error = client.ping("missing").await.unwrap_err();
+        assert!(error.to_string().contains("not found"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_with_retry_escalates_to_signals() {
+        let stop = |script: &'static str| async move {
+            let mut command = tokio::process::Command::new("sh");
+            command.args(["-c", script]);
+            let (output, _stdin, _stderr) = spawn_child(command).unwrap();
+            let mut service = fake_service("stdio", Duration::ZERO).await;
+            service.pid = output.id();
+            service.child = Some(output.handle());
+            service
+                .cancel_with_retry(3, Duration::from_millis(200))
+                .await
+                .unwrap()
+        };
+
+        // Ignores stdin closing, but exits on SIGTERM.
+        let outcome = stop("while :; do sleep 0.05; done").await;
+        assert!(matches!(
+            outcome,
+            StopOutcome::Cancelled { attempts: 2, .. }
+        ));
+
+        let outcome = stop("trap '' TERM; while :; do sleep 0.05; done").await;
+        assert!(matches!(outcome, StopOutcome::Killed { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_inputs_are_not_reused_by_launches() {
+        struct Answer;
+        #[async_trait::async_trait]
+        impl ServiceEventHandler for Answer {
+            async fn on_service_started(&self, _service: &DiscoveredService) {}
+            async fn on_service_stopped(&self, _service_name: &str, _reason: QuitReason) {}
+        }
+        #[async_trait::async_trait]
+        impl UserInputProvider for Answer {
+            async fn request_input(&self, _service_name: &str, _key: &str) -> Result<String> {
+                Ok("world".to_string())
+            }
+        }
+        impl ZeroHandler for Answer {}
+
+        let tracked = client_with_handler(&[("svc", Duration::ZERO)], Some(Arc::new(Answer))).await;
+        let config: ZeroConfig = toml::from_str("cache_inputs = true").unwrap();
+        let client = ZeroClient::new(tracked.actor.clone(), &config);
+        let templates = HashMap::from([("who".to_string(), "{{name}}".to_string())]);
+        // Only the rendering matters here, not whether the fake server has the prompt.
+        let _ = client.get_prompt_templated("svc", "greet", templates).await;
+
+        let seeded = |inputs: &InputCache| {
+            let mut ctx = json!({});
+            inputs.seed("svc.", &mut ctx);
+            ctx
+        };
+        assert_eq!(
+            seeded(client.prompt_inputs.as_ref().unwrap()),
+            json!({ "name": "world" })
+        );
+        assert_eq!(seeded(client.inputs.as_ref().unwrap()), json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_stops_are_forgotten_after_their_cooldown() {
+        let client = client_with(&[("other", Duration::ZERO)]).await;
+        let mut service = fake_service("svc", Duration::ZERO).await;
+        service.config = serde_json::from_value(json!({
+            "protocol": "sse",
+            "name": "fake",
+            "url": "http://localhost",
+            "relaunch_cooldown_ms": 50,
+        }))
+        .unwrap();
+        client
+            .actor
+            .cast(ServiceMessage::AddService {
+                name: service.info.fullname.clone(),
+                service: Box::new(service),
+            })
+            .unwrap();
+
+        client.stop_service("svc").await.unwrap();
+        assert!(client.stopped_at("svc.").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Without a cooldown of its own, this stop isn't remembered at all.
+        client.stop_service("other").await.unwrap();
+        assert!(client.stopped_at("svc.").await.unwrap().is_none());
+        assert!(client.stopped_at("other.").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_streaming_call_times_out_and_counts_towards_health() {
+        let client = client_with(&[]).await;
+        let mut service = fake_service("slow", Duration::from_secs(30)).await;
+        service.config = serde_json::from_value(json!({
+            "protocol": "sse",
+            "name": "fake",
+            "url": "http://localhost",
+            "tool_call_timeout_ms": 50,
+        }))
+        .unwrap();
+        client
+            .actor
+            .cast(ServiceMessage::AddService {
+                name: service.info.fullname.clone(),
+                service: Box::new(service),
+            })
+            .unwrap();
+
+        let mut stream = client
+            .call_tool_stream_text("slow", "wait", None)
+            .await
+            .unwrap();
+        let error = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("the streaming call never timed out")
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(ZeroError::ToolCallTimeout { tool, .. }) if tool == "wait"
+        ));
+        let health = client.service_health("slow").await.unwrap();
+        assert_eq!(health.consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_exported_state_leaves_out_proxies() {
+        let client = client_with(&[]).await;
+        let mut service = fake_service("svc", Duration::ZERO).await;
+        service.config = serde_json::from_value(json!({
+            "protocol": "sse",
+            "name": "fake",
+            "url": "http://localhost",
+            "proxy": { "url": "http://proxy:3128", "username": "me", "password": "secret" },
+        }))
+        .unwrap();
+        client
+            .actor
+            .cast(ServiceMessage::AddService {
+                name: service.info.fullname.clone(),
+                service: Box::new(service),
+            })
+            .unwrap();
+
+        let state = client.export_state().await.unwrap();
+        assert_eq!(state.services.len(), 1);
+        assert!(state.services[0].config.proxy().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_stops_run_the_hook_once() {
+        #[derive(Default)]
+        struct StopCounter(std::sync::atomic::AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl ServiceEventHandler for StopCounter {
+            async fn on_service_discovered(&self, _: &DiscoveredService, _: &McpConfig) {}
+            async fn on_service_started(&self, _service: &DiscoveredService) {}
+            async fn on_service_stopped(&self, _service_name: &str, _reason: QuitReason) {}
+            async fn on_before_service_stop(&self, _service_name: &str) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        #[async_trait::async_trait]
+        impl UserInputProvider for StopCounter {
+            async fn request_input(&self, _service_name: &str, _key: &str) -> Result<String> {
+                bail!("no input in tests")
+            }
+        }
+        impl ZeroHandler for StopCounter {}
+
+        let counter = Arc::new(StopCounter::default());
+        let client = client_with_handler(&[("svc", Duration::ZERO)], Some(counter.clone())).await;
+        let (first, second) = tokio::join!(client.stop_service("svc"), client.stop_service("svc"));
+        assert!(
+            first.is_ok() != second.is_ok(),
+            "both or neither stop succeeded"
+        );
+        assert_eq!(counter.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(client.stop_service("svc").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failing_handler_factory_fails_start() {
+        struct NoBrowser;
+        impl MdnsBrowser for NoBrowser {
+            fn browse(&self, _service_type: &str) -> Result<mdns_sd::Receiver<ServiceEvent>> {
+                bail!("not browsing")
+            }
+        }
+
+        let config: ZeroConfig = toml::from_str("").unwrap();
+        let make_handler =
+            |_client| async { Err::<Arc<dyn ZeroHandler>, _>(anyhow!("database unavailable")) };
+        let Err(error) =
+            start_with_hooks(config, make_handler, NoBrowser, ManagerHooks::default()).await
+        else {
+            panic!("start succeeded without a handler");
+        };
+        assert_eq!(error.root_cause().to_string(), "database unavailable");
+    }
+
+    /// Resolves whatever is sent on its channel, for every service type.
+    struct ChannelBrowser(mdns_sd::Receiver<ServiceEvent>);
+
+    impl MdnsBrowser for ChannelBrowser {
+        fn browse(&self, _service_type: &str) -> Result<mdns_sd::Receiver<ServiceEvent>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// Counts launch attempts and the port of each, vetoing them so nothing
+    /// is actually spawned.
+    #[derive(Default)]
+    struct LaunchCounter(
+        std::sync::atomic::AtomicUsize,
+        Duration,
+        std::sync::Mutex<Vec<u16>>,
+    );
+
+    #[async_trait::async_trait]
+    impl ServiceEventHandler for LaunchCounter {
+        async fn on_service_discovered(&self, service: &DiscoveredService, _config: &McpConfig) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.2.lock().unwrap().push(service.port);
+        }
+        async fn on_service_started(&self, _service: &DiscoveredService) {}
+        async fn on_service_stopped(&self, _service_name: &str, _reason: QuitReason) {}
+        async fn on_launch(&self, _service_name: &str, _launch: RenderedLaunch) -> Result<()> {
+            tokio::time::sleep(self.1).await;
+            bail!("not launching in tests")
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl UserInputProvider for LaunchCounter {
+        async fn request_input(&self, _service_name: &str, _key: &str) -> Result<String> {
+            bail!("no input in tests")
+        }
+    }
+
+    impl ZeroHandler for LaunchCounter {}
+
+    #[tokio::test]
+    async fn test_flapping_service_is_not_relaunched() {
+        let config: ZeroConfig = toml::from_str(
+            r#"
+            debounce_ms = 200
+            [[service_mapping]]
+            zeroconf_service = "_flap._tcp.local."
+            protocol = "stdio"
+            name = "Flap"
+            command = "true"
+            args = []
+            "#,
+        )
+        .unwrap();
+        let (events, rx) = flume::unbounded();
+        // The first launch is still in flight when the service flaps.
+        let counter = Arc::new(LaunchCounter(
+            Default::default(),
+            Duration::from_millis(300),
+            Default::default(),
+        ));
+        let handler: Arc<dyn ZeroHandler> = counter.clone();
+        let zeromcp = start_with_hooks(
+            config,
+            |_client| std::future::ready(Ok(handler)),
+            ChannelBrowser(rx),
+            ManagerHooks::default(),
+        )
+        .await
+        .unwrap();
+        let launches = || counter.0.load(std::sync::atomic::Ordering::SeqCst);
+        let info =
+            mdns_sd::ServiceInfo::new("_flap._tcp.local.", "a", "a.local.", "", 1, None).unwrap();
+        let resolved = || ServiceEvent::ServiceResolved(info.clone());
+        let removed = || {
+            ServiceEvent::ServiceRemoved(
+                "_flap._tcp.local.".to_string(),
+                "a._flap._tcp.local.".to_string(),
+            )
+        };
+
+        events.send(resolved()).unwrap();
+        events.send(removed()).unwrap();
+        events.send(resolved()).unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(
+            launches(),
+            1,
+            "a resolve within the debounce window relaunched"
+        );
+
+        events.send(removed()).unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        events.send(resolved()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            launches(),
+            2,
+            "a resolve after the removal took effect didn't relaunch"
+        );
+
+        zeromcp.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_launch_is_retried_on_unchanged_resolution() {
+        let config: ZeroConfig = toml::from_str(
+            r#"
+            [[service_mapping]]
+            zeroconf_service = "_retry._tcp.local."
+            protocol = "stdio"
+            name = "Retry"
+            command = "true"
+            args = []
+            "#,
+        )
+        .unwrap();
+        let (events, rx) = flume::unbounded();
+        let counter = Arc::new(LaunchCounter::default());
+        let handler: Arc<dyn ZeroHandler> = counter.clone();
+        let zeromcp = start_with_hooks(
+            config,
+            |_client| std::future::ready(Ok(handler)),
+            ChannelBrowser(rx),
+            ManagerHooks::default(),
+        )
+        .await
+        .unwrap();
+        let info =
+            mdns_sd::ServiceInfo::new("_retry._tcp.local.", "a", "a.local.", "", 1, None).unwrap();
+
+        events
+            .send(ServiceEvent::ServiceResolved(info.clone()))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        events.send(ServiceEvent::ServiceResolved(info)).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(counter.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        zeromcp.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_changed_service_relaunches_after_launch_in_flight() {
+        let config: ZeroConfig = toml::from_str(
+            r#"
+            [[service_mapping]]
+            zeroconf_service = "_dup._tcp.local."
+            protocol = "stdio"
+            name = "Dup"
+            command = "true"
+            args = []
+            "#,
+        )
+        .unwrap();
+        let (events, rx) = flume::unbounded();
+        let counter = Arc::new(LaunchCounter(
+            Default::default(),
+            Duration::from_millis(300),
+            Default::default(),
+        ));
+        let handler: Arc<dyn ZeroHandler> = counter.clone();
+        let zeromcp = start_with_hooks(
+            config,
+            |_client| std::future::ready(Ok(handler)),
+            ChannelBrowser(rx),
+            ManagerHooks::default(),
+        )
+        .await
+        .unwrap();
+        let resolved = |port| {
+            let info =
+                mdns_sd::ServiceInfo::new("_dup._tcp.local.", "a", "a.local.", "", port, None)
+                    .unwrap();
+            ServiceEvent::ServiceResolved(info)
+        };
+
+        // The second resolve arrives while the first launch is still in
+        // flight, and is launched once that one ends.
+        events.send(resolved(1)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        events.send(resolved(2)).unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(*counter.2.lock().unwrap(), [1]);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(*counter.2.lock().unwrap(), [1, 2]);
+
+        zeromcp.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_call_limit_waits_for_a_free_slot() {
+        let limit = CallLimit::new(Some(1));
+        let first = limit.acquire().await.unwrap();
+        assert!(first.is_some());
+        let waiting = tokio::time::timeout(Duration::from_millis(50), limit.acquire()).await;
+        assert!(waiting.is_err(), "a second request got past a limit of 1");
+        drop(first);
+        let second = tokio::time::timeout(Duration::from_secs(5), limit.acquire()).await;
+        assert!(second.unwrap().unwrap().is_some());
+
+        assert!(CallLimit::default().acquire().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_call_queue_bounds_outstanding_calls() {
+        let queue = CallQueue::new(Some(2));
+        let first = queue.reserve("svc").unwrap();
+        let second = queue.reserve("svc").unwrap();
+        let full = queue.reserve("svc").unwrap_err();
+        assert!(matches!(
+            full.downcast_ref::<ZeroError>(),
+            Some(ZeroError::QueueFull { depth: 2, .. })
+        ));
+
+        let drained = queue.drained();
+        drop(first);
+        let third = queue.reserve("svc").unwrap();
+        drop(second);
+        drop(third);
+        tokio::time::timeout(Duration::from_secs(5), drained)
+            .await
+            .expect("queue never drained");
+    }
+
+    #[tokio::test]
+    async fn test_draining_refuses_calls_and_waits_for_requests() {
+        let client = client_with(&[("svc", Duration::from_millis(300))]).await;
+        let listing = tokio::spawn({
+            let client = client.clone();
+            async move { client.list_all_tools("svc").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let drain = tokio::spawn({
+            let client = client.clone();
+            async move { client.drain_calls("svc.").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!drain.is_finished());
+
+        let params = CallToolRequestParam {
+            name: "echo".into(),
+            arguments: None,
+        };
+        let refused = client.call_tool("svc", params.clone()).await.unwrap_err();
+        assert!(matches!(
+            refused.downcast_ref(),
+            Some(ZeroError::Draining { service }) if service == "svc."
+        ));
+        let drained = tokio::time::timeout(Duration::from_secs(5), drain)
+            .await
+            .expect("the listing was never drained");
+        assert!(drained.unwrap().unwrap());
+        assert!(listing.is_finished());
+        assert!(!client.drain_calls("gone.").await.unwrap());
+
+        client.resume_calls("svc.").await.unwrap();
+        let result = client.call_tool("svc", params).await;
+        assert!(!matches!(
+            result.as_ref().err().and_then(|e| e.downcast_ref()),
+            Some(ZeroError::Draining { .. })
+        ));
+    }
+}
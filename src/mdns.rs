@@ -0,0 +1,39 @@
+//! The mDNS browsing the service manager relies on, abstracted so tests and
+//! embedders can supply their own discovery source.
+
+use anyhow::Result;
+use mdns_sd::{DaemonStatus, Receiver, ServiceDaemon, ServiceEvent};
+
+/// Browses for services of a given type, as [`ServiceDaemon`] does.
+pub trait MdnsBrowser: Send + Sync {
+    /// Starts browsing for `service_type`, returning a channel of its events.
+    fn browse(&self, service_type: &str) -> Result<Receiver<ServiceEvent>>;
+
+    /// Stops an earlier browse for `service_type`, so browsing it again
+    /// doesn't leave the old query running. Its channel receives
+    /// `ServiceEvent::SearchStopped` and then closes. Does nothing by default.
+    fn stop_browse(&self, _service_type: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Asks the daemon whether it's still running, for `ZeroMcp::self_test`,
+    /// returning a channel for its answer. Browsers without a daemon to ask
+    /// return `None`, the default, and count as running.
+    fn status(&self) -> Result<Option<Receiver<DaemonStatus>>> {
+        Ok(None)
+    }
+}
+
+impl MdnsBrowser for ServiceDaemon {
+    fn browse(&self, service_type: &str) -> Result<Receiver<ServiceEvent>> {
+        Ok(ServiceDaemon::browse(self, service_type)?)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> Result<()> {
+        Ok(ServiceDaemon::stop_browse(self, service_type)?)
+    }
+
+    fn status(&self) -> Result<Option<Receiver<DaemonStatus>>> {
+        Ok(Some(ServiceDaemon::status(self)?))
+    }
+}
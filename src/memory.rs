@@ -0,0 +1,349 @@
+use crate::error::ZeroError;
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+
+/// How much memory cached listings and unread stream fragments take up, as
+/// returned by `ZeroClient::memory_stats`.
+///
+/// Sizes are estimates based on each listing's JSON encoding.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryStats {
+    /// Approximate bytes held by cached listings across all services.
+    pub cached_bytes: usize,
+    /// Number of cached listings.
+    pub cached_listings: usize,
+    /// Approximate bytes of streaming tool call fragments not yet read by
+    /// their caller.
+    pub buffered_bytes: usize,
+    /// The configured `memory_budget_bytes`, if any.
+    pub budget_bytes: Option<usize>,
+    /// Listings evicted, or streams' unread fragments shed, to stay within
+    /// the budget since startup.
+    pub evictions: u64,
+    /// Approximate bytes freed by those evictions.
+    pub evicted_bytes: u64,
+}
+
+/// A cached listing, with its approximate size and when it was last used.
+#[derive(Debug)]
+pub(crate) struct Cached<T> {
+    value: T,
+    bytes: usize,
+    last_used: Instant,
+}
+
+impl<T: Serialize> Cached<T> {
+    pub(crate) fn new(value: T) -> Self {
+        let bytes = serde_json::to_vec(&value).map_or(0, |encoded| encoded.len());
+        Self {
+            value,
+            bytes,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+impl<T> Cached<T> {
+    /// Returns the value, marking it as recently used.
+    pub(crate) fn get(&mut self) -> &T {
+        self.last_used = Instant::now();
+        &self.value
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn last_used(&self) -> Instant {
+        self.last_used
+    }
+}
+
+/// The memory budget as seen by fragment buffers, which fill up off the
+/// manager: lets a sender ask the manager to enforce the budget as soon as
+/// unread fragments push memory past it.
+pub(crate) struct FragmentBudget {
+    limit: usize,
+    /// Unread fragment bytes across every stream.
+    buffered: AtomicUsize,
+    /// Bytes of cached listings, as of the last time the budget was enforced.
+    cached: AtomicUsize,
+    /// Set once enforcement has been asked for, until it has run.
+    requested: AtomicBool,
+    enforce: Box<dyn Fn() + Send + Sync>,
+}
+
+impl FragmentBudget {
+    /// Creates a budget of `limit` bytes that calls `enforce` when unread
+    /// fragments take memory past it.
+    pub(crate) fn new(limit: usize, enforce: impl Fn() + Send + Sync + 'static) -> Self {
+        Self {
+            limit,
+            buffered: AtomicUsize::new(0),
+            cached: AtomicUsize::new(0),
+            requested: AtomicBool::new(false),
+            enforce: Box::new(enforce),
+        }
+    }
+
+    /// Records that the budget has just been enforced, with `cached` bytes of
+    /// listings left.
+    pub(crate) fn enforced(&self, cached: usize) {
+        self.cached.store(cached, Ordering::Relaxed);
+        self.requested.store(false, Ordering::Relaxed);
+    }
+
+    fn grow(&self, bytes: usize) {
+        let buffered = self.buffered.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if buffered + self.cached.load(Ordering::Relaxed) > self.limit
+            && !self.requested.swap(true, Ordering::Relaxed)
+        {
+            (self.enforce)();
+        }
+    }
+
+    fn shrink(&self, bytes: usize) {
+        self.buffered.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for FragmentBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FragmentBudget")
+            .field("limit", &self.limit)
+            .field("buffered", &self.buffered)
+            .field("cached", &self.cached)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Fragments a streaming tool call received that its caller hasn't read yet.
+#[derive(Debug, Default)]
+pub(crate) struct FragmentBuffer {
+    fragments: Mutex<Fragments>,
+    budget: Option<Arc<FragmentBudget>>,
+}
+
+#[derive(Debug, Default)]
+struct Fragments {
+    queue: VecDeque<(Result<String>, Instant)>,
+    bytes: usize,
+    closed: bool,
+    /// Set once unread fragments were shed, after which no more are taken.
+    shed: bool,
+    waker: Option<Waker>,
+}
+
+impl FragmentBuffer {
+    /// Creates a buffer whose fragments count against `budget`, if any.
+    pub(crate) fn new(budget: Option<Arc<FragmentBudget>>) -> Self {
+        Self {
+            fragments: Mutex::default(),
+            budget,
+        }
+    }
+
+    /// Returns the next item, or `None` once the sender is gone and every
+    /// item has been read.
+    pub(crate) fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<Result<String>>> {
+        let mut fragments = self.lock();
+        if let Some((item, _)) = fragments.queue.pop_front() {
+            let bytes = item.as_ref().map_or(0, String::len);
+            fragments.bytes -= bytes;
+            if let Some(budget) = &self.budget {
+                budget.shrink(bytes);
+            }
+            return Poll::Ready(Some(item));
+        }
+        if fragments.closed {
+            return Poll::Ready(None);
+        }
+        fragments.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Returns the bytes of unread fragments and when the oldest arrived, if
+    /// there are any.
+    pub(crate) fn usage(&self) -> Option<(usize, Instant)> {
+        let fragments = self.lock();
+        let (_, oldest) = fragments.queue.iter().find(|(item, _)| item.is_ok())?;
+        Some((fragments.bytes, *oldest))
+    }
+
+    /// Drops the unread fragments and ends the stream with
+    /// `ZeroError::FragmentsShed`, so the caller knows its output is
+    /// incomplete, keeping any earlier error. Returns their approximate size.
+    pub(crate) fn shed(&self) -> usize {
+        let mut fragments = self.lock();
+        fragments.queue.retain(|(item, _)| item.is_err());
+        let bytes = std::mem::take(&mut fragments.bytes);
+        if let Some(budget) = &self.budget {
+            budget.shrink(bytes);
+        }
+        fragments.queue.push_back((
+            Err(ZeroError::FragmentsShed { bytes }.into()),
+            Instant::now(),
+        ));
+        fragments.shed = true;
+        fragments.closed = true;
+        if let Some(waker) = fragments.waker.take() {
+            waker.wake();
+        }
+        bytes
+    }
+
+    /// Queues `item`, returning `false` if the stream was shed and takes no more.
+    fn push(&self, item: Result<String>) -> bool {
+        let mut fragments = self.lock();
+        if fragments.shed {
+            return false;
+        }
+        let bytes = item.as_ref().map_or(0, String::len);
+        fragments.bytes += bytes;
+        fragments.queue.push_back((item, Instant::now()));
+        if let Some(waker) = fragments.waker.take() {
+            waker.wake();
+        }
+        drop(fragments);
+        if let Some(budget) = &self.budget {
+            budget.grow(bytes);
+        }
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Fragments> {
+        // The queue stays consistent even if a holder panicked.
+        self.fragments
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for FragmentBuffer {
+    fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            budget.shrink(self.lock().bytes);
+        }
+    }
+}
+
+/// The sending side of a [`FragmentBuffer`], which ends the stream when dropped.
+#[derive(Debug)]
+pub(crate) struct FragmentSender(pub(crate) Arc<FragmentBuffer>);
+
+impl FragmentSender {
+    /// Queues `item` for the caller, returning `false` if the stream's
+    /// fragments were shed, which ends it.
+    pub(crate) fn send(&self, item: Result<String>) -> bool {
+        self.0.push(item)
+    }
+}
+
+impl Drop for FragmentSender {
+    fn drop(&mut self) {
+        let mut fragments = self.0.lock();
+        fragments.closed = true;
+        if let Some(waker) = fragments.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Picks the least recently used entries to drop so the rest fit in `budget`.
+///
+/// Entries are `(key, bytes, last_used)`; the keys to evict are returned
+/// oldest first.
+pub(crate) fn lru_evictions<K>(mut entries: Vec<(K, usize, Instant)>, budget: usize) -> Vec<K> {
+    let mut total: usize = entries.iter().map(|(_, bytes, _)| bytes).sum();
+    entries.sort_by_key(|(_, _, last_used)| *last_used);
+    entries
+        .into_iter()
+        .take_while(|(_, bytes, _)| {
+            let over = total > budget;
+            total -= bytes;
+            over
+        })
+        .map(|(key, _, _)| key)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_lru_evictions_drops_oldest_until_within_budget() {
+        let now = Instant::now();
+        let entries = vec![
+            ("newest", 40, now + Duration::from_secs(2)),
+            ("oldest", 30, now),
+            ("middle", 50, now + Duration::from_secs(1)),
+        ];
+        assert_eq!(lru_evictions(entries.clone(), 80), vec!["oldest", "middle"]);
+        assert_eq!(lru_evictions(entries.clone(), 100), vec!["oldest"]);
+        assert!(lru_evictions(entries, 120).is_empty());
+    }
+
+    #[test]
+    fn test_shedding_fragments_ends_the_stream_with_an_error() {
+        let buffer = Arc::new(FragmentBuffer::default());
+        let sender = FragmentSender(buffer.clone());
+        assert!(sender.send(Ok("ab".to_string())));
+        assert!(sender.send(Ok("c".to_string())));
+        assert_eq!(buffer.usage().map(|(bytes, _)| bytes), Some(3));
+
+        assert!(sender.send(Err(anyhow::anyhow!("tool failed"))));
+        assert_eq!(buffer.shed(), 3);
+        assert!(buffer.usage().is_none());
+        assert!(!sender.send(Ok("later".to_string())));
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let Poll::Ready(Some(Err(first))) = buffer.poll_next(&mut cx) else {
+            panic!("the earlier error was lost");
+        };
+        assert_eq!(first.to_string(), "tool failed");
+        let Poll::Ready(Some(Err(shed))) = buffer.poll_next(&mut cx) else {
+            panic!("shedding didn't end the stream with an error");
+        };
+        assert!(matches!(
+            shed.downcast_ref(),
+            Some(ZeroError::FragmentsShed { bytes: 3 })
+        ));
+        assert!(matches!(buffer.poll_next(&mut cx), Poll::Ready(None)));
+    }
+
+    #[test]
+    fn test_sending_past_the_budget_asks_for_enforcement_once() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let budget = Arc::new(FragmentBudget::new(4, {
+            let requests = requests.clone();
+            move || {
+                requests.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+        let buffer = Arc::new(FragmentBuffer::new(Some(budget.clone())));
+        let sender = FragmentSender(buffer.clone());
+        sender.send(Ok("abc".to_string()));
+        assert_eq!(requests.load(Ordering::Relaxed), 0);
+        sender.send(Ok("de".to_string()));
+        sender.send(Ok("f".to_string()));
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+
+        buffer.shed();
+        budget.enforced(0);
+        drop(sender);
+        drop(buffer);
+        assert_eq!(budget.buffered.load(Ordering::Relaxed), 0);
+    }
+}
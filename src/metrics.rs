@@ -0,0 +1,178 @@
+//! Lifecycle counters reported through a pluggable [`MetricsSink`].
+
+use crate::models::DiscoveredService;
+use rmcp::service::QuitReason;
+use serde::Serialize;
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Receives service lifecycle events, e.g. to forward them to a metrics backend.
+///
+/// Methods are called synchronously on the manager's tasks, so they should be
+/// cheap; every method defaults to doing nothing.
+pub trait MetricsSink: Send + Sync {
+    /// A service was resolved and matched a mapping.
+    fn service_discovered(&self, _service: &DiscoveredService) {}
+
+    /// A service was launched, or relaunched after a crash, and is now active.
+    fn service_launched(&self, _service: &DiscoveredService) {}
+
+    /// A service failed to launch, crashed, or failed a health check.
+    fn service_failed(&self, _service_name: &str, _error: &anyhow::Error) {}
+
+    /// An active service was stopped.
+    fn service_stopped(&self, _service_name: &str, _reason: &QuitReason) {}
+
+    /// A cached listing was evicted, or a stream's unread fragments shed, to
+    /// stay within `memory_budget_bytes`, freeing about `bytes`.
+    fn memory_evicted(&self, _bytes: usize) {}
+
+    /// Returns the counters recorded so far, if this sink keeps any.
+    /// Backs `ZeroClient::metrics`.
+    fn snapshot(&self) -> Option<MetricsSnapshot> {
+        None
+    }
+}
+
+/// A sink that ignores every event. Used when no sink is configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl MetricsSink for NoopMetrics {}
+
+/// A sink that counts events in memory, readable via `ZeroClient::metrics`.
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    discovered: AtomicU64,
+    launched: AtomicU64,
+    failed: AtomicU64,
+    stopped: AtomicU64,
+    evictions: AtomicU64,
+    evicted_bytes: AtomicU64,
+}
+
+impl InMemoryMetrics {
+    /// Creates a sink with every counter at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MetricsSink for InMemoryMetrics {
+    fn service_discovered(&self, _service: &DiscoveredService) {
+        self.discovered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn service_launched(&self, _service: &DiscoveredService) {
+        self.launched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn service_failed(&self, _service_name: &str, _error: &anyhow::Error) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn service_stopped(&self, _service_name: &str, _reason: &QuitReason) {
+        self.stopped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn memory_evicted(&self, bytes: usize) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+        self.evicted_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Option<MetricsSnapshot> {
+        Some(MetricsSnapshot {
+            discovered: self.discovered.load(Ordering::Relaxed),
+            launched: self.launched.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            stopped: self.stopped.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            evicted_bytes: self.evicted_bytes.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Counters recorded by [`InMemoryMetrics`], as returned by `ZeroClient::metrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MetricsSnapshot {
+    /// Services resolved and matched to a mapping.
+    pub discovered: u64,
+    /// Successful launches, including relaunches after a crash.
+    pub launched: u64,
+    /// Launch failures, crashes and failed health checks.
+    pub failed: u64,
+    /// Active services that were stopped.
+    pub stopped: u64,
+    /// Cached listings evicted and streams shed under memory pressure.
+    pub evictions: u64,
+    /// Approximate bytes freed by those evictions.
+    pub evicted_bytes: u64,
+}
+
+/// The sink a client reports to, shared by every clone of it.
+#[derive(Clone)]
+pub(crate) struct Metrics(Arc<dyn MetricsSink>);
+
+impl Metrics {
+    pub(crate) fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Self(sink)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self(Arc::new(NoopMetrics))
+    }
+}
+
+impl Deref for Metrics {
+    type Target = dyn MetricsSink;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Metrics").field(&"<sink>").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_in_memory_metrics_count_events() {
+        let metrics = InMemoryMetrics::new();
+        let service = DiscoveredService::new_static("svc");
+        metrics.service_discovered(&service);
+        metrics.service_discovered(&service);
+        metrics.service_launched(&service);
+        metrics.service_failed("svc", &anyhow!("boom"));
+        metrics.service_stopped("svc", &QuitReason::Closed);
+        metrics.memory_evicted(100);
+
+        assert_eq!(
+            metrics.snapshot(),
+            Some(MetricsSnapshot {
+                discovered: 2,
+                launched: 1,
+                failed: 1,
+                stopped: 1,
+                evictions: 1,
+                evicted_bytes: 100,
+            })
+        );
+        assert_eq!(NoopMetrics.snapshot(), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! Periodic tool calls configured with `[[schedule]]`.
+
+use crate::{
+    ZeroHandler,
+    client::ZeroClient,
+    config::Schedule,
+    utils::{catch_panic, normalize_fullname},
+};
+use rmcp::model::CallToolRequestParam;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, error};
+
+/// Spawns one task per schedule, each calling its tool every interval and
+/// passing the result to `on_scheduled_result`.
+pub(crate) fn spawn(
+    schedules: &[Schedule],
+    client: &ZeroClient,
+    handler: &Arc<dyn ZeroHandler>,
+) -> Vec<JoinHandle<()>> {
+    schedules
+        .iter()
+        .map(|schedule| {
+            let schedule = schedule.clone();
+            let client = client.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move { run(schedule, client, handler).await })
+        })
+        .collect()
+}
+
+async fn run(schedule: Schedule, client: ZeroClient, handler: Arc<dyn ZeroHandler>) {
+    let service = normalize_fullname(&schedule.service);
+    let mut ticks = time::interval(Duration::from_millis(schedule.interval_ms.max(1)));
+    // A tick missed while a slow call was running is dropped, not made up.
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        ticks.tick().await;
+        let active = match client.active_service_infos().await {
+            Ok(services) => services.iter().any(|s| s.fullname == service),
+            Err(e) => {
+                error!(schedule = %schedule.id, error = %e, "Failed to check service for schedule");
+                continue;
+            }
+        };
+        if !active {
+            debug!(schedule = %schedule.id, service = %service, "Service not active; skipping scheduled call");
+            continue;
+        }
+        let params = CallToolRequestParam {
+            name: schedule.tool.clone().into(),
+            arguments: schedule.args.clone(),
+        };
+        let result = client.call_tool(service.as_str(), params).await;
+        if let Err(panic) = catch_panic(handler.on_scheduled_result(&schedule.id, result)).await {
+            error!(schedule = %schedule.id, panic = %panic, "on_scheduled_result panicked");
+        }
+    }
+}
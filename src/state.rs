@@ -0,0 +1,66 @@
+//! Serializable manager state, for restarting without waiting on rediscovery.
+
+use crate::{config::McpConfig, models::DiscoveredService};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// The logical state of a manager, as returned by `ZeroMcp::export_state`.
+///
+/// Live connections aren't part of it: `start_from_state` relaunches each
+/// service from its saved identity and configuration, rendering templates
+/// again with the saved inputs, and requesting any others.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManagerState {
+    pub services: Vec<SavedService>,
+    /// Template inputs remembered under `cache_inputs`, by service. These can
+    /// include secrets such as tokens, so store the state accordingly.
+    #[serde(default)]
+    pub inputs: HashMap<String, Map<String, Value>>,
+}
+
+/// A service that was active when the state was exported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedService {
+    /// The service as last resolved.
+    pub service: DiscoveredService,
+    /// The configuration it was launched with, after overrides and defaults.
+    /// Its proxy is left out, since it may carry credentials; a restored
+    /// service gets the one the current config sets for it.
+    pub config: McpConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ZeroConfig;
+
+    #[test]
+    fn test_state_round_trips_through_json() {
+        let toml_content = r#"
+            [[service_mapping]]
+            zeroconf_service = "_a._mcp._tcp.local."
+            protocol = "sse"
+            name = "A"
+            url = "http://{{service.hostname}}/sse"
+            tool_call_timeout_ms = 500
+        "#;
+        let config = ZeroConfig::from_reader(toml_content.as_bytes()).unwrap();
+        let state = ManagerState {
+            services: vec![SavedService {
+                service: DiscoveredService::new_static("svc"),
+                config: config.service_mappings[0].mcp.clone(),
+            }],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        let restored: ManagerState = serde_json::from_str(&json).unwrap();
+        let saved = &restored.services[0];
+        assert_eq!(saved.service.fullname, state.services[0].service.fullname);
+        assert!(
+            matches!(&saved.config, McpConfig::Sse { url, .. } if url == "http://{{service.hostname}}/sse")
+        );
+        assert_eq!(saved.config.options().tool_call_timeout_ms, Some(500));
+    }
+}
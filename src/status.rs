@@ -0,0 +1,302 @@
+//! A minimal built-in HTTP server exposing health, service and metrics endpoints.
+
+use crate::{client::ZeroClient, timing::STARTUP_BUCKETS_MS};
+use anyhow::Result;
+use std::{fmt::Write as _, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+    task::JoinHandle,
+};
+use tracing::{debug, info, warn};
+
+/// Upper bound on the size of a request head we are willing to buffer.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// How long a client gets to send its request head, and then to take the
+/// response, before its connection is dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many connections are served at once. Further ones wait to be accepted.
+const MAX_CONNECTIONS: usize = 64;
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+}
+
+/// Binds `addr` and serves status requests in the background until the
+/// returned task is aborted.
+pub(crate) async fn serve(addr: SocketAddr, client: ZeroClient) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(addr = %listener.local_addr()?, "Status server listening");
+
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    Ok(tokio::spawn(async move {
+        loop {
+            let Ok(permit) = connections.clone().acquire_owned().await else {
+                return;
+            };
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let client = client.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &client).await {
+                            debug!(peer = %peer, error = %e, "Status request failed");
+                        }
+                        drop(permit);
+                    });
+                }
+                Err(e) => warn!(error = %e, "Status server failed to accept connection"),
+            }
+        }
+    }))
+}
+
+/// Reads a request head, returning `None` if it exceeds `MAX_REQUEST_HEAD`.
+/// A connection closed early yields what was read so far.
+async fn read_head(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        if buf.len() + n > MAX_REQUEST_HEAD {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(Some(buf))
+}
+
+async fn handle_connection(mut stream: TcpStream, client: &ZeroClient) -> Result<()> {
+    let mut unread = false;
+    let response = match tokio::time::timeout(IO_TIMEOUT, read_head(&mut stream)).await {
+        Err(_) => Response::new("408 Request Timeout", "text/plain", "request timeout\n"),
+        Ok(head) => match head? {
+            Some(buf) => {
+                let head = String::from_utf8_lossy(&buf);
+                let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+                match (request_line.next(), request_line.next()) {
+                    (Some("GET"), Some(path)) => route(path, client).await,
+                    (Some(_), Some(_)) => Response::new(
+                        "405 Method Not Allowed",
+                        "text/plain",
+                        "method not allowed\n",
+                    ),
+                    _ => Response::new("400 Bad Request", "text/plain", "bad request\n"),
+                }
+            }
+            None => {
+                unread = true;
+                Response::new(
+                    "431 Request Header Fields Too Large",
+                    "text/plain",
+                    "request head too large\n",
+                )
+            }
+        },
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    let write = async {
+        stream.write_all(header.as_bytes()).await?;
+        stream.write_all(response.body.as_bytes()).await?;
+        stream.shutdown().await
+    };
+    tokio::time::timeout(IO_TIMEOUT, write)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out writing the response"))??;
+    if unread {
+        // Closing with the rest of the request unread would reset the
+        // connection, which can discard the response before the client reads it.
+        let mut sink = [0u8; 1024];
+        let drain = async {
+            while stream.read(&mut sink).await? > 0 {}
+            Ok::<_, std::io::Error>(())
+        };
+        let _ = tokio::time::timeout(IO_TIMEOUT, drain).await;
+    }
+    Ok(())
+}
+
+async fn route(path: &str, client: &ZeroClient) -> Response {
+    let path = path.split('?').next().unwrap_or(path);
+    match path {
+        "/healthz" => match client.active_service_infos().await {
+            Ok(_) => Response::new("200 OK", "text/plain", "ok\n"),
+            Err(e) => Response::new("503 Service Unavailable", "text/plain", format!("{}\n", e)),
+        },
+        "/services" => match client.active_service_infos().await {
+            Ok(services) => match serde_json::to_string(&services) {
+                Ok(body) => Response::new("200 OK", "application/json", body),
+                Err(e) => Response::new(
+                    "500 Internal Server Error",
+                    "text/plain",
+                    format!("{}\n", e),
+                ),
+            },
+            Err(e) => Response::new("503 Service Unavailable", "text/plain", format!("{}\n", e)),
+        },
+        "/metrics" => match render_metrics(client).await {
+            Ok(body) => Response::new("200 OK", "text/plain; version=0.0.4", body),
+            Err(e) => Response::new("503 Service Unavailable", "text/plain", format!("{}\n", e)),
+        },
+        _ => Response::new("404 Not Found", "text/plain", "not found\n"),
+    }
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+async fn render_metrics(client: &ZeroClient) -> Result<String> {
+    let services = client.active_service_infos().await?;
+    let memory = client.memory_stats().await?;
+    let startup = client.startup_histogram().await?;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "# HELP zeromcp_active_services Number of running MCP services."
+    )?;
+    writeln!(out, "# TYPE zeromcp_active_services gauge")?;
+    writeln!(out, "zeromcp_active_services {}", services.len())?;
+
+    writeln!(out, "# HELP zeromcp_service_info Running MCP services.")?;
+    writeln!(out, "# TYPE zeromcp_service_info gauge")?;
+    for service in &services {
+        writeln!(
+            out,
+            "zeromcp_service_info{{fullname=\"{}\",hostname=\"{}\",port=\"{}\"}} 1",
+            escape_label(&service.fullname),
+            escape_label(&service.hostname),
+            service.port
+        )?;
+    }
+
+    writeln!(
+        out,
+        "# HELP zeromcp_cache_bytes Approximate bytes held by cached listings."
+    )?;
+    writeln!(out, "# TYPE zeromcp_cache_bytes gauge")?;
+    writeln!(out, "zeromcp_cache_bytes {}", memory.cached_bytes)?;
+    writeln!(
+        out,
+        "# HELP zeromcp_cache_evictions_total Cached listings evicted to stay within the memory budget."
+    )?;
+    writeln!(out, "# TYPE zeromcp_cache_evictions_total counter")?;
+    writeln!(out, "zeromcp_cache_evictions_total {}", memory.evictions)?;
+
+    writeln!(
+        out,
+        "# HELP zeromcp_startup_phase_seconds Time spent in each phase of launching a service."
+    )?;
+    writeln!(out, "# TYPE zeromcp_startup_phase_seconds histogram")?;
+    for (phase, histogram) in startup.phases() {
+        for (bound, count) in STARTUP_BUCKETS_MS.iter().zip(histogram.buckets) {
+            writeln!(
+                out,
+                "zeromcp_startup_phase_seconds_bucket{{phase=\"{}\",le=\"{}\"}} {}",
+                phase,
+                *bound as f64 / 1000.0,
+                count
+            )?;
+        }
+        writeln!(
+            out,
+            "zeromcp_startup_phase_seconds_bucket{{phase=\"{}\",le=\"+Inf\"}} {}",
+            phase, histogram.count
+        )?;
+        writeln!(
+            out,
+            "zeromcp_startup_phase_seconds_sum{{phase=\"{}\"}} {}",
+            phase,
+            histogram.sum.as_secs_f64()
+        )?;
+        writeln!(
+            out,
+            "zeromcp_startup_phase_seconds_count{{phase=\"{}\"}} {}",
+            phase, histogram.count
+        )?;
+    }
+    Ok(out)
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::ZeroConfig,
+        manager::{ServiceActor, ServiceActorArgs},
+    };
+    use ractor::Actor;
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoints() {
+        let config: ZeroConfig = toml::from_str("").unwrap();
+        let (active_set, _) = tokio::sync::watch::channel(Vec::new());
+        let (discovery, _) = tokio::sync::mpsc::unbounded_channel();
+        let args = ServiceActorArgs {
+            config: config.clone(),
+            active_set,
+            discovery,
+            handler: Default::default(),
+            metrics: Default::default(),
+            liveness: Default::default(),
+        };
+        let (actor, _handle) = Actor::spawn(None, ServiceActor, args).await.unwrap();
+        let client = ZeroClient::new(actor, &config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let task = serve(addr, client).await.unwrap();
+
+        assert!(get(addr, "/healthz").await.starts_with("HTTP/1.1 200 OK"));
+        assert!(get(addr, "/services").await.ends_with("[]"));
+        assert!(
+            get(addr, "/metrics")
+                .await
+                .contains("zeromcp_active_services 0")
+        );
+        assert!(get(addr, "/nope").await.starts_with("HTTP/1.1 404"));
+        let long_path = format!("/{}", "a".repeat(MAX_REQUEST_HEAD));
+        assert!(get(addr, &long_path).await.starts_with("HTTP/1.1 431"));
+        task.abort();
+    }
+}
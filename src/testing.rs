@@ -0,0 +1,92 @@
+//! Helpers for running ZeroMCP without a network, e.g. in integration tests
+//! or offline demos. Enabled by the `testing` feature.
+
+use crate::mdns::MdnsBrowser;
+use anyhow::Result;
+use mdns_sd::{Receiver, ServiceEvent, ServiceInfo};
+use std::sync::Mutex;
+
+/// An [`MdnsBrowser`] that resolves a fixed set of services.
+///
+/// Each browse immediately yields a `ServiceResolved` event for every service
+/// of the browsed type or subtype, then stays quiet; nothing is ever removed. Browsing a
+/// type again, e.g. on a refresh, resolves its services again.
+///
+/// ```no_run
+/// # use mdns_sd::ServiceInfo;
+/// # use zeromcp::testing::StaticMdnsBrowser;
+/// # fn main() -> anyhow::Result<()> {
+/// let service = ServiceInfo::new("_mcp._tcp.local.", "demo", "demo.local.", "127.0.0.1", 8000, None)?;
+/// let browser = StaticMdnsBrowser::new(vec![service]);
+/// // zeromcp::start_with_mdns(config, make_handler, browser).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct StaticMdnsBrowser {
+    services: Vec<ServiceInfo>,
+    // Browse channels are kept open, as a disconnected one is browsed again.
+    senders: Mutex<Vec<flume::Sender<ServiceEvent>>>,
+}
+
+impl StaticMdnsBrowser {
+    /// Creates a browser that resolves `services`.
+    pub fn new(services: impl IntoIterator<Item = ServiceInfo>) -> Self {
+        Self {
+            services: services.into_iter().collect(),
+            senders: Mutex::default(),
+        }
+    }
+
+    /// Adds a service to resolve on later browses.
+    pub fn with_service(mut self, service: ServiceInfo) -> Self {
+        self.services.push(service);
+        self
+    }
+}
+
+impl MdnsBrowser for StaticMdnsBrowser {
+    fn browse(&self, service_type: &str) -> Result<Receiver<ServiceEvent>> {
+        let (tx, rx) = flume::unbounded();
+        let matches = |s: &&ServiceInfo| {
+            s.get_type() == service_type || s.get_subtype().as_deref() == Some(service_type)
+        };
+        for service in self.services.iter().filter(matches) {
+            tx.send(ServiceEvent::ServiceResolved(service.clone()))?;
+        }
+        self.senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(tx);
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(ty: &str, name: &str) -> ServiceInfo {
+        ServiceInfo::new(ty, name, "host.local.", "127.0.0.1", 8000, None).unwrap()
+    }
+
+    #[test]
+    fn test_static_browser_resolves_matching_types() {
+        let browser = StaticMdnsBrowser::new([service("_mcp._tcp.local.", "a")])
+            .with_service(service("_other._tcp.local.", "b"))
+            .with_service(service("_mcp._tcp.local.", "c"));
+
+        let rx = browser.browse("_mcp._tcp.local.").unwrap();
+        let names: Vec<String> = rx
+            .drain()
+            .map(|event| match event {
+                ServiceEvent::ServiceResolved(info) => info.get_fullname().to_string(),
+                other => panic!("unexpected event: {other:?}"),
+            })
+            .collect();
+        assert_eq!(names, ["a._mcp._tcp.local.", "c._mcp._tcp.local."]);
+        assert!(!rx.is_disconnected());
+
+        assert!(browser.browse("_none._tcp.local.").unwrap().is_empty());
+    }
+}
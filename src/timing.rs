@@ -0,0 +1,105 @@
+//! Per-phase launch timings and the histogram aggregating them across services.
+
+use serde::Serialize;
+use std::time::Duration;
+
+/// Upper bounds, in milliseconds, of the buckets in a [`PhaseHistogram`].
+/// Slower launches only count towards the implicit `+Inf` bucket.
+pub const STARTUP_BUCKETS_MS: [u64; 9] = [10, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// How long each phase of a service's launch took, as returned by
+/// `ZeroClient::startup_timings`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StartupTimings {
+    /// Building the client handler and rendering templates, including any
+    /// time spent waiting on user input and `on_launch`.
+    pub render: Duration,
+    /// Spawning the process, or opening the SSE stream.
+    pub connect: Duration,
+    /// The MCP `initialize` handshake.
+    pub initialize: Duration,
+    /// Checking the connection is stable and meets the mapping's requirements.
+    pub probe: Duration,
+}
+
+impl StartupTimings {
+    /// Returns the time from resolution to the service being ready.
+    pub fn total(&self) -> Duration {
+        self.render + self.connect + self.initialize + self.probe
+    }
+}
+
+/// A cumulative histogram of one launch phase's durations.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PhaseHistogram {
+    /// Launches at or below each bound of [`STARTUP_BUCKETS_MS`].
+    pub buckets: [u64; STARTUP_BUCKETS_MS.len()],
+    /// Total time spent in the phase.
+    pub sum: Duration,
+    /// Number of launches recorded.
+    pub count: u64,
+}
+
+impl PhaseHistogram {
+    fn record(&mut self, duration: Duration) {
+        let millis = duration.as_millis();
+        for (bucket, bound) in self.buckets.iter_mut().zip(STARTUP_BUCKETS_MS) {
+            if millis <= u128::from(bound) {
+                *bucket += 1;
+            }
+        }
+        self.sum += duration;
+        self.count += 1;
+    }
+}
+
+/// Launch phase durations across every service started so far, as returned
+/// by `ZeroClient::startup_histogram`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StartupHistogram {
+    pub render: PhaseHistogram,
+    pub connect: PhaseHistogram,
+    pub initialize: PhaseHistogram,
+    pub probe: PhaseHistogram,
+}
+
+impl StartupHistogram {
+    pub(crate) fn record(&mut self, timings: &StartupTimings) {
+        self.render.record(timings.render);
+        self.connect.record(timings.connect);
+        self.initialize.record(timings.initialize);
+        self.probe.record(timings.probe);
+    }
+
+    /// Returns each phase's histogram along with its name.
+    pub fn phases(&self) -> [(&'static str, &PhaseHistogram); 4] {
+        [
+            ("render", &self.render),
+            ("connect", &self.connect),
+            ("initialize", &self.initialize),
+            ("probe", &self.probe),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = StartupHistogram::default();
+        let timings = |connect_ms| StartupTimings {
+            connect: Duration::from_millis(connect_ms),
+            ..Default::default()
+        };
+        histogram.record(&timings(30));
+        histogram.record(&timings(700));
+        histogram.record(&timings(20_000));
+
+        assert_eq!(histogram.connect.buckets, [0, 1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(histogram.connect.count, 3);
+        assert_eq!(histogram.connect.sum, Duration::from_millis(20_730));
+        assert_eq!(histogram.render.buckets, [3; 9]);
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{Context, Result, bail};
+use rmcp::model::{CallToolResult, Content};
+use serde::de::DeserializeOwned;
+use std::{fmt, marker::PhantomData};
+
+/// Maps a raw `CallToolResult` onto a user-defined success/error pair.
+///
+/// Tools often return differently shaped content depending on whether they
+/// succeeded. Implement this trait to model both shapes; by default, results
+/// are dispatched on their `is_error` flag. Override [`decode`](Self::decode)
+/// to dispatch on the content blocks instead.
+///
+/// [`JsonDecoder`] covers the common case of a JSON document in a text block.
+pub trait ToolResultDecoder {
+    type Ok;
+    type Err;
+
+    /// Decodes the content of a successful result.
+    fn decode_ok(&self, content: &[Content]) -> Result<Self::Ok>;
+
+    /// Decodes the content of a result flagged with `is_error`.
+    fn decode_err(&self, content: &[Content]) -> Result<Self::Err>;
+
+    /// Decodes a whole result. `Err` only signals that decoding itself failed.
+    fn decode(&self, result: &CallToolResult) -> Result<std::result::Result<Self::Ok, Self::Err>> {
+        if result.is_error.unwrap_or(false) {
+            self.decode_err(&result.content).map(Err)
+        } else {
+            self.decode_ok(&result.content).map(Ok)
+        }
+    }
+}
+
+/// Decodes results whose text content is JSON into `T`, and error results into `E`.
+///
+/// Text that isn't valid JSON is decoded as a JSON string, so `E = String`
+/// accepts plain-text error messages.
+pub struct JsonDecoder<T, E = String>(PhantomData<fn() -> (T, E)>);
+
+impl<T, E> JsonDecoder<T, E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T, E> Default for JsonDecoder<T, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, E> fmt::Debug for JsonDecoder<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonDecoder").finish()
+    }
+}
+
+impl<T: DeserializeOwned, E: DeserializeOwned> ToolResultDecoder for JsonDecoder<T, E> {
+    type Ok = T;
+    type Err = E;
+
+    fn decode_ok(&self, content: &[Content]) -> Result<T> {
+        parse_json_content(content)
+    }
+
+    fn decode_err(&self, content: &[Content]) -> Result<E> {
+        parse_json_content(content)
+    }
+}
+
+/// Deserializes the text blocks of a tool result, joined, as JSON.
+pub fn parse_json_content<T: DeserializeOwned>(content: &[Content]) -> Result<T> {
+    let texts: Vec<&str> = content
+        .iter()
+        .filter_map(|c| c.as_text().map(|t| t.text.as_str()))
+        .collect();
+    if texts.is_empty() {
+        bail!("Tool result has no text content to decode");
+    }
+    let text = texts.concat();
+    match serde_json::from_str(&text) {
+        Ok(value) => Ok(value),
+        Err(json_err) => serde_json::from_value(serde_json::Value::String(text))
+            .map_err(|_| json_err)
+            .context("Failed to decode tool result as JSON"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Forecast {
+        temp: i32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct ApiError {
+        code: u16,
+    }
+
+    #[test]
+    fn test_json_decoder_dispatches_on_is_error() {
+        let decoder = JsonDecoder::<Forecast, ApiError>::new();
+
+        let ok = CallToolResult::success(vec![Content::text(r#"{"temp": 21}"#)]);
+        assert_eq!(decoder.decode(&ok).unwrap(), Ok(Forecast { temp: 21 }));
+
+        let err = CallToolResult::error(vec![Content::text(r#"{"code": 404}"#)]);
+        assert_eq!(decoder.decode(&err).unwrap(), Err(ApiError { code: 404 }));
+
+        let plain = CallToolResult::error(vec![Content::text("city not found")]);
+        let decoder = JsonDecoder::<Forecast>::new();
+        assert_eq!(
+            decoder.decode(&plain).unwrap(),
+            Err("city not found".to_string())
+        );
+
+        let garbage = CallToolResult::success(vec![Content::text("not json")]);
+        assert!(decoder.decode(&garbage).is_err());
+    }
+}
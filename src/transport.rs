@@ -0,0 +1,399 @@
+use crate::models::{TrafficDirection, TrafficEvent};
+use anyhow::{Result, bail};
+use futures::{StreamExt, stream::FuturesUnordered};
+use rmcp::{
+    RoleClient,
+    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
+    transport::{
+        Transport,
+        common::client_side_sse::BoxedSseResponse,
+        sse_client::{SseClient, SseTransportError},
+    },
+};
+use std::{
+    fmt::Display,
+    future::Future,
+    io,
+    net::IpAddr,
+    pin::Pin,
+    process::Stdio,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, ReadBuf},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command},
+    sync::{Mutex, broadcast},
+};
+
+/// Query parameters servers commonly use to carry the SSE session identifier.
+const SESSION_ID_PARAMS: &[&str] = &["sessionId", "session_id"];
+
+/// An SSE client that records the session ID from the message endpoint the
+/// server hands out, so it can be surfaced for log correlation.
+#[derive(Clone, Debug)]
+pub(crate) struct SessionTrackingClient {
+    inner: reqwest::Client,
+    session_id: Arc<OnceLock<String>>,
+}
+
+impl SessionTrackingClient {
+    pub(crate) fn new(inner: reqwest::Client) -> Self {
+        Self {
+            inner,
+            session_id: Arc::default(),
+        }
+    }
+
+    /// Returns the session ID once the first message has been posted.
+    pub(crate) fn session_id(&self) -> Option<String> {
+        self.session_id.get().cloned()
+    }
+}
+
+fn session_id_from_uri(uri: &http::Uri) -> Option<String> {
+    uri.query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        SESSION_ID_PARAMS.contains(&key).then(|| value.to_string())
+    })
+}
+
+impl SseClient for SessionTrackingClient {
+    type Error = reqwest::Error;
+
+    async fn post_message(
+        &self,
+        uri: http::Uri,
+        message: ClientJsonRpcMessage,
+        auth_token: Option<String>,
+    ) -> Result<(), SseTransportError<Self::Error>> {
+        if self.session_id.get().is_none()
+            && let Some(id) = session_id_from_uri(&uri)
+        {
+            let _ = self.session_id.set(id);
+        }
+        self.inner.post_message(uri, message, auth_token).await
+    }
+
+    async fn get_stream(
+        &self,
+        uri: http::Uri,
+        last_event_id: Option<String>,
+        auth_token: Option<String>,
+    ) -> Result<BoxedSseResponse, SseTransportError<Self::Error>> {
+        self.inner.get_stream(uri, last_event_id, auth_token).await
+    }
+}
+
+/// How many traffic events a slow tap may fall behind before it misses some.
+pub(crate) const TRAFFIC_TAP_CAPACITY: usize = 256;
+
+/// Wraps a transport to publish every message it carries to `tap`.
+///
+/// Messages are only serialized for the tap while someone is subscribed.
+pub(crate) struct TappedTransport<T> {
+    inner: T,
+    tap: broadcast::Sender<TrafficEvent>,
+}
+
+impl<T> TappedTransport<T> {
+    pub(crate) fn new(inner: T, tap: broadcast::Sender<TrafficEvent>) -> Self {
+        Self { inner, tap }
+    }
+
+    fn publish<M: serde::Serialize>(&self, direction: TrafficDirection, message: &M) {
+        if self.tap.receiver_count() > 0 {
+            let _ = self.tap.send(TrafficEvent::new(direction, message));
+        }
+    }
+}
+
+impl<T: Transport<RoleClient>> Transport<RoleClient> for TappedTransport<T> {
+    type Error = T::Error;
+
+    fn send(
+        &mut self,
+        item: ClientJsonRpcMessage,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send + 'static {
+        self.publish(TrafficDirection::Outgoing, &item);
+        self.inner.send(item)
+    }
+
+    async fn receive(&mut self) -> Option<ServerJsonRpcMessage> {
+        let message = self.inner.receive().await?;
+        self.publish(TrafficDirection::Incoming, &message);
+        Some(message)
+    }
+
+    fn close(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        self.inner.close()
+    }
+}
+
+/// A child process's stdout, which shares ownership of the child so that it
+/// is killed once the transport reading from it and every [`ChildHandle`]
+/// are dropped.
+pub(crate) struct ChildOutput {
+    child: ChildHandle,
+    id: Option<u32>,
+    stdout: ChildStdout,
+}
+
+impl ChildOutput {
+    pub(crate) fn id(&self) -> Option<u32> {
+        self.id
+    }
+
+    /// Returns a handle for signalling the child directly.
+    pub(crate) fn handle(&self) -> ChildHandle {
+        self.child.clone()
+    }
+}
+
+/// Shared ownership of a stdio service's child process. Signals go through
+/// the owned `Child`, which isn't reaped until it is waited for, so they
+/// can't reach a process that reused its PID.
+#[derive(Debug, Clone)]
+pub(crate) struct ChildHandle(Arc<Mutex<Child>>);
+
+impl ChildHandle {
+    /// Asks the process to exit with SIGTERM. Windows has no equivalent, so
+    /// there the process is killed outright.
+    pub(crate) async fn terminate(&self) -> io::Result<()> {
+        let mut child = self.0.lock().await;
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        #[cfg(unix)]
+        if let Some(pid) = child.id() {
+            // SAFETY: `kill` has no memory-safety requirements, and the PID
+            // is still this child's, as it hasn't been reaped.
+            if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        #[cfg(not(unix))]
+        child.start_kill()?;
+        Ok(())
+    }
+
+    /// Waits for the process to exit.
+    pub(crate) async fn wait(&self) -> io::Result<()> {
+        self.0.lock().await.wait().await.map(|_| ())
+    }
+
+    /// Kills the process and waits for it to exit.
+    pub(crate) async fn kill(&self) -> io::Result<()> {
+        self.0.lock().await.kill().await
+    }
+}
+
+impl AsyncRead for ChildOutput {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stdout).poll_read(cx, buf)
+    }
+}
+
+/// Spawns `command` with every stdio stream piped, returning the halves of
+/// its MCP transport along with its stderr.
+pub(crate) fn spawn_child(
+    mut command: Command,
+) -> io::Result<(ChildOutput, ChildStdin, ChildStderr)> {
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = command.spawn()?;
+    let taken = |name| io::Error::other(format!("{} was not piped", name));
+    let stdin = child.stdin.take().ok_or_else(|| taken("stdin"))?;
+    let stdout = child.stdout.take().ok_or_else(|| taken("stdout"))?;
+    let stderr = child.stderr.take().ok_or_else(|| taken("stderr"))?;
+    let output = ChildOutput {
+        id: child.id(),
+        child: ChildHandle(Arc::new(Mutex::new(child))),
+        stdout,
+    };
+    Ok((output, stdin, stderr))
+}
+
+/// Builds a proxy applied to every request, bypassed for hosts matching `no_proxy`
+/// (or the `NO_PROXY` environment variable when unset).
+pub(crate) fn build_proxy(
+    url: &str,
+    credentials: Option<(&str, &str)>,
+    no_proxy: Option<&str>,
+) -> Result<reqwest::Proxy> {
+    let mut proxy = reqwest::Proxy::all(url)?;
+    if let Some((username, password)) = credentials {
+        proxy = proxy.basic_auth(username, password);
+    }
+    let no_proxy = match no_proxy {
+        Some(list) => reqwest::NoProxy::from_string(list),
+        None => reqwest::NoProxy::from_env(),
+    };
+    Ok(proxy.no_proxy(no_proxy))
+}
+
+/// How long a connection attempt runs alone before the next address is tried
+/// in parallel, per RFC 8305.
+pub(crate) const HAPPY_EYEBALLS_HEAD_START: Duration = Duration::from_millis(250);
+
+/// Orders advertised addresses for connection attempts, alternating IPv6 and
+/// IPv4 starting with IPv6. Entries that aren't IP addresses are dropped.
+pub(crate) fn happy_eyeballs_order(addresses: &[String]) -> Vec<IpAddr> {
+    let (mut v6, mut v4): (Vec<IpAddr>, Vec<IpAddr>) = addresses
+        .iter()
+        .filter_map(|addr| addr.parse().ok())
+        .partition(IpAddr::is_ipv6);
+    v6.dedup();
+    v4.dedup();
+
+    let mut ordered = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Formats an address for use as the host part of a URL.
+pub(crate) fn url_host(addr: &IpAddr) -> String {
+    match addr {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{}]", v6),
+    }
+}
+
+/// Races `connect` over `candidates`, starting the next attempt whenever the
+/// previous ones have had `head_start` to finish or have all failed.
+///
+/// Returns the first candidate that connects along with its connection;
+/// outstanding attempts are dropped. Fails only once every candidate has failed.
+pub(crate) async fn connect_first<C, T, F, Fut>(
+    candidates: Vec<C>,
+    head_start: Duration,
+    mut connect: F,
+) -> Result<(C, T)>
+where
+    C: Clone + Display,
+    F: FnMut(C) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut pending = candidates.into_iter();
+    let mut in_flight = FuturesUnordered::new();
+    let mut errors = Vec::new();
+
+    let mut start = |candidate: C, in_flight: &mut FuturesUnordered<_>| {
+        let attempt = connect(candidate.clone());
+        in_flight.push(async move { (candidate, attempt.await) });
+    };
+
+    loop {
+        if in_flight.is_empty() {
+            match pending.next() {
+                Some(candidate) => start(candidate, &mut in_flight),
+                None => break,
+            }
+        }
+        tokio::select! {
+            Some((candidate, result)) = in_flight.next() => match result {
+                Ok(conn) => return Ok((candidate, conn)),
+                Err(e) => errors.push(format!("{}: {:#}", candidate, e)),
+            },
+            _ = tokio::time::sleep(head_start), if pending.len() > 0 => {
+                if let Some(candidate) = pending.next() {
+                    start(candidate, &mut in_flight);
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        bail!("No addresses to connect to");
+    }
+    bail!("Every address failed to connect: {}", errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_child_separates_stderr() {
+        use tokio::io::AsyncReadExt;
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2"]);
+        let (mut output, _stdin, mut stderr) = spawn_child(command).unwrap();
+        assert!(output.id().is_some());
+
+        let (mut out, mut err) = (String::new(), String::new());
+        output.read_to_string(&mut out).await.unwrap();
+        stderr.read_to_string(&mut err).await.unwrap();
+        assert_eq!((out.as_str(), err.as_str()), ("out\n", "err\n"));
+    }
+
+    #[test]
+    fn test_session_id_from_uri() {
+        let uri: http::Uri = "http://host:8080/message?sessionId=abc123".parse().unwrap();
+        assert_eq!(session_id_from_uri(&uri).as_deref(), Some("abc123"));
+
+        let uri: http::Uri = "http://host/messages/?foo=1&session_id=xyz"
+            .parse()
+            .unwrap();
+        assert_eq!(session_id_from_uri(&uri).as_deref(), Some("xyz"));
+
+        let uri: http::Uri = "http://host/message".parse().unwrap();
+        assert_eq!(session_id_from_uri(&uri), None);
+    }
+
+    #[test]
+    fn test_happy_eyeballs_order_interleaves_families() {
+        let addresses = vec![
+            "10.0.0.1".to_string(),
+            "10.0.0.2".to_string(),
+            "fe80::1".to_string(),
+            "not-an-ip".to_string(),
+        ];
+        let ordered: Vec<String> = happy_eyeballs_order(&addresses)
+            .iter()
+            .map(url_host)
+            .collect();
+        assert_eq!(ordered, vec!["[fe80::1]", "10.0.0.1", "10.0.0.2"]);
+    }
+
+    #[tokio::test]
+    async fn test_connect_first_falls_back_past_unreachable_address() {
+        let head_start = Duration::from_millis(10);
+        let (winner, conn) = connect_first(
+            vec!["unreachable", "refused", "ok"],
+            head_start,
+            |candidate| async move {
+                match candidate {
+                    "unreachable" => std::future::pending().await,
+                    "refused" => bail!("connection refused"),
+                    _ => Ok(candidate.len()),
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!((winner, conn), ("ok", 2));
+
+        let err = connect_first(vec!["a"], head_start, |_| async {
+            Err::<(), _>(anyhow::anyhow!("connection refused"))
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("a: connection refused"));
+    }
+}
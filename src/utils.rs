@@ -1,14 +1,240 @@
-use std::collections::HashMap;
+use std::{any::Any, future::Future, panic::AssertUnwindSafe};
 
-use anyhow::Result;
+use crate::config::DuplicateToolPolicy;
+use anyhow::{Result, bail};
+use futures::{FutureExt, StreamExt, stream};
+use indexmap::{IndexMap, map::Entry};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rmcp::model::Tool;
+use tracing::warn;
 
-pub fn hashmap_to_header_map(raw_map: &HashMap<String, String>) -> Result<HeaderMap> {
+/// Maximum length of a DNS name in its textual form, per RFC 1035.
+pub const MAX_FULLNAME_LEN: usize = 255;
+/// Maximum length of a single DNS label.
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Normalizes a service fullname so lookups are insensitive to surrounding
+/// whitespace and to the presence of the trailing dot.
+pub fn normalize_fullname(name: &str) -> String {
+    let trimmed = name.trim();
+    if trimmed.ends_with('.') {
+        trimmed.to_string()
+    } else {
+        format!("{}.", trimmed)
+    }
+}
+
+/// Normalizes a service fullname and rejects names that exceed DNS limits.
+pub fn validate_fullname(name: &str) -> Result<String> {
+    let normalized = normalize_fullname(name);
+    if normalized.len() > MAX_FULLNAME_LEN {
+        bail!(
+            "Service fullname is {} bytes long, exceeding the {} byte limit",
+            normalized.len(),
+            MAX_FULLNAME_LEN
+        );
+    }
+    // The instance label may itself contain dots, so only the service type
+    // labels are held to the per-label limit.
+    if let Some(label) = normalized
+        .trim_end_matches('.')
+        .rsplit('.')
+        .take(3)
+        .find(|label| label.len() > MAX_LABEL_LEN)
+    {
+        bail!(
+            "Service fullname label '{}' exceeds the {} byte limit",
+            label,
+            MAX_LABEL_LEN
+        );
+    }
+    Ok(normalized)
+}
+
+/// Builds a header map from rendered header values, preserving their order.
+pub fn to_header_map(raw_map: &IndexMap<String, String>) -> Result<HeaderMap> {
     let mut hdrs = HeaderMap::new();
     for (name_str, value_str) in raw_map {
         let name = HeaderName::from_bytes(name_str.as_bytes())?;
-        let value = HeaderValue::from_str(&value_str)?;
+        let value = HeaderValue::from_str(value_str)?;
         hdrs.insert(name, value);
     }
     Ok(hdrs)
 }
+
+/// Leaves one tool per name in a listing, chosen by `policy`, warning about
+/// any names the server repeated.
+pub fn dedup_tools(tools: Vec<Tool>, policy: DuplicateToolPolicy) -> Result<Vec<Tool>> {
+    let mut unique: IndexMap<String, Tool> = IndexMap::with_capacity(tools.len());
+    let mut duplicates = Vec::new();
+    for tool in tools {
+        match unique.entry(tool.name.to_string()) {
+            Entry::Vacant(entry) => {
+                entry.insert(tool);
+            }
+            Entry::Occupied(mut entry) => {
+                duplicates.push(entry.key().clone());
+                if policy == DuplicateToolPolicy::Last {
+                    entry.insert(tool);
+                }
+            }
+        }
+    }
+    if !duplicates.is_empty() {
+        duplicates.sort();
+        duplicates.dedup();
+        if policy == DuplicateToolPolicy::Error {
+            bail!("Server lists duplicate tools: {}", duplicates.join(", "));
+        }
+        warn!(tools = ?duplicates, policy = ?policy, "Server lists duplicate tools");
+    }
+    Ok(unique.into_values().collect())
+}
+
+/// Matches `text` against a simple glob `pattern`, where `*` matches any run
+/// of characters (including none) and every other character matches itself.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one item.
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard at all: require an exact match.
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Runs `f` for every item with at most `concurrency` futures in flight,
+/// collecting every output in completion order.
+pub async fn bounded_fan_out<I, F, Fut>(items: I, concurrency: usize, f: F) -> Vec<Fut::Output>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future,
+{
+    stream::iter(items)
+        .map(f)
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Awaits a future, converting any panic it raises into an error message.
+pub async fn catch_panic<F: Future>(fut: F) -> std::result::Result<F::Output, String> {
+    AssertUnwindSafe(fut)
+        .catch_unwind()
+        .await
+        .map_err(|payload| panic_message(payload.as_ref()))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_fullname_trailing_dot() {
+        assert_eq!(
+            normalize_fullname("X._mcp._tcp.local"),
+            normalize_fullname("X._mcp._tcp.local.")
+        );
+        assert_eq!(
+            normalize_fullname(" X._mcp._tcp.local. "),
+            "X._mcp._tcp.local."
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("search", "search"));
+        assert!(!glob_match("search", "search_web"));
+        assert!(glob_match("search*", "search_web"));
+        assert!(glob_match("*_web", "search_web"));
+        assert!(glob_match("s*r*h", "search"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("a*b", "ab_c"));
+    }
+
+    #[test]
+    fn test_dedup_tools() {
+        let tool = |name: &str, description: &str| {
+            Tool::new(
+                name.to_string(),
+                description.to_string(),
+                std::sync::Arc::new(Default::default()),
+            )
+        };
+        let tools = vec![tool("a", "first"), tool("b", ""), tool("a", "second")];
+        let descriptions = |policy| {
+            dedup_tools(tools.clone(), policy)
+                .unwrap()
+                .into_iter()
+                .map(|t| format!("{}:{}", t.name, t.description.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(descriptions(DuplicateToolPolicy::First), ["a:first", "b:"]);
+        assert_eq!(descriptions(DuplicateToolPolicy::Last), ["a:second", "b:"]);
+        assert!(dedup_tools(tools, DuplicateToolPolicy::Error).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic_reports_message() {
+        assert_eq!(catch_panic(async { 42 }).await, Ok(42));
+        let result = catch_panic(async { panic!("handler blew up") }).await;
+        assert_eq!(result, Err("handler blew up".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_bounded_fan_out_limits_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let mut outputs = bounded_fan_out(0..20, 3, |i| {
+            let (in_flight, peak) = (&in_flight, &peak);
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        })
+        .await;
+
+        outputs.sort();
+        assert_eq!(outputs, (0..20).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_validate_fullname_rejects_overlong_names() {
+        assert!(validate_fullname("X._mcp._tcp.local").is_ok());
+
+        let long_instance = format!("{}._mcp._tcp.local.", "a".repeat(300));
+        assert!(validate_fullname(&long_instance).is_err());
+
+        let long_type = format!("X._{}._tcp.local.", "b".repeat(70));
+        assert!(validate_fullname(&long_type).is_err());
+    }
+}